@@ -0,0 +1,618 @@
+//! Beat and onset detection
+//!
+//! Provides the rhythm analysis that drives the visualizers:
+//! - `OnsetDetector` finds note onsets with spectral flux and an adaptive threshold
+//! - `TempoTracker` estimates the tempo from inter-onset intervals and predicts beats
+//! - `BeatTracker` combines both and is fed one spectrum at a time
+//! - `BeatGrid` runs the same tracker offline over a whole song
+//!
+//! All timing is based on playback time rather than frame count, so the
+//! results do not depend on the video frame rate or the offline hop size.
+//!
+//! A song's beat grid is cached on disk next to its waveform peaks, so
+//! reopening the song does not have to analyze it again.
+
+use super::{FFT_SIZE, SourceStamp, SpectrumAnalyzer};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Length of the flux history used for the adaptive threshold, in seconds
+const THRESHOLD_WINDOW: f64 = 1.0;
+/// Factor applied to the median flux to get the onset threshold
+const THRESHOLD_MULTIPLIER: f32 = 1.5;
+/// Constant added to the threshold so near-silence does not trigger onsets
+const THRESHOLD_OFFSET: f32 = 0.02;
+/// Shortest time between two onsets, in seconds
+const MIN_ONSET_INTERVAL: f64 = 0.1;
+/// Scale of the log compression applied to magnitudes before computing flux
+const COMPRESSION: f32 = 100.0;
+
+/// Length of the onset history used for tempo estimation, in seconds
+const TEMPO_WINDOW: f64 = 8.0;
+/// Slowest tempo reported; slower estimates are doubled into range
+const MIN_BPM: f32 = 80.0;
+/// Fastest tempo reported; faster estimates are halved into range
+const MAX_BPM: f32 = 160.0;
+/// Spread of each interval's vote in the tempo histogram, in BPM
+const BPM_SPREAD: f32 = 2.0;
+/// Fraction of a beat period within which an onset re-aligns the beat phase
+const PHASE_TOLERANCE: f64 = 0.2;
+
+/// Gap in playback time that is treated as a seek, in seconds
+const SEEK_THRESHOLD: f64 = 0.5;
+/// Distance between offline analysis windows, in frames
+const OFFLINE_HOP: usize = 512;
+
+/// Rhythm information for a single analysis step
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BeatState {
+    /// Strength of an onset detected in this step (flux relative to threshold)
+    pub onset: Option<f32>,
+    /// Whether a beat fell in this step
+    pub beat: bool,
+    /// Current tempo estimate in beats per minute
+    pub bpm: Option<f32>,
+}
+
+/// Detects note onsets through positive spectral flux
+///
+/// An onset is reported when the flux rises above a threshold that follows the
+/// median flux of the last second, so loud and quiet passages both work.
+pub struct OnsetDetector {
+    /// Compressed magnitudes of the previous spectrum
+    previous: Vec<f32>,
+    /// Recent `(time, flux)` pairs used for the adaptive threshold
+    flux_history: VecDeque<(f64, f32)>,
+    /// Time of the last reported onset
+    last_onset: Option<f64>,
+}
+
+impl OnsetDetector {
+    /// Creates a detector with no history
+    pub fn new() -> Self {
+        OnsetDetector {
+            previous: Vec::new(),
+            flux_history: VecDeque::new(),
+            last_onset: None,
+        }
+    }
+
+    /// Forgets all history, used after seeks
+    pub fn reset(&mut self) {
+        self.previous.clear();
+        self.flux_history.clear();
+        self.last_onset = None;
+    }
+
+    /// Processes one spectrum
+    ///
+    /// # Arguments
+    /// * `spectrum` - Magnitude spectrum in dBFS
+    /// * `time` - Playback time of the spectrum in seconds
+    ///
+    /// # Returns
+    /// The onset strength (flux divided by threshold) if an onset was detected
+    pub fn process(&mut self, spectrum: &[f32], time: f64) -> Option<f32> {
+        let compressed = spectrum
+            .iter()
+            .map(|db| (1.0 + COMPRESSION * 10f32.powf(db / 20.0)).ln());
+
+        // The first spectrum after a reset only primes the history
+        if self.previous.len() != spectrum.len() {
+            self.previous = compressed.collect();
+            return None;
+        }
+
+        let mut flux = 0.0;
+        for (previous, current) in self.previous.iter_mut().zip(compressed) {
+            flux += (current - *previous).max(0.0);
+            *previous = current;
+        }
+        flux /= spectrum.len() as f32;
+
+        self.flux_history.push_back((time, flux));
+        while let Some(&(oldest, _)) = self.flux_history.front() {
+            if time - oldest <= THRESHOLD_WINDOW {
+                break;
+            }
+            self.flux_history.pop_front();
+        }
+
+        let threshold = self.median_flux() * THRESHOLD_MULTIPLIER + THRESHOLD_OFFSET;
+        let rested = self
+            .last_onset
+            .is_none_or(|last| time - last >= MIN_ONSET_INTERVAL);
+
+        if flux > threshold && rested {
+            self.last_onset = Some(time);
+            Some(flux / threshold)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the median of the flux history
+    fn median_flux(&self) -> f32 {
+        let mut values: Vec<f32> = self.flux_history.iter().map(|&(_, flux)| flux).collect();
+        if values.is_empty() {
+            return 0.0;
+        }
+        let middle = values.len() / 2;
+        *values
+            .select_nth_unstable_by(middle, |a, b| a.total_cmp(b))
+            .1
+    }
+}
+
+/// Estimates tempo from onsets and predicts where beats fall
+///
+/// Every pair of recent onsets votes for the tempo implied by its interval
+/// (folded into `MIN_BPM..MAX_BPM`). The winning tempo sets the beat period,
+/// and onsets close to a predicted beat pull the beat phase onto them.
+pub struct TempoTracker {
+    /// Onset times within the last `TEMPO_WINDOW` seconds
+    onsets: VecDeque<f64>,
+    /// Current tempo estimate
+    bpm: Option<f32>,
+    /// Predicted time of the next beat
+    next_beat: Option<f64>,
+}
+
+impl TempoTracker {
+    /// Creates a tracker with no tempo estimate
+    pub fn new() -> Self {
+        TempoTracker {
+            onsets: VecDeque::new(),
+            bpm: None,
+            next_beat: None,
+        }
+    }
+
+    /// Forgets all history, used after seeks
+    pub fn reset(&mut self) {
+        self.onsets.clear();
+        self.bpm = None;
+        self.next_beat = None;
+    }
+
+    /// Returns the current tempo estimate in beats per minute
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    /// Records an onset and refreshes the tempo estimate
+    ///
+    /// # Arguments
+    /// * `time` - Playback time of the onset in seconds
+    pub fn add_onset(&mut self, time: f64) {
+        self.onsets.push_back(time);
+        while let Some(&oldest) = self.onsets.front() {
+            if time - oldest <= TEMPO_WINDOW {
+                break;
+            }
+            self.onsets.pop_front();
+        }
+
+        if let Some(estimate) = self.estimate_bpm() {
+            self.bpm = Some(match self.bpm {
+                // Smooth small drifts, but follow real tempo changes right away
+                Some(bpm) if (estimate - bpm).abs() < bpm * 0.05 => bpm * 0.8 + estimate * 0.2,
+                _ => estimate,
+            });
+        }
+
+        // Pull the beat phase onto onsets that land near a predicted beat
+        if let (Some(bpm), Some(next_beat)) = (self.bpm, self.next_beat) {
+            let period = 60.0 / bpm as f64;
+            let tolerance = period * PHASE_TOLERANCE;
+            if (next_beat - time).abs() < tolerance {
+                // The upcoming beat is due now; let `advance` report it on the onset
+                self.next_beat = Some(time);
+            } else if (time - (next_beat - period)).abs() < tolerance {
+                // The previous beat already fired slightly early or late
+                self.next_beat = Some(time + period);
+            }
+        } else if let Some(bpm) = self.bpm {
+            self.next_beat = Some(time + 60.0 / bpm as f64);
+        }
+    }
+
+    /// Advances playback time
+    ///
+    /// # Arguments
+    /// * `time` - Current playback time in seconds
+    ///
+    /// # Returns
+    /// `true` if a predicted beat was passed since the last call
+    pub fn advance(&mut self, time: f64) -> bool {
+        let (Some(bpm), Some(next_beat)) = (self.bpm, self.next_beat) else {
+            return false;
+        };
+        if time < next_beat {
+            return false;
+        }
+
+        // Skip any beats missed during long frames
+        let period = 60.0 / bpm as f64;
+        let missed = ((time - next_beat) / period).floor() + 1.0;
+        self.next_beat = Some(next_beat + missed * period);
+        true
+    }
+
+    /// Builds a tempo histogram from onset intervals and returns its peak
+    fn estimate_bpm(&self) -> Option<f32> {
+        let bins = (MAX_BPM - MIN_BPM) as usize;
+        let mut histogram = vec![0.0f32; bins];
+        let min_interval = 60.0 / (2.0 * MAX_BPM as f64);
+        let max_interval = 2.0 * 60.0 / MIN_BPM as f64;
+
+        for (i, &earlier) in self.onsets.iter().enumerate() {
+            for (distance, &later) in self.onsets.iter().skip(i + 1).enumerate() {
+                let interval = later - earlier;
+                if interval > max_interval {
+                    break;
+                }
+                if interval < min_interval {
+                    continue;
+                }
+
+                let mut bpm = (60.0 / interval) as f32;
+                while bpm < MIN_BPM {
+                    bpm *= 2.0;
+                }
+                while bpm >= MAX_BPM {
+                    bpm /= 2.0;
+                }
+
+                // Neighbouring onsets are stronger evidence than distant ones
+                let weight = 1.0 / (distance + 1) as f32;
+                for (bin, votes) in histogram.iter_mut().enumerate() {
+                    let delta = (MIN_BPM + bin as f32 - bpm) / BPM_SPREAD;
+                    *votes += weight * (-0.5 * delta * delta).exp();
+                }
+            }
+        }
+
+        let (bin, &votes) = histogram
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))?;
+        (votes > 0.0).then_some(MIN_BPM + bin as f32)
+    }
+}
+
+/// Real-time beat tracker combining onset detection and tempo tracking
+pub struct BeatTracker {
+    /// Finds onsets in each spectrum
+    onsets: OnsetDetector,
+    /// Turns onsets into a tempo and beat predictions
+    tempo: TempoTracker,
+    /// Playback time of the previous step, used to detect seeks
+    last_time: Option<f64>,
+}
+
+impl BeatTracker {
+    /// Creates a tracker with no history
+    pub fn new() -> Self {
+        BeatTracker {
+            onsets: OnsetDetector::new(),
+            tempo: TempoTracker::new(),
+            last_time: None,
+        }
+    }
+
+    /// Analyzes one spectrum
+    ///
+    /// Jumps in playback time (seeking, switching songs) reset the history.
+    ///
+    /// # Arguments
+    /// * `spectrum` - Magnitude spectrum in dBFS
+    /// * `time` - Playback time of the spectrum in seconds
+    ///
+    /// # Returns
+    /// Onset, beat and tempo information for this step
+    pub fn process(&mut self, spectrum: &[f32], time: f64) -> BeatState {
+        if let Some(last_time) = self.last_time
+            && (time < last_time || time - last_time > SEEK_THRESHOLD)
+        {
+            self.onsets.reset();
+            self.tempo.reset();
+        }
+        self.last_time = Some(time);
+
+        let onset = self.onsets.process(spectrum, time);
+        if onset.is_some() {
+            self.tempo.add_onset(time);
+        }
+
+        BeatState {
+            onset,
+            beat: self.tempo.advance(time),
+            bpm: self.tempo.bpm(),
+        }
+    }
+}
+
+/// Beat times for a whole song, computed ahead of playback
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BeatGrid {
+    /// Source file version, used to invalidate stale caches
+    source: Option<SourceStamp>,
+    /// Tempo at the end of the song in beats per minute
+    pub bpm: Option<f32>,
+    /// Times of every beat in seconds, in ascending order
+    pub beats: Vec<f64>,
+}
+
+impl BeatGrid {
+    /// Runs the beat tracker over an entire song
+    ///
+    /// # Arguments
+    /// * `samples` - Interleaved samples of the song
+    /// * `channels` - Number of interleaved channels
+    /// * `sample_rate` - Sample rate in Hz
+    pub fn analyze(samples: &[f32], channels: u16, sample_rate: u32) -> Self {
+        let mut analyzer = SpectrumAnalyzer::new(FFT_SIZE);
+        let mut tracker = BeatTracker::new();
+        let mut grid = BeatGrid::default();
+        let channels = channels.max(1) as usize;
+        let frames = samples.len() / channels;
+
+        for frame in (0..frames).step_by(OFFLINE_HOP) {
+            let time = frame as f64 / sample_rate as f64;
+            let spectrum = analyzer.process_samples(samples, channels as u16, frame * channels);
+            if tracker.process(spectrum, time).beat {
+                grid.beats.push(time);
+            }
+        }
+
+        grid.bpm = tracker.tempo.bpm();
+        grid
+    }
+
+    /// Loads the grid from the cache, or analyzes and caches the song on a
+    /// background thread
+    ///
+    /// # Arguments
+    /// * `source` - Path of the audio file, used to validate the cache
+    /// * `cache` - Path of the cache file
+    /// * `samples` - Interleaved samples of the song
+    /// * `channels` - Number of interleaved channels
+    /// * `sample_rate` - Sample rate in Hz
+    ///
+    /// # Returns
+    /// Receiver that yields the grid once it is available
+    pub fn spawn_load(
+        source: PathBuf,
+        cache: PathBuf,
+        samples: Arc<Vec<f32>>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Receiver<BeatGrid> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let stamp = SourceStamp::of(&source);
+            let grid = match Self::load(&cache) {
+                Some(grid) if stamp.is_some() && grid.source == stamp => grid,
+                _ => {
+                    let mut grid = Self::analyze(&samples, channels, sample_rate);
+                    grid.source = stamp;
+                    if let Err(e) = grid.save(&cache) {
+                        eprintln!("Failed to cache beat grid: {}", e);
+                    }
+                    grid
+                }
+            };
+            // The song may have been dropped by then, which is fine
+            let _ = sender.send(grid);
+        });
+        receiver
+    }
+
+    /// Reads a cached grid, returning `None` if the cache is missing or unreadable
+    fn load(path: &Path) -> Option<Self> {
+        let json = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Writes the grid to a cache file, creating its directory if needed
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Checks whether a beat falls inside a time range
+    ///
+    /// # Arguments
+    /// * `start` - Exclusive start of the range in seconds
+    /// * `end` - Inclusive end of the range in seconds
+    pub fn has_beat_between(&self, start: f64, end: f64) -> bool {
+        let first_after_start = self.beats.partition_point(|&beat| beat <= start);
+        self.beats
+            .get(first_after_start)
+            .is_some_and(|&beat| beat <= end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+    /// Time from one click to the next, in seconds (120 BPM)
+    const CLICK_INTERVAL: f64 = 0.5;
+    /// Time of the first click, in seconds
+    const FIRST_CLICK: f64 = 0.25;
+    /// Step between offline analysis windows, in seconds
+    const HOP_SECONDS: f64 = OFFLINE_HOP as f64 / SAMPLE_RATE as f64;
+
+    /// Returns `seconds` of mono silence with a short noise burst on every beat
+    fn click_track(seconds: f64) -> Vec<f32> {
+        let mut samples = vec![0.0; (seconds * SAMPLE_RATE as f64) as usize];
+        let click_length = SAMPLE_RATE as usize / 100;
+        let mut noise = 1u32;
+        for click in click_times(seconds) {
+            let start = (click * SAMPLE_RATE as f64) as usize;
+            for (n, sample) in samples[start..].iter_mut().take(click_length).enumerate() {
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+                *sample = 0.8 * white * (1.0 - n as f32 / click_length as f32);
+            }
+        }
+        samples
+    }
+
+    /// Returns the times of the clicks in a click track
+    fn click_times(seconds: f64) -> Vec<f64> {
+        (0..)
+            .map(|beat| FIRST_CLICK + beat as f64 * CLICK_INTERVAL)
+            .take_while(|&time| time + 0.1 < seconds)
+            .collect()
+    }
+
+    /// Returns whether `time` falls shortly after a click
+    ///
+    /// The window taper hides the first samples of a click, so it can take a
+    /// few analysis steps to show up.
+    fn follows_a_click(time: f64, seconds: f64) -> bool {
+        click_times(seconds)
+            .iter()
+            .any(|&click| time >= click && time - click <= 3.0 * HOP_SECONDS)
+    }
+
+    #[test]
+    fn onsets_are_found_at_each_click() {
+        let seconds = 4.0;
+        let samples = click_track(seconds);
+        let mut analyzer = SpectrumAnalyzer::new(FFT_SIZE);
+        let mut detector = OnsetDetector::new();
+        let mut onsets = Vec::new();
+        for frame in (0..samples.len()).step_by(OFFLINE_HOP) {
+            let time = frame as f64 / SAMPLE_RATE as f64;
+            if detector
+                .process(analyzer.process_samples(&samples, 1, frame), time)
+                .is_some()
+            {
+                onsets.push(time);
+            }
+        }
+
+        assert_eq!(onsets.len(), click_times(seconds).len(), "{:?}", onsets);
+        assert!(
+            onsets.iter().all(|&time| follows_a_click(time, seconds)),
+            "{:?}",
+            onsets
+        );
+    }
+
+    #[test]
+    fn tempo_is_folded_into_range() {
+        for interval in [0.25, 0.5, 1.0] {
+            let mut tempo = TempoTracker::new();
+            for beat in 0..8 {
+                tempo.add_onset(beat as f64 * interval);
+            }
+            let bpm = tempo.bpm().unwrap();
+            assert!((bpm - 120.0).abs() <= 1.0, "{} s: {}", interval, bpm);
+        }
+    }
+
+    #[test]
+    fn tempo_tracker_predicts_the_next_beat() {
+        let mut tempo = TempoTracker::new();
+        assert!(!tempo.advance(0.0));
+        for beat in 0..4 {
+            tempo.add_onset(beat as f64 * 0.5);
+            assert!(tempo.advance(beat as f64 * 0.5) || beat < 2);
+        }
+        // Without onsets, beats keep coming every half second
+        assert!(!tempo.advance(1.9));
+        assert!(tempo.advance(2.0));
+        assert!(!tempo.advance(2.1));
+        assert!(tempo.advance(2.5));
+    }
+
+    #[test]
+    fn beat_tracker_forgets_the_tempo_after_a_seek() {
+        let samples = click_track(4.0);
+        let mut analyzer = SpectrumAnalyzer::new(FFT_SIZE);
+        let mut tracker = BeatTracker::new();
+        let mut state = BeatState::default();
+        for frame in (0..samples.len()).step_by(OFFLINE_HOP) {
+            let spectrum = analyzer.process_samples(&samples, 1, frame);
+            state = tracker.process(spectrum, frame as f64 / SAMPLE_RATE as f64);
+        }
+        assert!(state.bpm.is_some());
+
+        let state = tracker.process(analyzer.process_samples(&samples, 1, 0), 0.0);
+        assert!(state.bpm.is_none() && !state.beat);
+    }
+
+    #[test]
+    fn beat_grid_lands_on_the_clicks() {
+        let seconds = 10.0;
+        let grid = BeatGrid::analyze(&click_track(seconds), 1, SAMPLE_RATE);
+
+        let bpm = grid.bpm.unwrap();
+        assert!((bpm - 120.0).abs() <= 1.0, "{}", bpm);
+        // Once the tempo is known every click is a beat, and nothing else is
+        let locked: Vec<f64> = grid
+            .beats
+            .iter()
+            .copied()
+            .filter(|&beat| beat > 3.0)
+            .collect();
+        assert_eq!(locked.len(), 14, "{:?}", grid.beats);
+        assert!(
+            locked.iter().all(|&beat| follows_a_click(beat, seconds)),
+            "{:?}",
+            locked
+        );
+        assert!(grid.has_beat_between(4.2, 4.3));
+        assert!(!grid.has_beat_between(4.3, 4.7));
+    }
+
+    #[test]
+    fn beat_grid_is_cached_until_the_song_changes() {
+        let dir = std::env::temp_dir().join(format!("beat_cache_{}", std::process::id()));
+        let source = dir.join("song.wav");
+        let cache = dir.join(".index").join("song.wav.beats.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&source, b"audio").unwrap();
+        let samples = Arc::new(click_track(6.0));
+        let load = || {
+            BeatGrid::spawn_load(
+                source.clone(),
+                cache.clone(),
+                samples.clone(),
+                1,
+                SAMPLE_RATE,
+            )
+            .recv()
+            .unwrap()
+        };
+
+        let analyzed = load();
+        assert!(analyzed.bpm.is_some());
+        // A cached grid is used as it is, so a changed cache shows through
+        let mut cached = BeatGrid::load(&cache).unwrap();
+        cached.bpm = Some(60.0);
+        cached.save(&cache).unwrap();
+        assert_eq!(load().bpm, Some(60.0));
+
+        // Once the song file changes it is analyzed again
+        fs::write(&source, b"other audio").unwrap();
+        let reanalyzed = load();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(reanalyzed.bpm, analyzed.bpm);
+        assert_eq!(reanalyzed.beats, analyzed.beats);
+    }
+}
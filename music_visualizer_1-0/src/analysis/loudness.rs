@@ -0,0 +1,317 @@
+//! EBU R128 loudness measurement
+//!
+//! Measures a whole track the way ITU-R BS.1770-4 describes:
+//! - Every channel is K-weighted: a high shelf modelling the head, followed
+//!   by a high-pass that ignores rumble
+//! - The mean square of 400 ms blocks, overlapping by 75%, is summed over
+//!   the channels and converted to LUFS
+//! - Blocks below -70 LUFS are gated out, then blocks more than 10 LU below
+//!   the average of the remaining ones; the average of what is left is the
+//!   integrated loudness
+//!
+//! The true peak is the highest sample after upsampling four times, which
+//! catches peaks between the samples that a DAC would reproduce.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Length of a gating block in 100 ms steps
+const BLOCK_STEPS: usize = 4;
+/// Blocks quieter than this are ignored, in LUFS
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the ungated average are ignored, in LU
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Factor the true-peak meter upsamples by
+const OVERSAMPLING: usize = 4;
+/// Input samples each interpolated sample is computed from
+const INTERPOLATION_TAPS: usize = 12;
+
+/// Loudness and peak level of a track
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS, or `None` if the track is silent
+    pub integrated_lufs: Option<f32>,
+    /// Highest level between or at the samples, linear (1.0 is full scale)
+    pub true_peak: f32,
+}
+
+impl Loudness {
+    /// Measures a whole track
+    ///
+    /// # Arguments
+    /// * `samples` - Interleaved samples of the track
+    /// * `channels` - Number of interleaved channels
+    /// * `sample_rate` - Frames per second
+    pub fn measure(samples: &[f32], channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        let step_frames = (sample_rate as usize / 10).max(1);
+        let weights: Vec<f64> = (0..channels)
+            .map(|channel| channel_weight(channels, channel))
+            .collect();
+        let mut filters = vec![KWeighting::new(sample_rate); channels];
+        let mut peak_meters = vec![TruePeakMeter::new(); channels];
+
+        // Weighted mean square of each 100 ms step, combined into blocks below
+        let mut steps = Vec::with_capacity(samples.len() / channels / step_frames + 1);
+        for step in samples.chunks(step_frames * channels) {
+            let mut sum = 0.0;
+            for frame in step.chunks_exact(channels) {
+                for (channel, &sample) in frame.iter().enumerate() {
+                    peak_meters[channel].push(sample);
+                    let weighted = filters[channel].process(sample as f64);
+                    sum += weights[channel] * weighted * weighted;
+                }
+            }
+            // A partial step at the end would skew the last block
+            if step.len() == step_frames * channels {
+                steps.push(sum / step_frames as f64);
+            }
+        }
+
+        let blocks: Vec<f64> = steps
+            .windows(BLOCK_STEPS)
+            .map(|window| window.iter().sum::<f64>() / BLOCK_STEPS as f64)
+            .collect();
+        let gated_mean = |threshold_lufs: f64| {
+            let gated: Vec<f64> = blocks
+                .iter()
+                .copied()
+                .filter(|&power| power_to_lufs(power) > threshold_lufs)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+        let integrated_lufs = gated_mean(ABSOLUTE_GATE_LUFS)
+            .and_then(|mean| {
+                // The relative gate never lets in blocks the absolute gate dropped
+                let relative_gate = power_to_lufs(mean) - RELATIVE_GATE_LU;
+                gated_mean(relative_gate.max(ABSOLUTE_GATE_LUFS))
+            })
+            .map(|mean| power_to_lufs(mean) as f32);
+
+        Loudness {
+            integrated_lufs,
+            true_peak: peak_meters
+                .iter()
+                .map(TruePeakMeter::peak)
+                .fold(0.0, f32::max),
+        }
+    }
+}
+
+/// Converts a weighted mean square to LUFS
+fn power_to_lufs(power: f64) -> f64 {
+    if power > 0.0 {
+        -0.691 + 10.0 * power.log10()
+    } else {
+        f64::NEG_INFINITY
+    }
+}
+
+/// Returns how much a channel counts towards the loudness
+///
+/// In 5.1 audio (L, R, C, LFE, Ls, Rs) the surround channels count more and
+/// the LFE channel not at all; every other channel counts once.
+fn channel_weight(channels: usize, channel: usize) -> f64 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Biquad filter in double precision, as the sums run over whole tracks
+#[derive(Copy, Clone, Debug)]
+struct Biquad {
+    /// Feed-forward coefficients
+    b: [f64; 3],
+    /// Feedback coefficients, without the leading 1
+    a: [f64; 2],
+    /// Transposed direct form II state
+    z: [f64; 2],
+}
+
+impl Biquad {
+    /// Filters one sample
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.z[0];
+        self.z[0] = self.b[1] * input - self.a[0] * output + self.z[1];
+        self.z[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// The K-weighting filter of BS.1770 for one channel
+///
+/// The coefficients are derived for any sample rate from the analog
+/// prototypes of the filters specified for 48 kHz.
+#[derive(Copy, Clone, Debug)]
+struct KWeighting {
+    /// High shelf boosting the treble by about 4 dB
+    shelf: Biquad,
+    /// High-pass removing everything below about 38 Hz
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    /// Creates the filter for a sample rate
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate.max(1) as f64;
+
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        KWeighting { shelf, high_pass }
+    }
+
+    /// Filters one sample
+    fn process(&mut self, input: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(input))
+    }
+}
+
+/// Peak meter for one channel that also sees peaks between samples
+#[derive(Clone, Debug)]
+struct TruePeakMeter {
+    /// Last `INTERPOLATION_TAPS` samples, oldest first
+    history: [f32; INTERPOLATION_TAPS],
+    /// Interpolation filter per in-between position
+    phases: [[f32; INTERPOLATION_TAPS]; OVERSAMPLING - 1],
+    /// Highest absolute level so far
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    /// Creates a meter that has seen only silence
+    ///
+    /// The interpolation filters are Hann-windowed sinc functions centered
+    /// between the two middle samples of the history.
+    fn new() -> Self {
+        let mut phases = [[0.0; INTERPOLATION_TAPS]; OVERSAMPLING - 1];
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            let fraction = (phase + 1) as f64 / OVERSAMPLING as f64;
+            let half = INTERPOLATION_TAPS as f64 / 2.0;
+            for (tap, coefficient) in taps.iter_mut().enumerate() {
+                // Distance from the interpolated point to this sample
+                let t = tap as f64 - (half - 1.0) - fraction;
+                let sinc = if t == 0.0 {
+                    1.0
+                } else {
+                    (PI * t).sin() / (PI * t)
+                };
+                let window = 0.5 * (1.0 + (PI * t / half).cos());
+                *coefficient = (sinc * window) as f32;
+            }
+        }
+        TruePeakMeter {
+            history: [0.0; INTERPOLATION_TAPS],
+            phases,
+            peak: 0.0,
+        }
+    }
+
+    /// Feeds the next sample
+    fn push(&mut self, sample: f32) {
+        self.history.rotate_left(1);
+        self.history[INTERPOLATION_TAPS - 1] = sample;
+        self.peak = self.peak.max(sample.abs());
+        for taps in &self.phases {
+            let interpolated: f32 = taps.iter().zip(&self.history).map(|(c, x)| c * x).sum();
+            self.peak = self.peak.max(interpolated.abs());
+        }
+    }
+
+    /// Returns the highest absolute level so far
+    fn peak(&self) -> f32 {
+        self.peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Returns `seconds` of a stereo sine, both channels equal
+    fn stereo_sine(frequency: f64, level_dbfs: f64, phase: f64, seconds: f64) -> Vec<f32> {
+        let amplitude = 10f64.powf(level_dbfs / 20.0);
+        let frames = (seconds * SAMPLE_RATE as f64) as usize;
+        (0..frames)
+            .flat_map(|n| {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                let sample = (amplitude * (2.0 * PI * frequency * t + phase).sin()) as f32;
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_sine_measures_minus_23_lufs() {
+        // EBU Tech 3341, case 1
+        let samples = stereo_sine(997.0, -23.0, 0.0, 5.0);
+        let loudness = Loudness::measure(&samples, 2, SAMPLE_RATE);
+        let lufs = loudness.integrated_lufs.unwrap();
+        assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+    }
+
+    #[test]
+    fn quiet_passages_are_gated_out() {
+        let mut samples = stereo_sine(997.0, -20.0, 0.0, 3.0);
+        samples.extend(stereo_sine(997.0, -40.0, 0.0, 3.0));
+        let lufs = Loudness::measure(&samples, 2, SAMPLE_RATE)
+            .integrated_lufs
+            .unwrap();
+        // Blocks across the change count a little towards the quieter half
+        assert!((lufs + 20.0).abs() < 0.3, "{}", lufs);
+    }
+
+    #[test]
+    fn relative_gate_stays_above_the_absolute_gate() {
+        // The relative gate of -75 LUFS would let the -72 LUFS half back in
+        let mut samples = stereo_sine(997.0, -65.0, 0.0, 3.0);
+        samples.extend(stereo_sine(997.0, -72.0, 0.0, 3.0));
+        let lufs = Loudness::measure(&samples, 2, SAMPLE_RATE)
+            .integrated_lufs
+            .unwrap();
+        assert!((lufs + 65.0).abs() < 0.3, "{}", lufs);
+
+        let silence = vec![0.0; SAMPLE_RATE as usize * 2];
+        assert_eq!(
+            Loudness::measure(&silence, 2, SAMPLE_RATE).integrated_lufs,
+            None
+        );
+    }
+
+    #[test]
+    fn true_peak_sees_peaks_between_samples() {
+        // At a quarter of the sample rate and 45 degrees off, every sample
+        // lands at 0.707 of the sine's real peak
+        let samples = stereo_sine(SAMPLE_RATE as f64 / 4.0, 0.0, PI / 4.0, 1.0);
+        let sample_peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((sample_peak - 0.707).abs() < 0.001);
+
+        let true_peak = Loudness::measure(&samples, 2, SAMPLE_RATE).true_peak;
+        assert!((true_peak - 1.0).abs() < 0.05, "{}", true_peak);
+    }
+}
@@ -0,0 +1,20 @@
+//! Audio analysis module
+//!
+//! Turns the decoded samples of a song into data the visualizers can react to:
+//! - Windowed FFT magnitude spectrum around the playhead
+//! - Onset, beat and tempo detection, live or over a whole song
+//! - Min/max/RMS waveform overview of a whole song
+//! - EBU R128 loudness and true peak of a whole song
+
+mod beat;
+mod loudness;
+mod peaks;
+mod spectrum;
+
+pub use beat::{BeatGrid, BeatTracker};
+pub use loudness::Loudness;
+pub use peaks::{SourceStamp, WaveformPeaks};
+pub use spectrum::{SILENCE_DB, SpectrumAnalyzer};
+
+/// Number of samples per spectrum analysis window
+pub const FFT_SIZE: usize = 2048;
@@ -0,0 +1,179 @@
+//! Waveform overview peaks
+//!
+//! Summarizes a whole track as a fixed number of columns, each holding the
+//! minimum, maximum and RMS level of its slice of audio. The summary is small
+//! enough to cache on disk, so reopening a track does not have to rescan it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Number of columns stored per track, independent of the display width
+const PEAK_COLUMNS: usize = 2048;
+
+/// Levels of one slice of audio (mono, -1.0 to 1.0)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PeakColumn {
+    /// Lowest sample value
+    pub min: f32,
+    /// Highest sample value
+    pub max: f32,
+    /// Root-mean-square level
+    pub rms: f32,
+}
+
+/// Identifies the version of a source file a cache was computed from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SourceStamp {
+    /// File size in bytes
+    len: u64,
+    /// Modification time in seconds since the Unix epoch
+    modified: u64,
+}
+
+impl SourceStamp {
+    /// Reads the stamp of a file, or `None` if its metadata is unavailable
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(SourceStamp {
+            len: metadata.len(),
+            modified: modified.as_secs(),
+        })
+    }
+}
+
+/// Min/max/RMS overview of a complete track
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WaveformPeaks {
+    /// Source file version, used to invalidate stale caches
+    source: Option<SourceStamp>,
+    /// Lowest sample value per column
+    min: Vec<f32>,
+    /// Highest sample value per column
+    max: Vec<f32>,
+    /// RMS level per column
+    rms: Vec<f32>,
+}
+
+impl WaveformPeaks {
+    /// Scans a track and computes its peaks
+    ///
+    /// # Arguments
+    /// * `samples` - Interleaved samples of the track
+    /// * `channels` - Number of interleaved channels
+    pub fn compute(samples: &[f32], channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        let frames = samples.len() / channels;
+        let columns = PEAK_COLUMNS.min(frames);
+        let mut peaks = WaveformPeaks {
+            source: None,
+            min: Vec::with_capacity(columns),
+            max: Vec::with_capacity(columns),
+            rms: Vec::with_capacity(columns),
+        };
+
+        for column in 0..columns {
+            let start = column * frames / columns;
+            let end = (column + 1) * frames / columns;
+            let mut min = 0.0f32;
+            let mut max = 0.0f32;
+            let mut sum_of_squares = 0.0f32;
+
+            for frame in samples[start * channels..end * channels].chunks_exact(channels) {
+                let mono = frame.iter().sum::<f32>() / channels as f32;
+                min = min.min(mono);
+                max = max.max(mono);
+                sum_of_squares += mono * mono;
+            }
+
+            peaks.min.push(min);
+            peaks.max.push(max);
+            peaks
+                .rms
+                .push((sum_of_squares / (end - start).max(1) as f32).sqrt());
+        }
+
+        peaks
+    }
+
+    /// Loads peaks from the cache, or computes and caches them on a background thread
+    ///
+    /// # Arguments
+    /// * `source` - Path of the audio file, used to validate the cache
+    /// * `cache` - Path of the cache file
+    /// * `samples` - Interleaved samples of the track
+    /// * `channels` - Number of interleaved channels
+    ///
+    /// # Returns
+    /// Receiver that yields the peaks once they are available
+    pub fn spawn_load(
+        source: PathBuf,
+        cache: PathBuf,
+        samples: Arc<Vec<f32>>,
+        channels: u16,
+    ) -> Receiver<WaveformPeaks> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let stamp = SourceStamp::of(&source);
+            let peaks = match Self::load(&cache) {
+                Some(peaks) if stamp.is_some() && peaks.source == stamp => peaks,
+                _ => {
+                    let mut peaks = Self::compute(&samples, channels);
+                    peaks.source = stamp;
+                    if let Err(e) = peaks.save(&cache) {
+                        eprintln!("Failed to cache waveform peaks: {}", e);
+                    }
+                    peaks
+                }
+            };
+            // The song may have been dropped by then, which is fine
+            let _ = sender.send(peaks);
+        });
+        receiver
+    }
+
+    /// Reads cached peaks, returning `None` if the cache is missing or unreadable
+    fn load(path: &Path) -> Option<Self> {
+        let json = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Writes the peaks to a cache file, creating its directory if needed
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Combines the stored columns that fall inside a slice of the track
+    ///
+    /// # Arguments
+    /// * `start` - Start of the slice as a fraction of the track (0.0 to 1.0)
+    /// * `end` - End of the slice as a fraction of the track (0.0 to 1.0)
+    ///
+    /// # Returns
+    /// The extreme min/max and the loudest RMS inside the slice
+    pub fn column(&self, start: f32, end: f32) -> PeakColumn {
+        let count = self.rms.len();
+        if count == 0 {
+            return PeakColumn::default();
+        }
+
+        let first = ((start * count as f32) as usize).min(count - 1);
+        let last = ((end * count as f32).ceil() as usize).clamp(first + 1, count);
+        let mut result = PeakColumn::default();
+        for i in first..last {
+            result.min = result.min.min(self.min[i]);
+            result.max = result.max.max(self.max[i]);
+            result.rms = result.rms.max(self.rms[i]);
+        }
+        result
+    }
+}
@@ -0,0 +1,115 @@
+//! Short-time spectrum analysis
+//!
+//! Computes a Hann-windowed FFT of the samples just behind the playhead and
+//! reports the magnitude of each frequency bin in decibels.
+
+use crate::song::Song;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Magnitude reported for silent bins, in dBFS
+pub const SILENCE_DB: f32 = -120.0;
+
+/// Real-time spectrum analyzer for the currently playing song
+///
+/// Manages:
+/// - The FFT plan and scratch buffers (allocated once)
+/// - The analysis window
+/// - The most recent magnitude spectrum
+pub struct SpectrumAnalyzer {
+    /// Planned forward FFT of `fft_size` points
+    fft: Arc<dyn Fft<f32>>,
+    /// Hann window coefficients
+    window: Vec<f32>,
+    /// Complex working buffer for the in-place FFT
+    buffer: Vec<Complex<f32>>,
+    /// Magnitude of each bin below Nyquist, in dBFS
+    magnitudes: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates a new analyzer
+    ///
+    /// # Arguments
+    /// * `fft_size` - Number of samples per analysis window (a power of two works best)
+    pub fn new(fft_size: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(fft_size);
+        let window = (0..fft_size)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / fft_size as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+
+        SpectrumAnalyzer {
+            fft,
+            window,
+            buffer: vec![Complex::default(); fft_size],
+            magnitudes: vec![SILENCE_DB; fft_size / 2],
+        }
+    }
+
+    /// Analyzes the window of audio that has just been heard
+    ///
+    /// # Arguments
+    /// * `song` - Song whose playhead should be analyzed
+    /// * `latency` - Time from the audio callback until a sample is heard
+    ///
+    /// # Returns
+    /// Magnitude of each bin from 0 Hz up to Nyquist, in dBFS
+    pub fn process(&mut self, song: &Song, latency: Duration) -> &[f32] {
+        self.process_samples(
+            song.samples(),
+            song.channels(),
+            song.audible_position(latency),
+        )
+    }
+
+    /// Analyzes the window of audio ending at a sample position
+    ///
+    /// Channels are averaged to mono before the transform. Positions before the
+    /// start of the samples are treated as silence.
+    ///
+    /// # Arguments
+    /// * `samples` - Interleaved samples to analyze
+    /// * `channels` - Number of interleaved channels
+    /// * `position` - Sample index just after the end of the window
+    ///
+    /// # Returns
+    /// Magnitude of each bin from 0 Hz up to Nyquist, in dBFS
+    pub fn process_samples(&mut self, samples: &[f32], channels: u16, position: usize) -> &[f32] {
+        let channels = channels.max(1) as usize;
+        let fft_size = self.window.len();
+
+        // Align to a frame boundary so channels are not mixed up
+        let end_frame = position.min(samples.len()) / channels;
+
+        for (i, (value, weight)) in self.buffer.iter_mut().zip(&self.window).enumerate() {
+            let mono = match (end_frame + i).checked_sub(fft_size) {
+                Some(frame) => {
+                    let start = frame * channels;
+                    samples[start..start + channels].iter().sum::<f32>() / channels as f32
+                }
+                None => 0.0,
+            };
+            *value = Complex::new(mono * weight, 0.0);
+        }
+
+        self.fft.process(&mut self.buffer);
+
+        // Normalize so a full-scale sine reads roughly 0 dBFS
+        let scale = 4.0 / fft_size as f32;
+        for (magnitude, value) in self.magnitudes.iter_mut().zip(&self.buffer) {
+            let amplitude = value.norm() * scale;
+            *magnitude = if amplitude > 0.0 {
+                (20.0 * amplitude.log10()).max(SILENCE_DB)
+            } else {
+                SILENCE_DB
+            };
+        }
+
+        &self.magnitudes
+    }
+}
@@ -0,0 +1,198 @@
+//! Command line options
+//!
+//! - `--host <name>` plays through the named audio host instead of the one
+//!   in the config file, without changing the config file
+//! - `--output null` throws the audio away and `--output wav:<file>` records
+//!   it to a WAV file, e.g. for runs without sound hardware; `--unthrottled`
+//!   renders their audio as fast as possible instead of in real time
+//! - `--headless` plays the whole library once without a window and exits,
+//!   e.g. `--headless --output wav:<file> --unthrottled` renders it to a file
+//! - `--list-hosts` prints the audio hosts available and exits
+//! - `--help` prints the usage and exits
+
+use crate::output::{OutputTarget, Pace};
+use std::path::PathBuf;
+use std::process;
+
+/// Usage printed by `--help` and after invalid options
+const USAGE: &str = "\
+Usage: music_visualizer_1-0 [OPTIONS]
+
+Options:
+  --host <name>       Play through this audio host, e.g. ALSA or JACK
+  --output <target>   Send the audio to `device` (default), `null` or
+                      `wav:<file>`
+  --unthrottled       Render null and wav output as fast as possible
+  --headless          Play the library once without a window and exit
+  --list-hosts        Print the available audio hosts and exit
+  --help              Print this help and exit";
+
+/// Options given on the command line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Args {
+    /// Audio host overriding the one in the config file
+    pub host: Option<String>,
+    /// Where the audio goes
+    pub output: OutputTarget,
+    /// True if null and wav output is rendered as fast as possible
+    pub unthrottled: bool,
+    /// True if the library should be played without a window
+    pub headless: bool,
+    /// True if the available hosts should be listed instead of starting
+    pub list_hosts: bool,
+}
+
+impl Args {
+    /// Reads the options of this process
+    ///
+    /// Prints the usage and exits for `--help`; reports invalid options and
+    /// exits with an error status.
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Err(message) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    /// Parses options, leaving out the program name
+    ///
+    /// # Arguments
+    /// * `args` - The command line arguments after the program name
+    ///
+    /// # Returns
+    /// The options, `None` if help was asked for, or a description of the
+    /// first invalid option
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => match args.next() {
+                    Some(name) => parsed.host = Some(name),
+                    None => return Err("--host needs the name of an audio host".to_string()),
+                },
+                "--output" => match args.next() {
+                    Some(target) => parsed.output = parse_target(&target)?,
+                    None => return Err("--output needs a target".to_string()),
+                },
+                "--unthrottled" => parsed.unthrottled = true,
+                "--headless" => parsed.headless = true,
+                "--list-hosts" => parsed.list_hosts = true,
+                "--help" | "-h" => return Ok(None),
+                _ => {
+                    if let Some(name) = arg.strip_prefix("--host=") {
+                        parsed.host = Some(name.to_string());
+                    } else if let Some(target) = arg.strip_prefix("--output=") {
+                        parsed.output = parse_target(target)?;
+                    } else {
+                        return Err(format!("Unknown option: {}", arg));
+                    }
+                }
+            }
+        }
+        Ok(Some(parsed))
+    }
+
+    /// Returns where the audio goes, at the pace asked for
+    pub fn output_target(&self) -> OutputTarget {
+        let pace = if self.unthrottled {
+            Pace::Unthrottled
+        } else {
+            Pace::RealTime
+        };
+        match &self.output {
+            OutputTarget::Null(_) => OutputTarget::Null(pace),
+            OutputTarget::WavFile(path, _) => OutputTarget::WavFile(path.clone(), pace),
+            OutputTarget::Device => OutputTarget::Device,
+        }
+    }
+}
+
+/// Parses the target of `--output`
+fn parse_target(target: &str) -> Result<OutputTarget, String> {
+    match target {
+        "device" => Ok(OutputTarget::Device),
+        "null" => Ok(OutputTarget::Null(Pace::RealTime)),
+        _ => match target.strip_prefix("wav:") {
+            Some(path) if !path.is_empty() => {
+                Ok(OutputTarget::WavFile(PathBuf::from(path), Pace::RealTime))
+            }
+            _ => Err(format!("Unknown output target: {}", target)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_options_give_the_defaults() {
+        assert_eq!(parse(&[]), Ok(Some(Args::default())));
+    }
+
+    #[test]
+    fn host_options_are_read() {
+        let args = parse(&["--host", "JACK", "--list-hosts"]).unwrap().unwrap();
+        assert_eq!(args.host.as_deref(), Some("JACK"));
+        assert!(args.list_hosts);
+        // The last one wins
+        let args = parse(&["--host", "JACK", "--host=ALSA"]).unwrap().unwrap();
+        assert_eq!(args.host.as_deref(), Some("ALSA"));
+    }
+
+    #[test]
+    fn help_stops_parsing() {
+        assert_eq!(parse(&["--help"]), Ok(None));
+        assert_eq!(parse(&["--host", "ALSA", "-h", "--bogus"]), Ok(None));
+    }
+
+    #[test]
+    fn invalid_options_are_reported() {
+        assert_eq!(
+            parse(&["--bogus"]),
+            Err("Unknown option: --bogus".to_string())
+        );
+        assert_eq!(parse(&["JACK"]), Err("Unknown option: JACK".to_string()));
+        assert!(parse(&["--host"]).is_err());
+    }
+
+    #[test]
+    fn output_options_are_read() {
+        let args = parse(&["--output", "wav:out.wav", "--unthrottled", "--headless"])
+            .unwrap()
+            .unwrap();
+        assert!(args.headless);
+        assert_eq!(
+            args.output_target(),
+            OutputTarget::WavFile(PathBuf::from("out.wav"), Pace::Unthrottled)
+        );
+
+        let args = parse(&["--output=null"]).unwrap().unwrap();
+        assert_eq!(args.output_target(), OutputTarget::Null(Pace::RealTime));
+        let args = parse(&["--output", "null", "--output", "device"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(args.output_target(), OutputTarget::Device);
+    }
+
+    #[test]
+    fn invalid_output_targets_are_reported() {
+        assert!(parse(&["--output"]).is_err());
+        assert_eq!(
+            parse(&["--output", "speakers"]),
+            Err("Unknown output target: speakers".to_string())
+        );
+        assert!(parse(&["--output=wav:"]).is_err());
+    }
+}
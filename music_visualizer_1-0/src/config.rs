@@ -0,0 +1,228 @@
+//! Persistent user settings
+//!
+//! Settings are stored as JSON in `config.json` in the working directory,
+//! the same directory the music library folder is looked up in, and read
+//! once at startup. A missing file gives the defaults; unknown or missing
+//! fields fall back to their defaults, so older config files keep working.
+
+use crate::dsp::{EffectSettings, EqSettings, LimiterSettings};
+use crate::fade::{Crossfade, CrossfadeCurve, FadeDurations};
+use crate::host::HostSettings;
+use crate::keymap::{self, Action};
+use crate::normalization::NormalizationSettings;
+use crate::output::{OutputSettings, OutputTarget};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// File the settings are stored in, relative to the working directory
+pub const CONFIG_PATH: &str = "config.json";
+
+/// Errors that can occur while reading or writing the config file
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not access config file: {0}")]
+    Io(#[from] io::Error),
+    #[error("config file is not valid: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// User settings remembered between sessions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Width of the menu panel in wide windows, in pixels
+    pub panel_width: f32,
+    /// True if the menu panel is hidden for a full-size visualization
+    pub panel_collapsed: bool,
+    /// Keyboard shortcuts as key name → action; replaces the default bindings
+    pub keymap: BTreeMap<String, Action>,
+    /// Master volume in dB
+    pub volume_db: f32,
+    /// True if the output is muted
+    pub muted: bool,
+    /// Left/right balance from -1.0 (left only) to 1.0 (right only)
+    pub balance: f32,
+    /// Length of the fade-in after play, resume and seek, in milliseconds
+    pub fade_in_ms: f32,
+    /// Length of the fade-out before pause, seek and track changes, in milliseconds
+    pub fade_out_ms: f32,
+    /// Overlap between songs advancing through the queue, in seconds; 0 is gapless
+    pub crossfade_seconds: f32,
+    /// Shape of the crossfade: `linear`, `equal_power` or `s_curve`
+    pub crossfade_curve: CrossfadeCurve,
+    /// Name of the audio output device; `None` uses the system default
+    pub output_device: Option<String>,
+    /// Frames per output buffer; `None` uses the device default. Smaller
+    /// buffers lower the latency but make underruns more likely
+    pub buffer_frames: Option<u32>,
+    /// Name of the audio host, e.g. `ALSA` or `JACK`; `None` uses the default host
+    pub audio_host: Option<String>,
+    /// True if JACK connects the output to the system playback ports
+    pub jack_connect_ports: bool,
+    /// True if JACK starts a server when none is running
+    pub jack_start_server: bool,
+    /// Equalizer mode, bands and whether it is on
+    pub equalizer: EqSettings,
+    /// Effect chain in processing order, with bypass switches and parameters
+    pub effects: Vec<EffectSettings>,
+    /// Loudness normalization mode, target and clipping prevention
+    pub normalization: NormalizationSettings,
+    /// Output limiter switch, ceiling and release
+    pub limiter: LimiterSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            panel_width: 200.0,
+            panel_collapsed: false,
+            keymap: keymap::default_bindings(),
+            volume_db: 0.0,
+            muted: false,
+            balance: 0.0,
+            fade_in_ms: 20.0,
+            fade_out_ms: 20.0,
+            crossfade_seconds: 0.0,
+            crossfade_curve: CrossfadeCurve::EqualPower,
+            output_device: None,
+            buffer_frames: None,
+            audio_host: None,
+            jack_connect_ports: true,
+            jack_start_server: false,
+            equalizer: EqSettings::default(),
+            effects: Vec::new(),
+            normalization: NormalizationSettings::default(),
+            limiter: LimiterSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the configured fade lengths
+    pub fn fade_durations(&self) -> FadeDurations {
+        FadeDurations {
+            fade_in_ms: self.fade_in_ms,
+            fade_out_ms: self.fade_out_ms,
+        }
+    }
+
+    /// Returns the configured crossfade
+    pub fn crossfade(&self) -> Crossfade {
+        Crossfade {
+            seconds: self.crossfade_seconds.max(0.0),
+            curve: self.crossfade_curve,
+        }
+    }
+
+    /// Returns the configured audio output
+    ///
+    /// # Arguments
+    /// * `target` - Where the audio goes, as given on the command line
+    /// * `host` - Audio host given on the command line, used instead of the
+    ///   configured one
+    pub fn output_settings(&self, target: OutputTarget, host: Option<&str>) -> OutputSettings {
+        OutputSettings {
+            target,
+            host: HostSettings {
+                name: host.map(str::to_string).or_else(|| self.audio_host.clone()),
+                jack_connect_ports: self.jack_connect_ports,
+                jack_start_server: self.jack_start_server,
+            },
+            device: self.output_device.clone(),
+            buffer_frames: self.buffer_frames,
+            fade_durations: self.fade_durations(),
+        }
+    }
+
+    /// Loads the settings, falling back to the defaults
+    ///
+    /// A missing config file is normal on first start; any other problem is
+    /// reported and the defaults are used.
+    pub fn load() -> Self {
+        match Self::load_from(Path::new(CONFIG_PATH)) {
+            Ok(config) => config,
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(e) => {
+                eprintln!("Failed to load config, using defaults: {}", e);
+                Config::default()
+            }
+        }
+    }
+
+    /// Reads settings from a file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the config file
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Writes the settings to the config file
+    pub fn save(&self) -> Result<(), ConfigError> {
+        self.save_to(Path::new(CONFIG_PATH))
+    }
+
+    /// Writes settings to a file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the config file
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a path in the temporary directory unique to this process
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("config_{}_{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let config = Config {
+            panel_width: 320.0,
+            panel_collapsed: true,
+            volume_db: -12.0,
+            crossfade_curve: CrossfadeCurve::SCurve,
+            output_device: Some("Headphones".to_string()),
+            buffer_frames: Some(256),
+            ..Config::default()
+        };
+        let path = temp_path("roundtrip");
+        config.save_to(&path).unwrap();
+        let loaded = Config::load_from(&path);
+        let _ = fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_fields_take_the_defaults() {
+        let path = temp_path("partial");
+        fs::write(&path, r#"{ "panel_width": 250.0, "unknown": 1 }"#).unwrap();
+        let loaded = Config::load_from(&path);
+        fs::write(&path, "not json").unwrap();
+        let invalid = Config::load_from(&path);
+        let _ = fs::remove_file(&path);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.panel_width, 250.0);
+        assert!(!loaded.panel_collapsed);
+        assert_eq!(loaded.keymap, keymap::default_bindings());
+        assert!(matches!(invalid, Err(ConfigError::Format(_))));
+        assert!(matches!(Config::load_from(&path), Err(ConfigError::Io(_))));
+    }
+}
@@ -2,13 +2,180 @@
 //!
 //! Manages the coordination between all application components:
 //! - View (visual display)
+//! - Seek bar (waveform overview beneath the view)
 //! - Menu (user interface)
-//! - Song (audio playback)
+//! - Player (playback state machine owning the library and song)
+//! - Spectrum and beat analysis feeding the visualizers
 //!
-//! Handles layout, updates, and rendering of the complete application.
+//! Handles layout, updates, and rendering of the complete application. The
+//! layout is recomputed whenever the window is resized; wide windows get the
+//! menu as a column on the right, narrow ones as a panel along the bottom.
+//! The divider between menu and view resizes or collapses the panel, and the
+//! chosen panel settings are saved to the config file.
+//!
+//! Key presses the menu does not use are looked up in the keymap and run as
+//! shortcuts; a help overlay lists the active bindings.
+//!
+//! Volume, mute and balance are shared with the audio callback and saved to
+//! the config file once the user lets go of the slider. The output device,
+//! equalizer, effect chain, loudness normalization, limiter and crossfade
+//! settings chosen in the menu are saved the same way.
 
-use crate::{menu::Menu, view::View};
+use crate::analysis::{BeatTracker, FFT_SIZE, SpectrumAnalyzer};
+use crate::cli::Args;
+use crate::config::Config;
+use crate::divider::{Divider, DividerAction};
+use crate::dsp::{EffectChain, EqControl, LimiterControl, default_effects};
+use crate::fade::CrossfadeControl;
+use crate::keymap::{Action, Keymap};
+use crate::music_library::{LIBRARY_DIR, MusicLibrary};
+use crate::output::AudioOutput;
+use crate::player::{Player, PlayerCommand, PlayerState, SKIP_SECONDS};
+use crate::visualizer::AudioFrame;
+use crate::volume::VolumeControl;
+use crate::{menu::Menu, seek_bar::SeekBar, view::View};
 use nannou::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Height of the waveform seek bar beneath the view, in pixels
+const SEEK_BAR_HEIGHT: f32 = 60.0;
+/// Narrowest the menu column can be dragged, in pixels
+const MIN_MENU_WIDTH: f32 = 160.0;
+/// Largest share of the window width the menu column can take
+const MAX_MENU_FRACTION: f32 = 0.6;
+/// Windows narrower than this put the menu along the bottom, in pixels
+const NARROW_WINDOW_WIDTH: f32 = 640.0;
+/// Smallest height of the bottom menu panel in narrow windows, in pixels
+const MIN_BOTTOM_MENU_HEIGHT: f32 = 280.0;
+/// Volume change per volume shortcut key press, in dB
+const VOLUME_STEP_DB: f32 = 3.0;
+
+/// Rectangles of the application components for one window size
+struct AppLayout {
+    /// Visualization area
+    view: Rect,
+    /// Waveform seek bar beneath the view
+    seek_bar: Rect,
+    /// Menu panel
+    menu: Rect,
+    /// Start and end of the line separating the menu from the rest
+    divider: (Point2, Point2),
+}
+
+impl AppLayout {
+    /// Computes the layout for a window
+    ///
+    /// # Arguments
+    /// * `win_rect` - The dimensions of the application window
+    /// * `config` - User settings holding the panel width and collapsed state
+    ///
+    /// # Layout
+    /// - Wide windows: menu takes up the configured width on the right side
+    /// - Narrow windows: menu spans the bottom, at least
+    ///   `MIN_BOTTOM_MENU_HEIGHT` tall and at most half the window
+    /// - A collapsed menu takes up no space at all
+    /// - Seek bar runs along the bottom of the remaining space
+    /// - View occupies the rest above the seek bar
+    fn new(win_rect: Rect, config: &Config) -> Self {
+        let (menu, rest, divider) = if win_rect.w() < NARROW_WINDOW_WIDTH {
+            let menu_height = if config.panel_collapsed {
+                0.0
+            } else {
+                (win_rect.h() * 0.4)
+                    .max(MIN_BOTTOM_MENU_HEIGHT)
+                    .min(win_rect.h() / 2.0)
+            };
+            let menu = Rect::from_x_y_w_h(
+                win_rect.x(),
+                win_rect.bottom() + menu_height / 2.0,
+                win_rect.w(),
+                menu_height,
+            );
+            let divider = (
+                pt2(win_rect.left(), menu.top()),
+                pt2(win_rect.right(), menu.top()),
+            );
+            (menu, win_rect.pad_bottom(menu_height), divider)
+        } else {
+            let menu_width = if config.panel_collapsed {
+                0.0
+            } else {
+                clamp_menu_width(config.panel_width, win_rect)
+            };
+            let menu = Rect::from_x_y_w_h(
+                win_rect.right() - menu_width / 2.0,
+                win_rect.y(),
+                menu_width,
+                win_rect.h(),
+            );
+            let divider = (
+                pt2(menu.left(), win_rect.top()),
+                pt2(menu.left(), win_rect.bottom()),
+            );
+            (menu, win_rect.pad_right(menu_width), divider)
+        };
+
+        let seek_bar_height = SEEK_BAR_HEIGHT.min(rest.h() / 2.0);
+        let seek_bar = Rect::from_x_y_w_h(
+            rest.x(),
+            rest.bottom() + seek_bar_height / 2.0,
+            rest.w(),
+            seek_bar_height,
+        );
+
+        AppLayout {
+            view: rest.pad_bottom(seek_bar_height),
+            seek_bar,
+            menu,
+            divider,
+        }
+    }
+}
+
+/// Limits a menu column width to what fits the window
+fn clamp_menu_width(width: f32, win_rect: Rect) -> f32 {
+    width
+        .min(win_rect.w() * MAX_MENU_FRACTION)
+        .max(MIN_MENU_WIDTH.min(win_rect.w()))
+}
+
+/// Creates the player and the output it plays on, as the config file and
+/// the command line options ask
+///
+/// The effect settings in `config` are replaced by those the effect chain
+/// took over, so only changes made from now on need saving.
+///
+/// # Arguments
+/// * `config` - User settings
+/// * `args` - Command line options, overriding the config file
+/// * `volume` - Volume settings applied in the audio callback
+/// * `eq` - Equalizer settings applied in the audio callback
+/// * `limiter` - Limiter settings applied in the audio callback
+/// * `crossfade` - Crossfade settings applied in the audio callback
+pub fn create_player(
+    config: &mut Config,
+    args: &Args,
+    volume: Arc<VolumeControl>,
+    eq: Arc<EqControl>,
+    limiter: Arc<LimiterControl>,
+    crossfade: Arc<CrossfadeControl>,
+) -> Player {
+    let mut effects = EffectChain::new(default_effects());
+    effects.apply_settings(&config.effects);
+    config.effects = effects.settings();
+
+    let output = AudioOutput::new(
+        volume,
+        eq,
+        effects.rack(),
+        limiter,
+        crossfade,
+        config.output_settings(args.output_target(), args.host.as_deref()),
+    );
+    let library = MusicLibrary::new(Path::new(LIBRARY_DIR));
+    Player::new(library, output, effects, config.normalization)
+}
 
 /// Main application controller that orchestrates all components
 ///
@@ -19,10 +186,38 @@ use nannou::prelude::*;
 pub struct Controller {
     /// Handles the main visualization display
     view: View,
+    /// Waveform overview used to show and change the playback position
+    seek_bar: SeekBar,
     /// Manages the user interface and controls
     menu: Menu,
+    /// Plays the selected song; the single source of truth for playback
+    player: Player,
+    /// Line between menu and view, used to resize and collapse the menu
+    divider: Divider,
+    /// User settings, saved whenever the panel is changed
+    config: Config,
+    /// Volume, mute and balance shared with the audio callback
+    volume: Arc<VolumeControl>,
+    /// Equalizer settings shared with the audio callback
+    eq: Arc<EqControl>,
+    /// Limiter settings shared with the audio callback
+    limiter: Arc<LimiterControl>,
+    /// Crossfade settings shared with the audio callback
+    crossfade: Arc<CrossfadeControl>,
+    /// Keyboard shortcuts loaded from the config
+    keymap: Keymap,
+    /// True while the keyboard shortcut overlay is shown
+    show_help: bool,
     /// Stores the main window dimensions
     window_rect: Rect,
+    /// Rectangles of all components for the current window size
+    layout: AppLayout,
+    /// Computes the spectrum around the playhead for the visualizers
+    analyzer: SpectrumAnalyzer,
+    /// Detects onsets, beats and tempo in real time
+    beat_tracker: BeatTracker,
+    /// Playback time analyzed in the previous frame, in seconds
+    last_playback_time: f64,
 }
 
 impl Controller {
@@ -30,51 +225,287 @@ impl Controller {
     ///
     /// # Arguments
     /// * `win_rect` - The dimensions of the application window
-    ///
-    /// # Layout
-    /// - Menu takes up 200px on the right side
-    /// - View occupies remaining space on the left
-    /// - A divider line separates the two sections
-    pub fn new(win_rect: Rect) -> Self {
-        let menu_width = 200.0;
-        let menu_rect = Rect::from_x_y_w_h(
-            win_rect.right() - menu_width / 2.0,
-            win_rect.y(),
-            menu_width,
-            win_rect.h(),
-        );
+    /// * `args` - Command line options, overriding the config file
+    pub fn new(win_rect: Rect, args: &Args) -> Self {
+        let mut config = Config::load();
+        let layout = AppLayout::new(win_rect, &config);
+        let volume = Arc::new(VolumeControl::new(
+            config.volume_db,
+            config.muted,
+            config.balance,
+        ));
 
-        let view_rect = Rect::from_x_y_w_h(
-            win_rect.left() + (win_rect.w() - menu_width) / 2.0,
-            win_rect.y(),
-            win_rect.w() - menu_width,
-            win_rect.h(),
+        let eq = Arc::new(EqControl::new(config.equalizer.clone()));
+        let limiter = Arc::new(LimiterControl::new(config.limiter));
+        let crossfade = Arc::new(CrossfadeControl::new(config.crossfade()));
+        let mut player = create_player(
+            &mut config,
+            args,
+            volume.clone(),
+            eq.clone(),
+            limiter.clone(),
+            crossfade.clone(),
         );
 
         Controller {
-            view: View::new(view_rect),
-            menu: Menu::new(menu_rect),
+            view: View::new(layout.view, player.subscribe()),
+            seek_bar: SeekBar::new(layout.seek_bar),
+            menu: Menu::new(
+                layout.menu,
+                &player,
+                volume.clone(),
+                eq.clone(),
+                limiter.clone(),
+                crossfade.clone(),
+            ),
+            player,
+            divider: Divider::new(layout.divider, config.panel_collapsed),
+            volume,
+            eq,
+            limiter,
+            crossfade,
+            keymap: Keymap::from_config(&config.keymap),
+            show_help: false,
+            config,
             window_rect: win_rect,
+            layout,
+            analyzer: SpectrumAnalyzer::new(FFT_SIZE),
+            beat_tracker: BeatTracker::new(),
+            last_playback_time: 0.0,
+        }
+    }
+
+    /// Adapts the layout to a new window size
+    ///
+    /// # Arguments
+    /// * `win_rect` - The new dimensions of the application window
+    pub fn resize(&mut self, win_rect: Rect) {
+        if win_rect == self.window_rect {
+            return;
+        }
+        self.window_rect = win_rect;
+        self.relayout();
+    }
+
+    /// Recomputes the layout and moves every component into it
+    fn relayout(&mut self) {
+        self.layout = AppLayout::new(self.window_rect, &self.config);
+        self.view.set_rect(self.layout.view);
+        self.seek_bar.set_rect(self.layout.seek_bar);
+        self.menu.set_rect(self.layout.menu, &self.player);
+        self.divider
+            .set_line(self.layout.divider, self.config.panel_collapsed);
+    }
+
+    /// Applies what the user did with the divider
+    fn apply_divider_action(&mut self, action: DividerAction) {
+        match action {
+            DividerAction::Drag(mouse) => {
+                let width = self.window_rect.right() - mouse.x;
+                self.config.panel_width = clamp_menu_width(width, self.window_rect);
+                self.relayout();
+            }
+            DividerAction::Release => self.save_config(),
+            DividerAction::ToggleCollapse => {
+                self.config.panel_collapsed = !self.config.panel_collapsed;
+                self.relayout();
+                self.save_config();
+            }
+        }
+    }
+
+    /// Writes the config file, reporting failures
+    fn save_config(&self) {
+        if let Err(e) = self.config.save() {
+            eprintln!("Failed to save config: {}", e);
+        }
+    }
+
+    /// Saves volume, output device, equalizer, effect, normalization,
+    /// limiter and crossfade changes once the mouse is released
+    ///
+    /// Saving is held back while a slider is dragged, so the config file is
+    /// written once per change rather than every frame. The chosen device is
+    /// saved even while the output falls back to the default one.
+    ///
+    /// # Arguments
+    /// * `app` - Reference to the Nannou application for input access
+    fn save_playback_settings(&mut self, app: &App) {
+        if app.mouse.buttons.left().is_down() {
+            return;
+        }
+        let volume_db = self.volume.volume_db();
+        let muted = self.volume.is_muted();
+        let balance = self.volume.balance();
+        let output_device = self.player.output().device();
+        let equalizer = self.eq.settings();
+        let effects = self.player.effects().settings();
+        let normalization = self.player.normalization();
+        let limiter = self.limiter.settings();
+        let crossfade = self.crossfade.crossfade();
+        if volume_db != self.config.volume_db
+            || muted != self.config.muted
+            || balance != self.config.balance
+            || output_device != self.config.output_device.as_deref()
+            || equalizer != self.config.equalizer
+            || effects != self.config.effects
+            || normalization != self.config.normalization
+            || limiter != self.config.limiter
+            || crossfade != self.config.crossfade()
+        {
+            self.config.volume_db = volume_db;
+            self.config.muted = muted;
+            self.config.balance = balance;
+            self.config.output_device = output_device.map(str::to_string);
+            self.config.equalizer = equalizer;
+            self.config.effects = effects;
+            self.config.normalization = normalization;
+            self.config.limiter = limiter;
+            self.config.crossfade_seconds = crossfade.seconds;
+            self.config.crossfade_curve = crossfade.curve;
+            self.save_config();
         }
     }
 
     /// Updates all application components
     ///
     /// Called once per frame to:
-    /// 1. Update menu state based on user input
-    /// 2. Update song playback based on menu state
-    /// 3. Update view based on playback state
+    /// 1. Resize or collapse the menu panel through the divider
+    /// 2. Update menu state based on user input
+    /// 3. Apply visualizer changes requested through the menu
+    /// 4. Send seek bar input to the player and apply all queued commands
+    /// 5. Analyze the audio and update the view with the result
+    /// 6. Save changed volume, output device, equalizer, effect,
+    ///    normalization, limiter and crossfade settings
+    ///
+    /// The menu and seek bar ignore the mouse while the divider is dragged.
     ///
     /// # Arguments
     /// * `app` - Reference to the Nannou application for input handling
     pub fn update(&mut self, app: &App) {
-        self.menu.update(app);
+        if let Some(action) = self.divider.update(app) {
+            self.apply_divider_action(action);
+        }
+        let is_dragging_divider = self.divider.is_dragging();
+
+        if !is_dragging_divider && !self.config.panel_collapsed {
+            self.menu.update(app, &self.player);
+        }
+
+        if self.menu.take_visualizer_cycle() {
+            self.view.next_visualizer();
+        }
+        for (name, value) in self.menu.take_param_changes() {
+            self.view.set_visualizer_param(name, value);
+        }
+
+        if !is_dragging_divider && let Some(fraction) = self.seek_bar.update(app) {
+            let duration = self.player.song().duration_seconds();
+            self.player
+                .send(PlayerCommand::Seek(fraction as f64 * duration));
+        }
+
+        self.player.update();
+        let song = self.player.song();
+        let is_playing = self.player.state() == PlayerState::Playing;
+        // Follow what is heard, which lags the playhead by the output latency
+        let latency = self.player.output().latency();
+        let time = song.audible_seconds(latency);
+        let spectrum = self.analyzer.process(song, latency);
+        let mut beat = if is_playing {
+            self.beat_tracker.process(spectrum, time)
+        } else {
+            Default::default()
+        };
+
+        // Prefer the precomputed beat grid once the background analysis is done
+        if let Some(grid) = song.beat_grid() {
+            beat.beat = is_playing && grid.has_beat_between(self.last_playback_time, time);
+            beat.bpm = grid.bpm.or(beat.bpm);
+        }
+        self.last_playback_time = time;
+
+        let frame = AudioFrame {
+            is_playing,
+            time,
+            spectrum,
+            sample_rate: song.sample_rate(),
+            onset: beat.onset,
+            beat: beat.beat,
+            bpm: beat.bpm,
+        };
+        self.view.update(&frame);
+
         self.menu
-            .music_library
-            .selected_song
-            .update(self.menu.is_playing());
-        self.view
-            .update(self.menu.music_library.selected_song.is_playing());
+            .set_visualizer_info(self.view.visualizer_name(), self.view.visualizer_params());
+
+        self.save_playback_settings(app);
+    }
+
+    /// Handles window events such as resizing, mouse wheel movement and key presses
+    ///
+    /// # Arguments
+    /// * `app` - Reference to the Nannou application
+    /// * `event` - The window event to handle
+    pub fn event(&mut self, app: &App, event: &WindowEvent) {
+        if let Resized(_) = event {
+            self.resize(app.window_rect());
+        }
+
+        // Any key closes the help overlay without doing anything else
+        if self.show_help
+            && let KeyPressed(key) = event
+            && self.keymap.action(*key) != Some(Action::ToggleHelp)
+        {
+            self.show_help = false;
+            return;
+        }
+
+        // A collapsed menu gets no input
+        if !self.config.panel_collapsed && self.menu.event(app, event, &self.player) {
+            return;
+        }
+
+        if let KeyPressed(key) = event
+            && let Some(action) = self.keymap.action(*key)
+        {
+            self.run_action(app, action);
+        }
+    }
+
+    /// Performs the action of a keyboard shortcut
+    ///
+    /// # Arguments
+    /// * `app` - Reference to the Nannou application
+    /// * `action` - The action to perform
+    fn run_action(&mut self, app: &App, action: Action) {
+        match action {
+            Action::PlayPause => self.player.send(PlayerCommand::TogglePlayPause),
+            Action::SeekBackward => self.player.send(PlayerCommand::SeekBy(-SKIP_SECONDS)),
+            Action::SeekForward => self.player.send(PlayerCommand::SeekBy(SKIP_SECONDS)),
+            Action::NextSong => self.player.send(PlayerCommand::Next),
+            Action::PreviousSong => self.player.send(PlayerCommand::Previous),
+            Action::CycleVisualizer => self.view.next_visualizer(),
+            Action::ToggleFullscreen => {
+                let window = app.main_window();
+                window.set_fullscreen(!window.is_fullscreen());
+            }
+            Action::Search => {
+                if self.config.panel_collapsed {
+                    self.apply_divider_action(DividerAction::ToggleCollapse);
+                }
+                self.menu.focus_search();
+            }
+            Action::VolumeUp => self
+                .volume
+                .set_volume_db(self.volume.volume_db() + VOLUME_STEP_DB),
+            Action::VolumeDown => self
+                .volume
+                .set_volume_db(self.volume.volume_db() - VOLUME_STEP_DB),
+            Action::ToggleMute => self.volume.set_muted(!self.volume.is_muted()),
+            Action::TogglePanel => self.apply_divider_action(DividerAction::ToggleCollapse),
+            Action::ToggleHelp => self.show_help = !self.show_help,
+        }
     }
 
     /// Renders all application components
@@ -82,6 +513,7 @@ impl Controller {
     /// Draws the complete application interface including:
     /// - Background
     /// - View visualization
+    /// - Waveform seek bar
     /// - Menu controls
     /// - Divider line
     ///
@@ -96,21 +528,39 @@ impl Controller {
 
         // Draw view and menu
         self.view.draw(&draw); //TODO: Rename (maybe canvas)
-        self.menu.draw(&draw);
-
-        // Draw divider line between view and menu
-        draw.line()
-            .start(pt2(
-                self.window_rect.right() - 200.0,
-                self.window_rect.top(),
-            ))
-            .end(pt2(
-                self.window_rect.right() - 200.0,
-                self.window_rect.bottom(),
-            ))
-            .color(BLACK)
-            .weight(1.0);
+        if !self.config.panel_collapsed {
+            self.menu.draw(&draw, &self.player);
+        }
+
+        // Draw waveform overview with the current playback position
+        let song = self.player.song();
+        let progress = song.position_seconds() / song.duration_seconds().max(f64::EPSILON);
+        self.seek_bar.draw(&draw, song.peaks(), progress as f32);
+
+        // Draw divider line between view and menu, with its collapse handle
+        self.divider.draw(&draw);
+
+        if self.show_help {
+            self.keymap.draw_help(&draw, self.window_rect);
+        }
 
         draw.to_frame(app, &frame).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_width_fits_the_window() {
+        let window = Rect::from_x_y_w_h(0.0, 0.0, 1000.0, 800.0);
+        assert_eq!(clamp_menu_width(300.0, window), 300.0);
+        assert_eq!(clamp_menu_width(50.0, window), MIN_MENU_WIDTH);
+        assert_eq!(clamp_menu_width(900.0, window), 1000.0 * MAX_MENU_FRACTION);
+        // A window narrower than the minimum gets the whole width
+        let tiny = Rect::from_x_y_w_h(0.0, 0.0, 100.0, 800.0);
+        assert_eq!(clamp_menu_width(300.0, tiny), 100.0);
+        assert_eq!(clamp_menu_width(10.0, tiny), 100.0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Panel divider
+//!
+//! The line between the menu and the visualization doubles as a control:
+//! - Dragging it resizes the menu panel (side panel only)
+//! - A small handle on it collapses and expands the panel
+
+use crate::ui::{Button, Input};
+use nannou::prelude::*;
+
+/// Distance from the line within which a press starts a drag, in pixels
+const GRAB_DISTANCE: f32 = 4.0;
+/// Length of the collapse handle along the line, in pixels
+const HANDLE_LENGTH: f32 = 40.0;
+/// Thickness of the collapse handle across the line, in pixels
+const HANDLE_THICKNESS: f32 = 14.0;
+
+/// What the user did with the divider this frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DividerAction {
+    /// The divider is being dragged to the mouse position
+    Drag(Point2),
+    /// A drag has ended
+    Release,
+    /// The collapse handle was clicked
+    ToggleCollapse,
+}
+
+/// Draggable divider line with a collapse handle
+pub struct Divider {
+    /// Start and end of the line
+    line: (Point2, Point2),
+    /// True if the panel is collapsed
+    collapsed: bool,
+    /// True while the user is dragging the line
+    is_dragging: bool,
+    /// Mouse state of the current frame
+    input: Input,
+}
+
+impl Divider {
+    /// Creates a divider
+    ///
+    /// # Arguments
+    /// * `line` - Start and end of the line
+    /// * `collapsed` - Whether the panel starts collapsed
+    pub fn new(line: (Point2, Point2), collapsed: bool) -> Self {
+        Divider {
+            line,
+            collapsed,
+            is_dragging: false,
+            input: Input::default(),
+        }
+    }
+
+    /// Moves the divider, e.g. after the layout changed
+    ///
+    /// # Arguments
+    /// * `line` - Start and end of the line
+    /// * `collapsed` - Whether the panel is collapsed
+    pub fn set_line(&mut self, line: (Point2, Point2), collapsed: bool) {
+        self.line = line;
+        self.collapsed = collapsed;
+    }
+
+    /// Returns whether the line runs vertically (menu at the side)
+    fn is_vertical(&self) -> bool {
+        self.line.0.x == self.line.1.x
+    }
+
+    /// Returns whether the divider is being dragged
+    pub fn is_dragging(&self) -> bool {
+        self.is_dragging
+    }
+
+    /// Handles clicks on the handle and drags of the line
+    ///
+    /// Only a vertical divider can be dragged, and only while expanded.
+    ///
+    /// # Arguments
+    /// * `app` - Reference to Nannou application for input access
+    ///
+    /// # Returns
+    /// The action the user performed, if any
+    pub fn update(&mut self, app: &App) -> Option<DividerAction> {
+        let is_mouse_pressed = app.mouse.buttons.left().is_down();
+        self.input = Input::new(app.mouse.position(), is_mouse_pressed, self.input.is_down);
+
+        if self.handle().clicked(&self.input) {
+            return Some(DividerAction::ToggleCollapse);
+        }
+
+        if self.is_dragging {
+            if is_mouse_pressed {
+                return Some(DividerAction::Drag(self.input.mouse));
+            }
+            self.is_dragging = false;
+            return Some(DividerAction::Release);
+        }
+
+        let near_line = (self.input.mouse.x - self.line.0.x).abs() <= GRAB_DISTANCE
+            && self.input.mouse.y <= self.line.0.y.max(self.line.1.y)
+            && self.input.mouse.y >= self.line.0.y.min(self.line.1.y);
+        if self.input.clicked && self.is_vertical() && !self.collapsed && near_line {
+            self.is_dragging = true;
+            return Some(DividerAction::Drag(self.input.mouse));
+        }
+        None
+    }
+
+    /// Builds the collapse handle, sitting on the visualization side of the line
+    fn handle(&self) -> Button {
+        let middle = (self.line.0 + self.line.1) / 2.0;
+        let (rect, label) = if self.is_vertical() {
+            let rect = Rect::from_x_y_w_h(
+                middle.x - HANDLE_THICKNESS / 2.0,
+                middle.y,
+                HANDLE_THICKNESS,
+                HANDLE_LENGTH,
+            );
+            (rect, if self.collapsed { "<" } else { ">" })
+        } else {
+            let rect = Rect::from_x_y_w_h(
+                middle.x,
+                middle.y + HANDLE_THICKNESS / 2.0,
+                HANDLE_LENGTH,
+                HANDLE_THICKNESS,
+            );
+            (rect, if self.collapsed { "^" } else { "v" })
+        };
+        Button::new(label, "collapse_button", rect).font_size(12)
+    }
+
+    /// Renders the line (highlighted while dragged) and the collapse handle
+    pub fn draw(&self, draw: &Draw) {
+        let color = if self.is_dragging {
+            rgb(0.4, 0.6, 1.0)
+        } else {
+            rgb(0.0, 0.0, 0.0)
+        };
+        let weight = if self.is_dragging { 3.0 } else { 1.0 };
+        draw.line()
+            .start(self.line.0)
+            .end(self.line.1)
+            .color(color)
+            .weight(weight);
+
+        self.handle().draw(draw, &self.input);
+    }
+}
@@ -0,0 +1,263 @@
+//! Second-order IIR filters
+//!
+//! Coefficients follow the formulas of Robert Bristow-Johnson's "Audio EQ
+//! Cookbook". Samples run through the transposed direct form II, which keeps
+//! two values of state per filter and channel and stays well behaved when the
+//! coefficients change while audio plays.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Shape of a biquad filter
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    /// Boosts or cuts a band around the frequency
+    Peaking,
+    /// Boosts or cuts everything below the frequency
+    LowShelf,
+    /// Boosts or cuts everything above the frequency
+    HighShelf,
+    /// Removes everything below the frequency
+    HighPass,
+    /// Removes everything above the frequency
+    LowPass,
+}
+
+impl FilterKind {
+    /// All filter kinds in the order the menu cycles through them
+    pub const ALL: [FilterKind; 5] = [
+        FilterKind::Peaking,
+        FilterKind::LowShelf,
+        FilterKind::HighShelf,
+        FilterKind::HighPass,
+        FilterKind::LowPass,
+    ];
+
+    /// Returns the name shown in the menu
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterKind::Peaking => "Peaking",
+            FilterKind::LowShelf => "Low shelf",
+            FilterKind::HighShelf => "High shelf",
+            FilterKind::HighPass => "High-pass",
+            FilterKind::LowPass => "Low-pass",
+        }
+    }
+
+    /// Returns whether the filter's gain has any effect
+    pub fn uses_gain(self) -> bool {
+        !matches!(self, FilterKind::HighPass | FilterKind::LowPass)
+    }
+
+    /// Returns the kind after this one, wrapping around at the end
+    pub fn next(self) -> FilterKind {
+        let index = FilterKind::ALL.iter().position(|&k| k == self).unwrap_or(0);
+        FilterKind::ALL[(index + 1) % FilterKind::ALL.len()]
+    }
+}
+
+/// Normalized coefficients of a biquad filter (`a0` is 1)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    /// Computes the coefficients of a filter
+    ///
+    /// The frequency is clamped below the Nyquist frequency and the Q to a
+    /// small positive value, so any settings give a stable filter.
+    ///
+    /// # Arguments
+    /// * `kind` - Shape of the filter
+    /// * `frequency` - Center or corner frequency in Hz
+    /// * `gain_db` - Boost (positive) or cut (negative) in dB; ignored by the
+    ///   pass filters
+    /// * `q` - Quality factor; higher values give a narrower band
+    /// * `sample_rate` - Frames per second of the filtered audio
+    pub fn new(kind: FilterKind, frequency: f32, gain_db: f32, q: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let frequency = frequency.clamp(1.0, sample_rate * 0.49);
+        let q = q.max(0.01);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a = 10f32.powf(gain_db / 40.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            FilterKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            FilterKind::LowShelf => {
+                let root = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + root),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - root),
+                    (a + 1.0) + (a - 1.0) * cos + root,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - root,
+                )
+            }
+            FilterKind::HighShelf => {
+                let root = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + root),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - root),
+                    (a + 1.0) - (a - 1.0) * cos + root,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - root,
+                )
+            }
+            FilterKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            FilterKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+        };
+
+        Coefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Returns the gain of the filter at a frequency
+    ///
+    /// # Arguments
+    /// * `frequency` - Frequency in Hz
+    /// * `sample_rate` - Frames per second the coefficients were computed for
+    ///
+    /// # Returns
+    /// The gain in dB, never below -120 dB
+    pub fn response_db(&self, frequency: f32, sample_rate: u32) -> f32 {
+        let w = 2.0 * PI * frequency / sample_rate.max(1) as f32;
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+        // H(e^jw) = (b0 + b1 e^-jw + b2 e^-2jw) / (1 + a1 e^-jw + a2 e^-2jw)
+        let num_re = self.b0 + self.b1 * cos1 + self.b2 * cos2;
+        let num_im = -(self.b1 * sin1 + self.b2 * sin2);
+        let den_re = 1.0 + self.a1 * cos1 + self.a2 * cos2;
+        let den_im = -(self.a1 * sin1 + self.a2 * sin2);
+        let power = (num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im);
+        (10.0 * power.log10()).max(-120.0)
+    }
+}
+
+/// State of one biquad filter for one channel
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadState {
+    /// Filters one sample
+    ///
+    /// # Arguments
+    /// * `coefficients` - The filter to apply
+    /// * `input` - The next input sample
+    ///
+    /// # Returns
+    /// The next output sample
+    pub fn process(&mut self, coefficients: &Coefficients, input: f32) -> f32 {
+        let c = coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn response(kind: FilterKind, gain_db: f32, frequency: f32) -> f32 {
+        Coefficients::new(kind, 1000.0, gain_db, 0.71, SAMPLE_RATE)
+            .response_db(frequency, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn peaking_band_reaches_its_gain_at_the_center_only() {
+        for gain_db in [-12.0, 6.0, 12.0] {
+            assert!((response(FilterKind::Peaking, gain_db, 1000.0) - gain_db).abs() < 0.01);
+            assert!(response(FilterKind::Peaking, gain_db, 20.0).abs() < 0.1);
+            assert!(response(FilterKind::Peaking, gain_db, 20_000.0).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn shelves_settle_at_their_gain() {
+        assert!((response(FilterKind::LowShelf, 9.0, 20.0) - 9.0).abs() < 0.1);
+        assert!(response(FilterKind::LowShelf, 9.0, 20_000.0).abs() < 0.1);
+        assert!((response(FilterKind::HighShelf, -9.0, 20_000.0) + 9.0).abs() < 0.1);
+        assert!(response(FilterKind::HighShelf, -9.0, 20.0).abs() < 0.1);
+        // Half the gain at the corner frequency
+        assert!((response(FilterKind::LowShelf, 9.0, 1000.0) - 4.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn pass_filters_are_3_db_down_at_the_corner() {
+        let low_pass =
+            Coefficients::new(FilterKind::LowPass, 1000.0, 0.0, FRAC_1_SQRT_2, SAMPLE_RATE);
+        assert!((low_pass.response_db(1000.0, SAMPLE_RATE) + 3.01).abs() < 0.05);
+        assert!(low_pass.response_db(20.0, SAMPLE_RATE).abs() < 0.01);
+        assert!(low_pass.response_db(16_000.0, SAMPLE_RATE) < -40.0);
+
+        let high_pass = Coefficients::new(
+            FilterKind::HighPass,
+            1000.0,
+            0.0,
+            FRAC_1_SQRT_2,
+            SAMPLE_RATE,
+        );
+        assert!((high_pass.response_db(1000.0, SAMPLE_RATE) + 3.01).abs() < 0.05);
+        assert!(high_pass.response_db(20.0, SAMPLE_RATE) < -60.0);
+    }
+
+    #[test]
+    fn filtering_a_sine_matches_the_response() {
+        let coefficients = Coefficients::new(FilterKind::Peaking, 1000.0, 6.0, 1.0, SAMPLE_RATE);
+        let mut state = BiquadState::default();
+        let mut peak = 0.0f32;
+        for n in 0..SAMPLE_RATE as usize {
+            let x = (2.0 * PI * 1000.0 * n as f32 / SAMPLE_RATE as f32).sin();
+            let y = state.process(&coefficients, x);
+            // Skip the transient at the start
+            if n > SAMPLE_RATE as usize / 2 {
+                peak = peak.max(y.abs());
+            }
+        }
+        let gain_db = 20.0 * peak.log10();
+        assert!((gain_db - 6.0).abs() < 0.05, "{}", gain_db);
+    }
+}
@@ -0,0 +1,138 @@
+//! Bitcrusher
+//!
+//! Lowers the resolution of the audio in both dimensions: samples are
+//! rounded to fewer bits, and each frame is held for several frames, which
+//! lowers the effective sample rate. The crushed audio is mixed with the
+//! original.
+
+use super::effect::{AudioEffect, EffectParam};
+use crate::output::StreamFormat;
+
+/// Parameters of the bitcrusher
+const PARAMS: &[EffectParam] = &[
+    EffectParam::new("Bits", 8.0, 1.0, 16.0, 1.0, ""),
+    EffectParam::new("Downsample", 4.0, 1.0, 32.0, 1.0, "x"),
+    EffectParam::new("Mix", 1.0, 0.0, 1.0, 0.05, ""),
+];
+
+/// Bit depth and sample rate reducer
+pub struct Bitcrusher {
+    /// Bits each sample is rounded to
+    bits: f32,
+    /// Frames each held frame lasts
+    downsample: usize,
+    /// Share of the crushed audio in the output
+    mix: f32,
+    /// Number of interleaved channels
+    channels: usize,
+    /// The frame being held, one sample per channel
+    held: Vec<f32>,
+    /// Frames left until the next frame is taken
+    hold_remaining: usize,
+}
+
+impl Bitcrusher {
+    /// Creates a bitcrusher at its default settings
+    pub fn new() -> Self {
+        Bitcrusher {
+            bits: PARAMS[0].default,
+            downsample: PARAMS[1].default as usize,
+            mix: PARAMS[2].default,
+            channels: 0,
+            held: Vec::new(),
+            hold_remaining: 0,
+        }
+    }
+}
+
+impl AudioEffect for Bitcrusher {
+    fn name(&self) -> &'static str {
+        "Bitcrusher"
+    }
+
+    fn params(&self) -> &'static [EffectParam] {
+        PARAMS
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        match name {
+            "Bits" => self.bits = value.round().max(1.0),
+            "Downsample" => self.downsample = (value.round() as usize).max(1),
+            "Mix" => self.mix = value,
+            _ => {}
+        }
+    }
+
+    fn prepare(&mut self, format: StreamFormat) {
+        self.channels = format.channels.max(1);
+        self.held = vec![0.0; self.channels];
+    }
+
+    fn process(&mut self, data: &mut [f32]) {
+        // Not prepared for a stream yet
+        if self.channels == 0 {
+            return;
+        }
+        let levels = 2f32.powf(self.bits - 1.0);
+        for frame in data.chunks_mut(self.channels) {
+            if self.hold_remaining == 0 {
+                for (held, &sample) in self.held.iter_mut().zip(frame.iter()) {
+                    *held = (sample * levels).round() / levels;
+                }
+                self.hold_remaining = self.downsample;
+            }
+            self.hold_remaining -= 1;
+            for (sample, &held) in frame.iter_mut().zip(&self.held) {
+                *sample += (held - *sample) * self.mix;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.held.fill(0.0);
+        self.hold_remaining = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared(channels: usize) -> Bitcrusher {
+        let mut bitcrusher = Bitcrusher::new();
+        bitcrusher.prepare(StreamFormat {
+            channels,
+            sample_rate: 48_000,
+        });
+        bitcrusher
+    }
+
+    #[test]
+    fn unprepared_bitcrusher_leaves_the_audio_alone() {
+        let mut data = vec![0.3; 8];
+        Bitcrusher::new().process(&mut data);
+        assert_eq!(data, [0.3; 8]);
+    }
+
+    #[test]
+    fn rounds_and_holds_each_frame() {
+        let mut bitcrusher = prepared(2);
+        bitcrusher.set_param("Bits", 2.0);
+        bitcrusher.set_param("Downsample", 2.0);
+        // Two bits leave the levels -0.5, 0.0 and 0.5
+        let mut data = vec![0.3, -0.3, 0.1, 0.1, -0.4, 0.9, 0.0, 0.0];
+        bitcrusher.process(&mut data);
+        assert_eq!(data, [0.5, -0.5, 0.5, -0.5, -0.5, 1.0, -0.5, 1.0]);
+    }
+
+    #[test]
+    fn mix_blends_in_the_original() {
+        let mut bitcrusher = prepared(1);
+        bitcrusher.set_param("Bits", 2.0);
+        bitcrusher.set_param("Downsample", 1.0);
+        bitcrusher.set_param("Mix", 0.5);
+        let mut data = vec![0.3, 0.1];
+        bitcrusher.process(&mut data);
+        assert!((data[0] - 0.4).abs() < 1e-6 && (data[1] - 0.05).abs() < 1e-6);
+    }
+}
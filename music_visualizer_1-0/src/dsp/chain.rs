@@ -0,0 +1,275 @@
+//! Ordered chain of audio effects
+//!
+//! The chain is split in two halves:
+//! - `EffectChain`, owned by the player, which the UI changes and reads back
+//! - `EffectRack`, shared with the audio callback, which holds the effects
+//!   and runs them in order
+//!
+//! Bypass switches and parameter values live in atomics per effect, so
+//! changing them never waits for the audio callback. The callback hands new
+//! values to an effect at the start of the next buffer. Reordering swaps the
+//! effects themselves and briefly locks the rack, the same way a track change
+//! locks the deck. The callback never waits for that lock: a buffer rendered
+//! while the rack is locked skips the effects.
+
+use super::effect::{AudioEffect, EffectParam};
+use crate::output::StreamFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Bypass switch and parameter values of an effect, as stored in the config file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EffectSettings {
+    /// Name of the effect
+    pub name: String,
+    /// True if the audio skips the effect
+    pub bypassed: bool,
+    /// Parameter values by parameter name
+    pub params: BTreeMap<String, f32>,
+}
+
+/// Settings of one effect shared between the UI and the audio callback
+struct EffectControl {
+    /// Name of the effect
+    name: &'static str,
+    /// Parameters of the effect
+    params: &'static [EffectParam],
+    /// True if the audio skips the effect
+    bypassed: AtomicBool,
+    /// Current value of each parameter, as `f32` bits
+    values: Vec<AtomicU32>,
+    /// Incremented whenever a value changes
+    version: AtomicU64,
+}
+
+impl EffectControl {
+    /// Creates the controls of an effect, bypassed and at default values
+    fn new(effect: &dyn AudioEffect) -> Self {
+        let params = effect.params();
+        EffectControl {
+            name: effect.name(),
+            params,
+            bypassed: AtomicBool::new(true),
+            values: params
+                .iter()
+                .map(|param| AtomicU32::new(param.default.to_bits()))
+                .collect(),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the current value of the parameter at an index
+    fn value(&self, index: usize) -> f32 {
+        f32::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+
+    /// Sets a parameter by name, clamped to its range; unknown names are ignored
+    fn set_value(&self, name: &str, value: f32) {
+        if let Some(index) = self.params.iter().position(|param| param.name == name) {
+            let param = &self.params[index];
+            let value = value.clamp(param.min, param.max);
+            self.values[index].store(value.to_bits(), Ordering::Relaxed);
+            self.version.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+/// An effect as run by the audio callback
+struct Slot {
+    /// The effect
+    effect: Box<dyn AudioEffect>,
+    /// Settings changed by the UI
+    control: Arc<EffectControl>,
+    /// Version of the values last handed to the effect
+    version: Option<u64>,
+    /// Whether the effect was skipped in the previous buffer
+    was_bypassed: bool,
+}
+
+impl Slot {
+    /// Hands the effect any parameter values changed since the last buffer
+    fn sync_params(&mut self) {
+        let version = self.control.version.load(Ordering::Acquire);
+        if self.version == Some(version) {
+            return;
+        }
+        for (index, param) in self.control.params.iter().enumerate() {
+            self.effect.set_param(param.name, self.control.value(index));
+        }
+        self.version = Some(version);
+    }
+}
+
+/// The effects in processing order, shared with the audio callback
+#[derive(Default)]
+pub struct EffectRack {
+    /// Effects in processing order
+    slots: Vec<Slot>,
+}
+
+impl EffectRack {
+    /// Gets every effect ready for a new stream
+    ///
+    /// # Arguments
+    /// * `format` - Channels and sample rate of the processed audio
+    pub fn prepare(&mut self, format: StreamFormat) {
+        for slot in &mut self.slots {
+            slot.effect.prepare(format);
+            slot.effect.reset();
+            slot.version = None;
+        }
+    }
+
+    /// Runs a buffer of interleaved samples through the effects in order
+    ///
+    /// An effect is reset when it comes out of bypass, so no audio from
+    /// before the bypass rings out.
+    pub fn process(&mut self, data: &mut [f32]) {
+        for slot in &mut self.slots {
+            slot.sync_params();
+            let bypassed = slot.control.bypassed.load(Ordering::Relaxed);
+            if !bypassed {
+                if slot.was_bypassed {
+                    slot.effect.reset();
+                }
+                slot.effect.process(data);
+            }
+            slot.was_bypassed = bypassed;
+        }
+    }
+}
+
+/// The effect chain as seen by the UI
+pub struct EffectChain {
+    /// Effects shared with the audio callback
+    rack: Arc<Mutex<EffectRack>>,
+    /// Settings of the effects, in the same order as the rack
+    controls: Vec<Arc<EffectControl>>,
+}
+
+impl EffectChain {
+    /// Creates a chain of effects, all bypassed and at default values
+    ///
+    /// # Arguments
+    /// * `effects` - The effects in processing order
+    pub fn new(effects: Vec<Box<dyn AudioEffect>>) -> Self {
+        let slots: Vec<Slot> = effects
+            .into_iter()
+            .map(|effect| Slot {
+                control: Arc::new(EffectControl::new(effect.as_ref())),
+                effect,
+                version: None,
+                was_bypassed: true,
+            })
+            .collect();
+        EffectChain {
+            controls: slots.iter().map(|slot| slot.control.clone()).collect(),
+            rack: Arc::new(Mutex::new(EffectRack { slots })),
+        }
+    }
+
+    /// Returns the effects for the audio callback to run
+    pub fn rack(&self) -> Arc<Mutex<EffectRack>> {
+        self.rack.clone()
+    }
+
+    /// Returns the parameters of the effect at a position, or none if there
+    /// is no such effect
+    pub fn params(&self, index: usize) -> &'static [EffectParam] {
+        self.controls
+            .get(index)
+            .map_or(&[], |control| control.params)
+    }
+
+    /// Returns the bypass switch and parameter values of every effect, in
+    /// processing order
+    pub fn settings(&self) -> Vec<EffectSettings> {
+        self.controls
+            .iter()
+            .map(|control| EffectSettings {
+                name: control.name.to_string(),
+                bypassed: control.bypassed.load(Ordering::Relaxed),
+                params: control
+                    .params
+                    .iter()
+                    .enumerate()
+                    .map(|(index, param)| (param.name.to_string(), control.value(index)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Restores settings saved with `settings`
+    ///
+    /// Effects are put in the saved order; effects missing from the saved
+    /// settings keep their place after the ones that were saved. Unknown
+    /// effects and parameters are ignored.
+    pub fn apply_settings(&mut self, settings: &[EffectSettings]) {
+        let mut position = 0;
+        for saved in settings {
+            let Some(offset) = self.controls[position..]
+                .iter()
+                .position(|control| control.name == saved.name)
+            else {
+                continue;
+            };
+            self.move_effect(position + offset, position);
+            let control = &self.controls[position];
+            control.bypassed.store(saved.bypassed, Ordering::Relaxed);
+            for (name, &value) in &saved.params {
+                control.set_value(name, value);
+            }
+            position += 1;
+        }
+    }
+
+    /// Bypasses an effect or puts it back in the chain
+    ///
+    /// # Arguments
+    /// * `index` - Position of the effect in the chain
+    /// * `bypassed` - True to have the audio skip the effect
+    pub fn set_bypassed(&self, index: usize, bypassed: bool) {
+        if let Some(control) = self.controls.get(index) {
+            control.bypassed.store(bypassed, Ordering::Relaxed);
+        }
+    }
+
+    /// Changes a parameter of an effect, clamped to the parameter's range
+    ///
+    /// # Arguments
+    /// * `index` - Position of the effect in the chain
+    /// * `name` - Name of the parameter
+    /// * `value` - New value
+    pub fn set_param(&self, index: usize, name: &str, value: f32) {
+        if let Some(control) = self.controls.get(index) {
+            control.set_value(name, value);
+        }
+    }
+
+    /// Moves an effect to another position, shifting the ones in between
+    ///
+    /// # Arguments
+    /// * `from` - Current position of the effect
+    /// * `to` - New position of the effect, clamped to the end of the chain
+    pub fn move_effect(&mut self, from: usize, to: usize) {
+        if from >= self.controls.len() {
+            return;
+        }
+        let to = to.min(self.controls.len() - 1);
+        if from == to {
+            return;
+        }
+        let control = self.controls.remove(from);
+        self.controls.insert(to, control);
+        let mut rack = self.lock_rack();
+        let slot = rack.slots.remove(from);
+        rack.slots.insert(to, slot);
+    }
+
+    /// Locks the rack, ignoring a panic in another holder of the lock
+    fn lock_rack(&self) -> MutexGuard<'_, EffectRack> {
+        self.rack.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,168 @@
+//! Dynamic range compressor
+//!
+//! Turns the audio down by `ratio` above the threshold. The level is the
+//! loudest channel of each frame, so the stereo image does not shift, and the
+//! gain reduction follows it with separate attack and release times.
+
+use super::effect::{AudioEffect, EffectParam};
+use crate::output::StreamFormat;
+
+/// Parameters of the compressor
+const PARAMS: &[EffectParam] = &[
+    EffectParam::new("Threshold", -18.0, -60.0, 0.0, 1.0, "dB"),
+    EffectParam::new("Ratio", 4.0, 1.0, 20.0, 0.5, ":1"),
+    EffectParam::new("Attack", 10.0, 0.1, 100.0, 0.1, "ms"),
+    EffectParam::new("Release", 150.0, 10.0, 1000.0, 10.0, "ms"),
+    EffectParam::new("Makeup", 0.0, 0.0, 24.0, 0.5, "dB"),
+];
+
+/// Level treated as silence, in dBFS
+const SILENCE_DB: f32 = -120.0;
+
+/// Feed-forward compressor with a stereo-linked peak detector
+pub struct Compressor {
+    /// Level above which the audio is turned down, in dBFS
+    threshold_db: f32,
+    /// Input dB above the threshold per output dB above it
+    ratio: f32,
+    /// Time to reach a higher gain reduction, in milliseconds
+    attack_ms: f32,
+    /// Time to let go of gain reduction, in milliseconds
+    release_ms: f32,
+    /// Gain added after compressing, in dB
+    makeup_db: f32,
+    /// Channels and sample rate of the processed audio
+    format: StreamFormat,
+    /// Per-frame smoothing factor while the gain reduction grows
+    attack_coefficient: f32,
+    /// Per-frame smoothing factor while the gain reduction shrinks
+    release_coefficient: f32,
+    /// Current gain reduction in dB
+    reduction_db: f32,
+}
+
+impl Compressor {
+    /// Creates a compressor at its default settings
+    pub fn new() -> Self {
+        let mut compressor = Compressor {
+            threshold_db: PARAMS[0].default,
+            ratio: PARAMS[1].default,
+            attack_ms: PARAMS[2].default,
+            release_ms: PARAMS[3].default,
+            makeup_db: PARAMS[4].default,
+            format: StreamFormat::default(),
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            reduction_db: 0.0,
+        };
+        compressor.update_coefficients();
+        compressor
+    }
+
+    /// Recomputes the smoothing factors from the times and sample rate
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.format.sample_rate.max(1) as f32;
+        let coefficient = |ms: f32| (-1000.0 / (ms.max(0.01) * sample_rate)).exp();
+        self.attack_coefficient = coefficient(self.attack_ms);
+        self.release_coefficient = coefficient(self.release_ms);
+    }
+}
+
+impl AudioEffect for Compressor {
+    fn name(&self) -> &'static str {
+        "Compressor"
+    }
+
+    fn params(&self) -> &'static [EffectParam] {
+        PARAMS
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        match name {
+            "Threshold" => self.threshold_db = value,
+            "Ratio" => self.ratio = value.max(1.0),
+            "Attack" => self.attack_ms = value,
+            "Release" => self.release_ms = value,
+            "Makeup" => self.makeup_db = value,
+            _ => return,
+        }
+        self.update_coefficients();
+    }
+
+    fn prepare(&mut self, format: StreamFormat) {
+        self.format = format;
+        self.update_coefficients();
+    }
+
+    fn process(&mut self, data: &mut [f32]) {
+        let slope = 1.0 - 1.0 / self.ratio;
+        for frame in data.chunks_mut(self.format.channels.max(1)) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let level_db = if peak > 0.0 {
+                (20.0 * peak.log10()).max(SILENCE_DB)
+            } else {
+                SILENCE_DB
+            };
+            let target_db = (level_db - self.threshold_db).max(0.0) * slope;
+            let coefficient = if target_db > self.reduction_db {
+                self.attack_coefficient
+            } else {
+                self.release_coefficient
+            };
+            self.reduction_db = target_db + coefficient * (self.reduction_db - target_db);
+
+            let gain = 10f32.powf((self.makeup_db - self.reduction_db) / 20.0);
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.reduction_db = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// Runs a second of constant stereo audio through a compressor at
+    /// default settings and returns the last frame
+    fn settle(compressor: &mut Compressor, left: f32, right: f32) -> [f32; 2] {
+        compressor.prepare(StreamFormat {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+        });
+        let mut data = [left, right].repeat(SAMPLE_RATE as usize);
+        compressor.process(&mut data);
+        [data[data.len() - 2], data[data.len() - 1]]
+    }
+
+    #[test]
+    fn quiet_audio_passes_unchanged() {
+        let [left, right] = settle(&mut Compressor::new(), 0.1, -0.1);
+        assert!((left - 0.1).abs() < 1e-6 && (right + 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn loud_audio_is_turned_down_by_the_ratio() {
+        // 18 dB over the threshold at 4:1 comes out 4.5 dB over it
+        let expected = 10f32.powf(-13.5 / 20.0);
+        let [left, right] = settle(&mut Compressor::new(), 1.0, 0.1);
+        assert!((left - expected).abs() < 1e-3, "{}", left);
+        // Both channels follow the louder one
+        assert!((right - 0.1 * expected).abs() < 1e-4, "{}", right);
+
+        let mut compressor = Compressor::new();
+        compressor.set_param("Makeup", 6.0);
+        let [left, _] = settle(&mut compressor, 1.0, 0.1);
+        assert!(
+            (left - expected * 10f32.powf(6.0 / 20.0)).abs() < 1e-3,
+            "{}",
+            left
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Interface of the effects in the effect chain
+//!
+//! An effect is prepared for a stream format on the main thread, then runs
+//! on the audio thread: `process`, `set_param` and `reset` are called from
+//! the audio callback and must not block or allocate. Anything that depends
+//! on the format, like buffers per channel, belongs in `prepare`.
+
+use crate::output::StreamFormat;
+
+/// A named setting of an effect
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EffectParam {
+    /// Display name, also used to identify the parameter in `set_param`
+    pub name: &'static str,
+    /// Value the effect starts with
+    pub default: f32,
+    /// Smallest allowed value
+    pub min: f32,
+    /// Largest allowed value
+    pub max: f32,
+    /// Interval menu sliders snap to (0.0 for no snapping)
+    pub step: f32,
+    /// Unit shown after the value, e.g. `dB` or `ms`; empty for none
+    pub unit: &'static str,
+}
+
+impl EffectParam {
+    /// Creates a parameter description
+    pub const fn new(
+        name: &'static str,
+        default: f32,
+        min: f32,
+        max: f32,
+        step: f32,
+        unit: &'static str,
+    ) -> Self {
+        EffectParam {
+            name,
+            default,
+            min,
+            max,
+            step,
+            unit,
+        }
+    }
+
+    /// Formats a value of the parameter for display
+    pub fn display_value(&self, value: f32) -> String {
+        let number = if self.step >= 1.0 {
+            format!("{:.0}", value)
+        } else {
+            format!("{:.2}", value)
+        };
+        if self.unit.is_empty() {
+            number
+        } else {
+            format!("{} {}", number, self.unit)
+        }
+    }
+}
+
+/// Processing applied to the audio between the mixer and the output
+pub trait AudioEffect: Send {
+    /// Returns the name shown in the menu and stored in the config file
+    fn name(&self) -> &'static str;
+
+    /// Returns the effect's parameters
+    fn params(&self) -> &'static [EffectParam] {
+        &[]
+    }
+
+    /// Changes a parameter by name (unknown names are ignored)
+    ///
+    /// # Arguments
+    /// * `name` - Parameter name as reported by `params`
+    /// * `value` - New value, already clamped to the parameter's range
+    fn set_param(&mut self, _name: &str, _value: f32) {}
+
+    /// Gets ready to process a stream; called before the stream starts
+    ///
+    /// # Arguments
+    /// * `format` - Channels and sample rate of the processed audio
+    fn prepare(&mut self, format: StreamFormat);
+
+    /// Processes a buffer of interleaved samples in place
+    fn process(&mut self, data: &mut [f32]);
+
+    /// Forgets the audio processed so far, e.g. filter state and tails
+    fn reset(&mut self);
+}
@@ -0,0 +1,440 @@
+//! Graphic and parametric equalizer
+//!
+//! The equalizer has two modes sharing one chain of biquad filters:
+//! - Graphic: ten peaking bands an octave apart with fixed frequency and Q,
+//!   where only the gains change
+//! - Parametric: up to `MAX_BANDS` bands, each with its own filter kind,
+//!   frequency, gain and Q
+//!
+//! The menu edits the settings through an `EqControl` while the audio
+//! callback filters with an `Equalizer`. The callback only picks up changed
+//! settings when it can take the lock without waiting, and keeps the
+//! previous coefficients otherwise, so it never blocks on the UI.
+//!
+//! Settings can be saved as named JSON presets in `PRESET_DIR`.
+
+use super::biquad::{BiquadState, Coefficients, FilterKind};
+use crate::output::StreamFormat;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use thiserror::Error;
+
+/// Number of bands of the graphic equalizer
+pub const GRAPHIC_BANDS: usize = 10;
+/// Center frequencies of the graphic bands in Hz, an octave apart
+pub const GRAPHIC_FREQUENCIES: [f32; GRAPHIC_BANDS] = [
+    31.0, 63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+/// Q of the graphic bands, about one octave wide
+const GRAPHIC_Q: f32 = 1.41;
+/// Most bands of the parametric equalizer
+pub const MAX_BANDS: usize = 10;
+/// Largest boost or cut of a band in dB
+pub const MAX_GAIN_DB: f32 = 12.0;
+/// Lowest band frequency in Hz
+pub const MIN_FREQUENCY: f32 = 20.0;
+/// Highest band frequency in Hz
+pub const MAX_FREQUENCY: f32 = 20000.0;
+/// Lowest Q, the widest band
+pub const MIN_Q: f32 = 0.1;
+/// Highest Q, the narrowest band
+pub const MAX_Q: f32 = 10.0;
+/// Directory the presets are stored in, one JSON file per preset
+pub const PRESET_DIR: &str = "eq_presets";
+
+/// Errors that can occur while reading or writing a preset
+#[derive(Debug, Error)]
+pub enum PresetError {
+    #[error("preset names must not be empty or contain path separators")]
+    InvalidName,
+    #[error("could not access preset file: {0}")]
+    Io(#[from] io::Error),
+    #[error("preset file is not valid: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// Which set of bands the equalizer uses
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EqMode {
+    /// Fixed bands an octave apart
+    #[default]
+    Graphic,
+    /// Freely placed bands
+    Parametric,
+}
+
+/// One filter of the parametric equalizer
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EqBand {
+    /// Shape of the filter
+    pub kind: FilterKind,
+    /// Center or corner frequency in Hz
+    pub frequency: f32,
+    /// Boost (positive) or cut (negative) in dB
+    pub gain_db: f32,
+    /// Quality factor; higher values give a narrower band
+    pub q: f32,
+}
+
+impl EqBand {
+    /// Creates a flat peaking band
+    ///
+    /// # Arguments
+    /// * `frequency` - Center frequency in Hz
+    pub fn new(frequency: f32) -> Self {
+        EqBand {
+            kind: FilterKind::Peaking,
+            frequency,
+            gain_db: 0.0,
+            q: 0.71,
+        }
+    }
+
+    /// Returns the filter coefficients of the band
+    ///
+    /// # Arguments
+    /// * `sample_rate` - Frames per second of the filtered audio
+    pub fn coefficients(&self, sample_rate: u32) -> Coefficients {
+        Coefficients::new(self.kind, self.frequency, self.gain_db, self.q, sample_rate)
+    }
+}
+
+/// Equalizer settings, stored in the config file and in presets
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    /// False to pass the audio through unchanged
+    pub enabled: bool,
+    /// Which set of bands is used
+    pub mode: EqMode,
+    /// Gain of each graphic band in dB, lowest frequency first
+    pub graphic_gains: [f32; GRAPHIC_BANDS],
+    /// Bands of the parametric mode
+    pub bands: Vec<EqBand>,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        EqSettings {
+            enabled: false,
+            mode: EqMode::Graphic,
+            graphic_gains: [0.0; GRAPHIC_BANDS],
+            bands: vec![
+                EqBand {
+                    kind: FilterKind::LowShelf,
+                    ..EqBand::new(100.0)
+                },
+                EqBand::new(1000.0),
+                EqBand {
+                    kind: FilterKind::HighShelf,
+                    ..EqBand::new(8000.0)
+                },
+            ],
+        }
+    }
+}
+
+impl EqSettings {
+    /// Returns the bands of the current mode; parametric bands past
+    /// `MAX_BANDS` are ignored
+    pub fn active_bands(&self) -> impl Iterator<Item = EqBand> + '_ {
+        let count = match self.mode {
+            EqMode::Graphic => GRAPHIC_BANDS,
+            EqMode::Parametric => self.bands.len().min(MAX_BANDS),
+        };
+        (0..count).map(move |index| match self.mode {
+            EqMode::Graphic => EqBand {
+                gain_db: self.graphic_gains[index],
+                q: GRAPHIC_Q,
+                ..EqBand::new(GRAPHIC_FREQUENCIES[index])
+            },
+            EqMode::Parametric => self.bands[index],
+        })
+    }
+
+    /// Returns the combined gain of all bands at a frequency
+    ///
+    /// # Arguments
+    /// * `frequency` - Frequency in Hz
+    /// * `sample_rate` - Frames per second of the filtered audio
+    ///
+    /// # Returns
+    /// The gain in dB; 0 while the equalizer is disabled
+    pub fn response_db(&self, frequency: f32, sample_rate: u32) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        self.active_bands()
+            .map(|band| {
+                band.coefficients(sample_rate)
+                    .response_db(frequency, sample_rate)
+            })
+            .sum()
+    }
+
+    /// Resets every band to 0 dB, keeping frequencies and filter kinds
+    pub fn flatten(&mut self) {
+        self.graphic_gains = [0.0; GRAPHIC_BANDS];
+        for band in &mut self.bands {
+            band.gain_db = 0.0;
+        }
+    }
+
+    /// Reads a preset from `PRESET_DIR`
+    ///
+    /// Values outside the ranges the menu offers are clamped into them, so a
+    /// hand-edited preset cannot produce extreme or unstable filters.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the preset, without the `.json` extension
+    pub fn load_preset(name: &str) -> Result<Self, PresetError> {
+        let json = fs::read_to_string(preset_path(name)?)?;
+        let mut settings: EqSettings = serde_json::from_str(&json)?;
+        settings.clamp_to_ranges();
+        Ok(settings)
+    }
+
+    /// Clamps every gain, frequency and Q into the ranges the menu offers
+    fn clamp_to_ranges(&mut self) {
+        for gain_db in &mut self.graphic_gains {
+            *gain_db = clamp_or(*gain_db, -MAX_GAIN_DB, MAX_GAIN_DB, 0.0);
+        }
+        for band in &mut self.bands {
+            band.gain_db = clamp_or(band.gain_db, -MAX_GAIN_DB, MAX_GAIN_DB, 0.0);
+            band.frequency = clamp_or(band.frequency, MIN_FREQUENCY, MAX_FREQUENCY, 1000.0);
+            band.q = clamp_or(band.q, MIN_Q, MAX_Q, 0.71);
+        }
+    }
+
+    /// Writes the settings as a preset to `PRESET_DIR`, replacing any preset
+    /// of the same name
+    ///
+    /// # Arguments
+    /// * `name` - Name of the preset, without the `.json` extension
+    pub fn save_preset(&self, name: &str) -> Result<(), PresetError> {
+        let path = preset_path(name)?;
+        fs::create_dir_all(PRESET_DIR)?;
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Returns the names of the saved presets in alphabetical order
+///
+/// A missing preset directory gives an empty list.
+pub fn preset_names() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(PRESET_DIR) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+/// Clamps a value into a range, replacing NaN with a fallback
+fn clamp_or(value: f32, min: f32, max: f32, fallback: f32) -> f32 {
+    if value.is_nan() {
+        fallback
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+/// Returns the file of a preset, rejecting names that would leave `PRESET_DIR`
+fn preset_path(name: &str) -> Result<PathBuf, PresetError> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(PresetError::InvalidName);
+    }
+    Ok(Path::new(PRESET_DIR).join(format!("{}.json", name)))
+}
+
+/// Equalizer settings shared between the UI and the audio callback
+pub struct EqControl {
+    /// Current settings
+    settings: Mutex<EqSettings>,
+    /// Incremented on every change, so the callback knows when to update
+    version: AtomicU64,
+}
+
+impl EqControl {
+    /// Creates shared equalizer settings
+    ///
+    /// # Arguments
+    /// * `settings` - The initial settings, clamped into the offered ranges
+    pub fn new(mut settings: EqSettings) -> Self {
+        settings.clamp_to_ranges();
+        EqControl {
+            settings: Mutex::new(settings),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a copy of the current settings
+    pub fn settings(&self) -> EqSettings {
+        self.lock().clone()
+    }
+
+    /// Changes the settings and lets the audio callback know
+    ///
+    /// # Arguments
+    /// * `change` - Edits the settings in place
+    pub fn update(&self, change: impl FnOnce(&mut EqSettings)) {
+        change(&mut self.lock());
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Locks the settings, ignoring a panic in another holder of the lock
+    fn lock(&self) -> MutexGuard<'_, EqSettings> {
+        self.settings.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The equalizer as run in the audio callback
+pub struct Equalizer {
+    /// Settings edited by the UI
+    control: Arc<EqControl>,
+    /// Version of the settings the coefficients were computed from
+    version: Option<u64>,
+    /// Channels and sample rate of the filtered audio
+    format: StreamFormat,
+    /// False to pass the audio through unchanged
+    enabled: bool,
+    /// One filter per active band
+    coefficients: Vec<Coefficients>,
+    /// Filter state per band and channel, band-major
+    states: Vec<BiquadState>,
+}
+
+impl Equalizer {
+    /// Creates an equalizer for a stream, allocating for the most bands up
+    /// front so that setting changes do not allocate in the callback
+    ///
+    /// # Arguments
+    /// * `control` - Settings edited by the UI
+    /// * `format` - Channels and sample rate of the filtered audio
+    pub fn new(control: Arc<EqControl>, format: StreamFormat) -> Self {
+        let most_bands = MAX_BANDS.max(GRAPHIC_BANDS);
+        let mut equalizer = Equalizer {
+            control,
+            version: None,
+            format,
+            enabled: false,
+            coefficients: Vec::with_capacity(most_bands),
+            states: vec![BiquadState::default(); most_bands * format.channels.max(1)],
+        };
+        equalizer.refresh();
+        equalizer
+    }
+
+    /// Filters a buffer of interleaved samples in place
+    pub fn process(&mut self, data: &mut [f32]) {
+        self.refresh();
+        if !self.enabled || self.coefficients.is_empty() {
+            return;
+        }
+        let channels = self.format.channels.max(1);
+        for frame in data.chunks_mut(channels) {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let states = self.states.iter_mut().skip(channel).step_by(channels);
+                *sample = self
+                    .coefficients
+                    .iter()
+                    .zip(states)
+                    .fold(*sample, |x, (c, state)| state.process(c, x));
+            }
+        }
+    }
+
+    /// Recomputes the coefficients if the settings changed and are not
+    /// being edited right now
+    ///
+    /// The filter state is cleared when bands are added or removed, or the
+    /// equalizer is switched on, so no stale state rings out.
+    fn refresh(&mut self) {
+        let version = self.control.version.load(Ordering::Acquire);
+        if self.version == Some(version) {
+            return;
+        }
+        let Ok(settings) = self.control.settings.try_lock() else {
+            return;
+        };
+        let band_count = self.coefficients.len();
+        let was_enabled = self.enabled;
+        self.enabled = settings.enabled;
+        self.coefficients.clear();
+        self.coefficients.extend(
+            settings
+                .active_bands()
+                .map(|band| band.coefficients(self.format.sample_rate)),
+        );
+        drop(settings);
+        if self.coefficients.len() != band_count || (self.enabled && !was_enabled) {
+            self.states.fill(BiquadState::default());
+        }
+        self.version = Some(version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_bands_are_clamped() {
+        let json = r#"{
+            "enabled": true,
+            "mode": "parametric",
+            "graphic_gains": [40.0, -40.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 3.0],
+            "bands": [
+                {"kind": "peaking", "frequency": 5.0, "gain_db": 60.0, "q": 0.0},
+                {"kind": "high_shelf", "frequency": 96000.0, "gain_db": -60.0, "q": 50.0}
+            ]
+        }"#;
+        let mut settings: EqSettings = serde_json::from_str(json).unwrap();
+        settings.clamp_to_ranges();
+
+        assert_eq!(settings.graphic_gains[0], MAX_GAIN_DB);
+        assert_eq!(settings.graphic_gains[1], -MAX_GAIN_DB);
+        assert_eq!(settings.graphic_gains[9], 3.0);
+        let low = settings.bands[0];
+        assert_eq!(
+            (low.frequency, low.gain_db, low.q),
+            (MIN_FREQUENCY, MAX_GAIN_DB, MIN_Q)
+        );
+        let high = settings.bands[1];
+        assert_eq!(
+            (high.frequency, high.gain_db, high.q),
+            (MAX_FREQUENCY, -MAX_GAIN_DB, MAX_Q)
+        );
+    }
+
+    #[test]
+    fn graphic_bands_add_up_in_the_response() {
+        let mut settings = EqSettings {
+            enabled: true,
+            ..EqSettings::default()
+        };
+        assert!(settings.response_db(1000.0, 48_000).abs() < 0.01);
+
+        settings.graphic_gains[5] = 6.0;
+        let at_band = settings.response_db(GRAPHIC_FREQUENCIES[5], 48_000);
+        assert!((at_band - 6.0).abs() < 0.5, "{}", at_band);
+
+        settings.enabled = false;
+        assert_eq!(settings.response_db(GRAPHIC_FREQUENCIES[5], 48_000), 0.0);
+    }
+}
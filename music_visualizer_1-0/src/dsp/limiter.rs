@@ -0,0 +1,316 @@
+//! Look-ahead brickwall limiter
+//!
+//! The last stage before the device, after the volume, so no gain stage
+//! before it (equalizer boosts, normalization, effects) can clip:
+//! - The audio is delayed by a short look-ahead, so the gain can already be
+//!   down when a peak comes out of the delay instead of reacting late
+//! - The gain needed for the loudest channel of every frame in the
+//!   look-ahead is held, then released slowly once the peaks have passed
+//! - The held gain is averaged over the look-ahead, which ramps it down
+//!   smoothly but still reaches the needed gain in time
+//! - Whatever remains above the ceiling, e.g. from rounding, is clipped at
+//!   the ceiling
+//!
+//! The settings live in atomics, like the volume, so the menu changes them
+//! without locking the audio callback. The callback reports the gain
+//! reduction and whether anything above full scale reached the device.
+
+use crate::output::StreamFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+/// Lowest ceiling offered, in dBFS
+pub const MIN_CEILING_DB: f32 = -12.0;
+/// Highest ceiling offered, in dBFS
+pub const MAX_CEILING_DB: f32 = 0.0;
+/// Shortest release offered, in milliseconds
+pub const MIN_RELEASE_MS: f32 = 10.0;
+/// Longest release offered, in milliseconds
+pub const MAX_RELEASE_MS: f32 = 1000.0;
+/// Delay that lets the gain go down before a peak, in milliseconds
+const LOOKAHEAD_MS: f32 = 1.5;
+
+/// Limiter settings
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimiterSettings {
+    /// True if the limiter holds peaks at the ceiling
+    pub enabled: bool,
+    /// Highest level that reaches the device, in dBFS
+    pub ceiling_db: f32,
+    /// Time to recover from gain reduction, in milliseconds
+    pub release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        LimiterSettings {
+            enabled: true,
+            ceiling_db: -1.0,
+            release_ms: 100.0,
+        }
+    }
+}
+
+/// Limiter settings and readouts shared between the UI and the audio callback
+pub struct LimiterControl {
+    /// True if the limiter holds peaks at the ceiling
+    enabled: AtomicBool,
+    /// Ceiling in dBFS, as `f32` bits
+    ceiling_db: AtomicU32,
+    /// Release in milliseconds, as `f32` bits
+    release_ms: AtomicU32,
+    /// Largest gain reduction in the last buffer in dB, as `f32` bits
+    gain_reduction_db: AtomicU32,
+    /// Number of buffers that sent samples above full scale to the device
+    clipped_buffers: AtomicUsize,
+}
+
+impl LimiterControl {
+    /// Creates the shared settings
+    ///
+    /// # Arguments
+    /// * `settings` - Initial settings, clamped to the offered ranges
+    pub fn new(settings: LimiterSettings) -> Self {
+        let control = LimiterControl {
+            enabled: AtomicBool::new(settings.enabled),
+            ceiling_db: AtomicU32::new(0),
+            release_ms: AtomicU32::new(0),
+            gain_reduction_db: AtomicU32::new(0.0f32.to_bits()),
+            clipped_buffers: AtomicUsize::new(0),
+        };
+        control.set_ceiling_db(settings.ceiling_db);
+        control.set_release_ms(settings.release_ms);
+        control
+    }
+
+    /// Returns the current settings
+    pub fn settings(&self) -> LimiterSettings {
+        LimiterSettings {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            ceiling_db: f32::from_bits(self.ceiling_db.load(Ordering::Relaxed)),
+            release_ms: f32::from_bits(self.release_ms.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Switches the limiter on or off
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the ceiling, clamped to `MIN_CEILING_DB..=MAX_CEILING_DB`
+    pub fn set_ceiling_db(&self, ceiling_db: f32) {
+        let ceiling_db = ceiling_db.clamp(MIN_CEILING_DB, MAX_CEILING_DB);
+        self.ceiling_db
+            .store(ceiling_db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Sets the release, clamped to `MIN_RELEASE_MS..=MAX_RELEASE_MS`
+    pub fn set_release_ms(&self, release_ms: f32) {
+        let release_ms = release_ms.clamp(MIN_RELEASE_MS, MAX_RELEASE_MS);
+        self.release_ms
+            .store(release_ms.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the largest gain reduction in the last buffer, in dB (0.0 or
+    /// more)
+    pub fn gain_reduction_db(&self) -> f32 {
+        f32::from_bits(self.gain_reduction_db.load(Ordering::Relaxed))
+    }
+
+    /// Returns how many buffers sent samples above full scale to the device
+    ///
+    /// Only possible while the limiter is off; a change means the output
+    /// just clipped.
+    pub fn clip_count(&self) -> usize {
+        self.clipped_buffers.load(Ordering::Relaxed)
+    }
+}
+
+/// Limiter state of an output stream; runs in the audio callback
+pub struct Limiter {
+    /// Settings and readouts shared with the UI
+    control: Arc<LimiterControl>,
+    /// Number of interleaved channels
+    channels: usize,
+    /// Sample rate in Hz
+    sample_rate: u32,
+    /// Interleaved samples waiting to come out, one look-ahead long
+    delay: VecDeque<f32>,
+    /// Gain each frame in the look-ahead and the one coming out needs
+    required: VecDeque<f32>,
+    /// Released gain of the last look-ahead's frames, averaged for output
+    released: VecDeque<f32>,
+    /// Held gain after release, before averaging
+    envelope: f32,
+}
+
+impl Limiter {
+    /// Creates the limiter for a stream, starting with an empty look-ahead
+    ///
+    /// # Arguments
+    /// * `control` - Settings and readouts shared with the UI
+    /// * `format` - Channels and sample rate of the stream
+    pub fn new(control: Arc<LimiterControl>, format: StreamFormat) -> Self {
+        let channels = format.channels.max(1);
+        let lookahead = ((LOOKAHEAD_MS / 1000.0 * format.sample_rate as f32) as usize).max(1);
+        Limiter {
+            control,
+            channels,
+            sample_rate: format.sample_rate,
+            delay: VecDeque::from(vec![0.0; lookahead * channels]),
+            required: VecDeque::from(vec![1.0; lookahead + 1]),
+            released: VecDeque::from(vec![1.0; lookahead]),
+            envelope: 1.0,
+        }
+    }
+
+    /// Limits an interleaved buffer in place
+    ///
+    /// While off, the audio still runs through the delay at the released
+    /// gain, so switching never jumps in time or level.
+    pub fn process(&mut self, data: &mut [f32]) {
+        let settings = self.control.settings();
+        let ceiling = 10f32.powf(settings.ceiling_db / 20.0);
+        let release_frames = settings.release_ms / 1000.0 * self.sample_rate as f32;
+        let release_coefficient = (-1.0 / release_frames.max(1.0)).exp();
+        let mut lowest_gain = 1.0f32;
+        let mut clipped = false;
+
+        for frame in data.chunks_mut(self.channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let required = if settings.enabled && peak > ceiling {
+                ceiling / peak
+            } else {
+                1.0
+            };
+            self.required.pop_front();
+            self.required.push_back(required);
+
+            // Hold the lowest gain until the frame needing it has come out
+            let held = self.required.iter().copied().fold(1.0, f32::min);
+            self.envelope = if held < self.envelope {
+                held
+            } else {
+                held + (self.envelope - held) * release_coefficient
+            };
+            self.released.pop_front();
+            self.released.push_back(self.envelope);
+            let gain = self.released.iter().sum::<f32>() / self.released.len() as f32;
+            lowest_gain = lowest_gain.min(gain);
+
+            for sample in frame.iter_mut() {
+                self.delay.push_back(*sample);
+                let delayed = self.delay.pop_front().unwrap_or(0.0) * gain;
+                *sample = if settings.enabled {
+                    delayed.clamp(-ceiling, ceiling)
+                } else {
+                    delayed
+                };
+                clipped |= sample.abs() > 1.0;
+            }
+        }
+
+        let reduction_db = -20.0 * lowest_gain.log10();
+        self.control
+            .gain_reduction_db
+            .store(reduction_db.max(0.0).to_bits(), Ordering::Relaxed);
+        if clipped {
+            self.control.clipped_buffers.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: StreamFormat = StreamFormat {
+        sample_rate: 48_000,
+        channels: 2,
+    };
+
+    fn limiter(settings: LimiterSettings) -> (Limiter, Arc<LimiterControl>) {
+        let control = Arc::new(LimiterControl::new(settings));
+        (Limiter::new(control.clone(), FORMAT), control)
+    }
+
+    /// Returns a stereo buffer of `frames` frames at `level` with a spike at
+    /// frame `spike`
+    fn with_spike(frames: usize, level: f32, spike: usize) -> Vec<f32> {
+        let mut data = vec![level; frames * 2];
+        data[spike * 2] = 1.0;
+        data[spike * 2 + 1] = -1.0;
+        data
+    }
+
+    #[test]
+    fn full_scale_spike_stays_below_the_ceiling() {
+        let (mut limiter, control) = limiter(LimiterSettings::default());
+        let ceiling = 10f32.powf(-1.0 / 20.0);
+        let mut data = with_spike(1000, 0.0, 100);
+        limiter.process(&mut data);
+
+        let peak = data.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak <= ceiling + 1e-6, "{}", peak);
+        assert!((control.gain_reduction_db() - 1.0).abs() < 0.01);
+        assert_eq!(control.clip_count(), 0);
+    }
+
+    #[test]
+    fn gain_is_down_before_the_spike_comes_out() {
+        let (mut limiter, _) = limiter(LimiterSettings::default());
+        let lookahead = limiter.delay.len() / 2;
+        let ceiling = 10f32.powf(-1.0 / 20.0);
+        let mut data = with_spike(1000, 0.5, 500);
+        limiter.process(&mut data);
+
+        // The spike itself only needs scaling, not clipping
+        let spike = 500 + lookahead;
+        assert!(
+            (data[spike * 2] - ceiling).abs() < 1e-4,
+            "{}",
+            data[spike * 2]
+        );
+        assert!((data[spike * 2 + 1] + ceiling).abs() < 1e-4);
+        // The gain ramps down over the look-ahead in front of it
+        assert!(data[(spike - lookahead / 2) * 2] < 0.5 * 0.96);
+        assert!((data[(spike - lookahead - 1) * 2] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gain_releases_back_to_unity() {
+        let (mut limiter, control) = limiter(LimiterSettings::default());
+        let mut data = with_spike(1000, 0.5, 0);
+        limiter.process(&mut data);
+        assert!(control.gain_reduction_db() > 0.9);
+
+        // Ten release times later
+        let mut data = vec![0.5; 48_000 * 2];
+        limiter.process(&mut data);
+        let last = data[data.len() - 1];
+        assert!((last - 0.5).abs() < 1e-3, "{}", last);
+        let mut data = vec![0.5; 64];
+        limiter.process(&mut data);
+        assert!(control.gain_reduction_db() < 0.01);
+    }
+
+    #[test]
+    fn disabled_limiter_only_delays() {
+        let (mut limiter, control) = limiter(LimiterSettings {
+            enabled: false,
+            ..LimiterSettings::default()
+        });
+        let lookahead = limiter.delay.len() / 2;
+        let input: Vec<f32> = (0..2000).map(|n| (n as f32 * 0.37).sin() * 1.5).collect();
+        let mut data = input.clone();
+        limiter.process(&mut data);
+
+        assert!(data[..lookahead * 2].iter().all(|&s| s == 0.0));
+        assert_eq!(data[lookahead * 2..], input[..input.len() - lookahead * 2]);
+        assert_eq!(control.gain_reduction_db(), 0.0);
+        assert_eq!(control.clip_count(), 1);
+    }
+}
@@ -0,0 +1,40 @@
+//! Signal processing applied to the audio on its way to the output
+//!
+//! - `biquad`: second-order filters, the building block of the equalizer
+//! - `eq`: graphic and parametric equalizer with presets
+//! - `effect`: the `AudioEffect` trait for processing stages
+//! - `chain`: the ordered, bypassable chain the effects run in
+//! - `compressor`, `widener`, `bitcrusher`: the effects the chain starts with
+//! - `limiter`: look-ahead brickwall limiter in front of the device
+
+mod biquad;
+mod bitcrusher;
+mod chain;
+mod compressor;
+mod effect;
+mod eq;
+mod limiter;
+mod widener;
+
+pub use bitcrusher::Bitcrusher;
+pub use chain::{EffectChain, EffectRack, EffectSettings};
+pub use compressor::Compressor;
+pub use effect::AudioEffect;
+pub use eq::{
+    EqBand, EqControl, EqMode, EqSettings, Equalizer, GRAPHIC_FREQUENCIES, MAX_BANDS,
+    MAX_FREQUENCY, MAX_GAIN_DB, MAX_Q, MIN_FREQUENCY, MIN_Q, preset_names,
+};
+pub use limiter::{
+    Limiter, LimiterControl, LimiterSettings, MAX_CEILING_DB, MAX_RELEASE_MS, MIN_CEILING_DB,
+    MIN_RELEASE_MS,
+};
+pub use widener::StereoWidener;
+
+/// Returns the effects the chain starts with, in processing order
+pub fn default_effects() -> Vec<Box<dyn AudioEffect>> {
+    vec![
+        Box::new(Compressor::new()),
+        Box::new(StereoWidener::new()),
+        Box::new(Bitcrusher::new()),
+    ]
+}
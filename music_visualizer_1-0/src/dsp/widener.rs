@@ -0,0 +1,97 @@
+//! Stereo widener
+//!
+//! Splits stereo audio into its mid (left + right) and side (left - right)
+//! parts and scales the side part: 0 folds the audio down to mono, 1 leaves
+//! it unchanged and 2 doubles the difference between the channels. Mono
+//! audio, and channels beyond the first two, pass through unchanged.
+
+use super::effect::{AudioEffect, EffectParam};
+use crate::output::StreamFormat;
+
+/// Parameters of the widener
+const PARAMS: &[EffectParam] = &[EffectParam::new("Width", 1.0, 0.0, 2.0, 0.05, "")];
+
+/// Mid/side stereo width control
+pub struct StereoWidener {
+    /// Gain of the side part
+    width: f32,
+    /// Number of interleaved channels
+    channels: usize,
+}
+
+impl StereoWidener {
+    /// Creates a widener that leaves the audio unchanged
+    pub fn new() -> Self {
+        StereoWidener {
+            width: PARAMS[0].default,
+            channels: StreamFormat::default().channels,
+        }
+    }
+}
+
+impl AudioEffect for StereoWidener {
+    fn name(&self) -> &'static str {
+        "Stereo widener"
+    }
+
+    fn params(&self) -> &'static [EffectParam] {
+        PARAMS
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        if name == "Width" {
+            self.width = value;
+        }
+    }
+
+    fn prepare(&mut self, format: StreamFormat) {
+        self.channels = format.channels;
+    }
+
+    fn process(&mut self, data: &mut [f32]) {
+        if self.channels < 2 {
+            return;
+        }
+        for frame in data.chunks_mut(self.channels) {
+            if let [left, right, ..] = frame {
+                let mid = (*left + *right) * 0.5;
+                let side = (*left - *right) * 0.5 * self.width;
+                *left = mid + side;
+                *right = mid - side;
+            }
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a buffer through a widener at some width
+    fn widen(channels: usize, width: f32, mut data: Vec<f32>) -> Vec<f32> {
+        let mut widener = StereoWidener::new();
+        widener.prepare(StreamFormat {
+            channels,
+            sample_rate: 48_000,
+        });
+        widener.set_param("Width", width);
+        widener.process(&mut data);
+        data
+    }
+
+    #[test]
+    fn width_scales_the_side_part() {
+        let frame = vec![0.75, 0.25];
+        assert_eq!(widen(2, 1.0, frame.clone()), [0.75, 0.25]);
+        assert_eq!(widen(2, 0.0, frame.clone()), [0.5, 0.5]);
+        assert_eq!(widen(2, 2.0, frame), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn mono_and_extra_channels_pass_through() {
+        assert_eq!(widen(1, 0.0, vec![0.75, 0.25]), [0.75, 0.25]);
+        assert_eq!(widen(3, 0.0, vec![0.75, 0.25, 0.9]), [0.5, 0.5, 0.9]);
+    }
+}
@@ -0,0 +1,297 @@
+//! Fades around pause, resume, seek and track changes
+//!
+//! Stopping the output or moving the playhead in the middle of a waveform
+//! makes an audible pop. The audio callback therefore runs its output through
+//! an `Envelope`:
+//! - Before the output is paused, it ramps down to silence
+//! - Before the playhead jumps or the track changes, it ramps down, lets the
+//!   change happen, and ramps back up
+//! - A new or resumed output ramps up from silence
+//!
+//! The main thread steers the envelope through `FadeControl`, whose atomics
+//! it can set while the callback reads them, without locking.
+//!
+//! Crossfades between songs are shaped by a `CrossfadeCurve`. Their length
+//! and curve live in a `CrossfadeControl`, so the menu can change them while
+//! the callback reads them.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering};
+
+/// Longest crossfade offered, in seconds
+pub const MAX_CROSSFADE_SECONDS: f32 = 12.0;
+
+/// Lengths of the fades, in milliseconds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FadeDurations {
+    /// Ramp up after starting, resuming or seeking
+    pub fade_in_ms: f32,
+    /// Ramp down before pausing, seeking or changing track
+    pub fade_out_ms: f32,
+}
+
+/// Fade requests shared between the main thread and the audio callback
+pub struct FadeControl {
+    /// True while the output should be heard
+    audible: AtomicBool,
+}
+
+impl FadeControl {
+    /// Creates fade requests for a silent output
+    pub fn new() -> Self {
+        FadeControl {
+            audible: AtomicBool::new(false),
+        }
+    }
+
+    /// Asks the callback to ramp the output up
+    pub fn fade_in(&self) {
+        self.audible.store(true, Ordering::Relaxed);
+    }
+
+    /// Asks the callback to ramp the output down and stay silent
+    pub fn fade_out(&self) {
+        self.audible.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns whether the output should be heard
+    pub fn is_audible(&self) -> bool {
+        self.audible.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for FadeControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gain ramp run by the audio callback, one step per output frame
+pub struct Envelope {
+    /// Current gain from 0.0 (silent) to 1.0
+    level: f32,
+    /// Gain added per frame while fading in
+    in_step: f32,
+    /// Gain removed per frame while fading out
+    out_step: f32,
+}
+
+impl Envelope {
+    /// Creates an envelope that starts silent, so the first frames fade in
+    ///
+    /// # Arguments
+    /// * `durations` - Lengths of the fades
+    /// * `sample_rate` - Output frames per second
+    pub fn new(durations: FadeDurations, sample_rate: u32) -> Self {
+        let step = |ms: f32| {
+            let frames = ms / 1000.0 * sample_rate as f32;
+            if frames > 1.0 { 1.0 / frames } else { 1.0 }
+        };
+        Envelope {
+            level: 0.0,
+            in_step: step(durations.fade_in_ms),
+            out_step: step(durations.fade_out_ms),
+        }
+    }
+
+    /// Advances the envelope by one frame
+    ///
+    /// # Arguments
+    /// * `control` - Fade requests from the main thread
+    /// * `hold` - True while a seek or track change waits for silence
+    ///
+    /// # Returns
+    /// The gain for the frame; 0.0 means the output is silent
+    pub fn next(&mut self, control: &FadeControl, hold: bool) -> f32 {
+        if control.is_audible() && !hold {
+            self.level = (self.level + self.in_step).min(1.0);
+        } else {
+            self.level = (self.level - self.out_step).max(0.0);
+        }
+        self.level
+    }
+}
+
+/// Shape of the gain ramps during a crossfade
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossfadeCurve {
+    /// Straight ramps; the sum dips by 3 dB in the middle
+    Linear,
+    /// Sine/cosine ramps keeping the total power constant
+    EqualPower,
+    /// Smooth ramps that change slowly at both ends
+    SCurve,
+}
+
+impl CrossfadeCurve {
+    /// Every curve, in the order the menu cycles through them
+    const ALL: [CrossfadeCurve; 3] = [
+        CrossfadeCurve::Linear,
+        CrossfadeCurve::EqualPower,
+        CrossfadeCurve::SCurve,
+    ];
+
+    /// Returns the name shown in the menu
+    pub fn label(self) -> &'static str {
+        match self {
+            CrossfadeCurve::Linear => "Linear",
+            CrossfadeCurve::EqualPower => "Equal power",
+            CrossfadeCurve::SCurve => "S-curve",
+        }
+    }
+
+    /// Returns the curve after this one, wrapping around at the end
+    pub fn next(self) -> CrossfadeCurve {
+        CrossfadeCurve::ALL[(self.index() + 1) % CrossfadeCurve::ALL.len()]
+    }
+
+    /// Returns the position of the curve in `ALL`
+    fn index(self) -> usize {
+        CrossfadeCurve::ALL
+            .iter()
+            .position(|&curve| curve == self)
+            .unwrap_or(0)
+    }
+
+    /// Computes the gains of both songs at a point in the crossfade
+    ///
+    /// # Arguments
+    /// * `progress` - Position in the crossfade from 0.0 (start) to 1.0 (end)
+    ///
+    /// # Returns
+    /// `(outgoing, incoming)` gains
+    pub fn gains(self, progress: f32) -> (f32, f32) {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            CrossfadeCurve::Linear => (1.0 - t, t),
+            CrossfadeCurve::EqualPower => ((t * FRAC_PI_2).cos(), (t * FRAC_PI_2).sin()),
+            CrossfadeCurve::SCurve => {
+                let s = t * t * (3.0 - 2.0 * t);
+                (1.0 - s, s)
+            }
+        }
+    }
+}
+
+/// Crossfade between songs advancing through the queue
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Crossfade {
+    /// Length of the overlap in seconds; 0.0 plays songs back to back
+    pub seconds: f32,
+    /// Shape of the gain ramps
+    pub curve: CrossfadeCurve,
+}
+
+impl Crossfade {
+    /// Returns whether songs overlap at all
+    pub fn is_enabled(&self) -> bool {
+        self.seconds > 0.0
+    }
+}
+
+/// Crossfade settings shared between the main thread and the audio callback
+pub struct CrossfadeControl {
+    /// Length of the overlap in seconds, as `f32` bits
+    seconds: AtomicU32,
+    /// Position of the curve in `CrossfadeCurve::ALL`
+    curve: AtomicU8,
+}
+
+impl CrossfadeControl {
+    /// Creates the shared settings
+    ///
+    /// # Arguments
+    /// * `crossfade` - Initial settings, clamped to the offered range
+    pub fn new(crossfade: Crossfade) -> Self {
+        let control = CrossfadeControl {
+            seconds: AtomicU32::new(0),
+            curve: AtomicU8::new(0),
+        };
+        control.set_seconds(crossfade.seconds);
+        control.set_curve(crossfade.curve);
+        control
+    }
+
+    /// Returns the current settings
+    pub fn crossfade(&self) -> Crossfade {
+        let curve = self.curve.load(Ordering::Relaxed) as usize;
+        Crossfade {
+            seconds: f32::from_bits(self.seconds.load(Ordering::Relaxed)),
+            curve: CrossfadeCurve::ALL[curve.min(CrossfadeCurve::ALL.len() - 1)],
+        }
+    }
+
+    /// Sets the length, clamped to `0.0..=MAX_CROSSFADE_SECONDS`; 0.0 plays
+    /// songs back to back
+    pub fn set_seconds(&self, seconds: f32) {
+        // `clamp` keeps NaN, which would never end a crossfade
+        let seconds = if seconds.is_nan() {
+            0.0
+        } else {
+            seconds.clamp(0.0, MAX_CROSSFADE_SECONDS)
+        };
+        self.seconds.store(seconds.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Sets the shape of the gain ramps
+    pub fn set_curve(&self, curve: CrossfadeCurve) {
+        self.curve.store(curve.index() as u8, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_power_keeps_the_power_constant() {
+        for step in 0..=100 {
+            let (outgoing, incoming) = CrossfadeCurve::EqualPower.gains(step as f32 / 100.0);
+            let power = outgoing * outgoing + incoming * incoming;
+            assert!((power - 1.0).abs() < 1e-5, "{} at {}", power, step);
+        }
+    }
+
+    #[test]
+    fn every_curve_runs_from_outgoing_to_incoming() {
+        for curve in CrossfadeCurve::ALL {
+            assert_eq!(curve.gains(0.0), (1.0, 0.0), "{:?}", curve);
+            let (outgoing, incoming) = curve.gains(1.0);
+            assert!(outgoing.abs() < 1e-6 && incoming == 1.0, "{:?}", curve);
+            // Out-of-range progress holds the ends
+            assert_eq!(curve.gains(-1.0), curve.gains(0.0));
+            assert_eq!(curve.gains(2.0), curve.gains(1.0));
+
+            let mut last = curve.gains(0.0);
+            for step in 1..=100 {
+                let gains = curve.gains(step as f32 / 100.0);
+                assert!(gains.0 <= last.0 && gains.1 >= last.1, "{:?}", curve);
+                last = gains;
+            }
+        }
+        // Both amplitude curves meet at half gain, 3 dB under equal power
+        assert_eq!(CrossfadeCurve::Linear.gains(0.5), (0.5, 0.5));
+        assert_eq!(CrossfadeCurve::SCurve.gains(0.5), (0.5, 0.5));
+    }
+
+    #[test]
+    fn control_clamps_and_keeps_the_curve() {
+        let control = CrossfadeControl::new(Crossfade {
+            seconds: 100.0,
+            curve: CrossfadeCurve::SCurve,
+        });
+        assert_eq!(
+            control.crossfade(),
+            Crossfade {
+                seconds: MAX_CROSSFADE_SECONDS,
+                curve: CrossfadeCurve::SCurve,
+            }
+        );
+        control.set_seconds(f32::NAN);
+        assert!(!control.crossfade().is_enabled());
+        control.set_curve(CrossfadeCurve::SCurve.next());
+        assert_eq!(control.crossfade().curve, CrossfadeCurve::Linear);
+    }
+}
@@ -0,0 +1,61 @@
+//! Playback without a window
+//!
+//! `--headless` plays the whole library once, in order, and exits after the
+//! last song. The `Player` runs exactly as it does behind the window, but is
+//! updated from a plain loop instead of nannou's frames, and far more often,
+//! so it follows the output closely even when the output renders faster
+//! than real time.
+
+use crate::cli::Args;
+use crate::config::Config;
+use crate::controller;
+use crate::dsp::{EqControl, LimiterControl};
+use crate::fade::CrossfadeControl;
+use crate::player::{PlayerCommand, PlayerState};
+use crate::volume::VolumeControl;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Time between player updates
+const UPDATE_INTERVAL: Duration = Duration::from_millis(2);
+/// Time the output gets to play out the end of the last song, on top of
+/// its latency
+const DRAIN_TIME: Duration = Duration::from_millis(100);
+
+/// Plays the library from the first song to the last
+///
+/// # Arguments
+/// * `args` - Command line options, overriding the config file
+///
+/// # Returns
+/// False if the library is empty or a song could not be played
+pub fn run(args: &Args) -> bool {
+    let mut config = Config::load();
+    let volume = Arc::new(VolumeControl::new(
+        config.volume_db,
+        config.muted,
+        config.balance,
+    ));
+    let eq = Arc::new(EqControl::new(config.equalizer.clone()));
+    let limiter = Arc::new(LimiterControl::new(config.limiter));
+    let crossfade = Arc::new(CrossfadeControl::new(config.crossfade()));
+    let mut player = controller::create_player(&mut config, args, volume, eq, limiter, crossfade);
+
+    if player.library().songs.is_empty() {
+        eprintln!("No songs found in {}", player.library().dir().display());
+        return false;
+    }
+    player.send(PlayerCommand::Load(0));
+    loop {
+        player.update();
+        match player.state() {
+            // The player stops after the last song
+            PlayerState::Stopped => break,
+            PlayerState::Error => return false,
+            _ => thread::sleep(UPDATE_INTERVAL),
+        }
+    }
+    thread::sleep(player.output().latency() + DRAIN_TIME);
+    true
+}
@@ -0,0 +1,83 @@
+//! Audio host selection
+//!
+//! cpal reaches the sound hardware through a host, one per audio API the
+//! platform offers (ALSA or JACK on Linux, WASAPI or ASIO on Windows, ...).
+//! The default host is used unless the config file or the `--host` flag
+//! names another one.
+//!
+//! cpal has no PulseAudio host; PulseAudio and PipeWire are reached through
+//! ALSA, whose `pulse` and `pipewire` devices show up in the device list.
+//!
+//! JACK support needs the `jack` cargo feature and is only offered on Linux.
+//! The JACK host can connect the output ports to the system playback ports
+//! and start a JACK server when none is running; both are configurable.
+
+/// Which audio host to open and how to set it up
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostSettings {
+    /// Name of the host, e.g. `ALSA` or `JACK`; `None` uses the default host
+    pub name: Option<String>,
+    /// Whether JACK connects the output ports to the system playback ports
+    pub jack_connect_ports: bool,
+    /// Whether JACK starts a server when none is running
+    pub jack_start_server: bool,
+}
+
+/// Returns the names of the audio hosts available on this machine
+pub fn available_host_names() -> Vec<&'static str> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| id.name())
+        .collect()
+}
+
+/// Opens the configured audio host, falling back to the default host
+///
+/// Problems are reported; the default host is used instead.
+///
+/// # Arguments
+/// * `settings` - Which host to open and how to set it up
+pub fn open(settings: &HostSettings) -> cpal::Host {
+    let Some(name) = &settings.name else {
+        return cpal::default_host();
+    };
+    let Some(id) = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+    else {
+        eprintln!(
+            "Audio host \"{}\" not available, using the default host (available: {})",
+            name,
+            available_host_names().join(", ")
+        );
+        if name.eq_ignore_ascii_case("jack") && cfg!(not(feature = "jack")) {
+            eprintln!("JACK support needs the `jack` cargo feature");
+        }
+        return cpal::default_host();
+    };
+
+    let host = match id {
+        #[cfg(all(feature = "jack", target_os = "linux"))]
+        cpal::HostId::Jack => open_jack(settings),
+        _ => cpal::host_from_id(id),
+    };
+    match host {
+        Ok(host) => host,
+        Err(e) => {
+            eprintln!(
+                "Failed to open audio host \"{}\", using the default host: {}",
+                name, e
+            );
+            cpal::default_host()
+        }
+    }
+}
+
+/// Opens the JACK host with the port and server settings applied
+#[cfg(all(feature = "jack", target_os = "linux"))]
+fn open_jack(settings: &HostSettings) -> Result<cpal::Host, cpal::HostUnavailable> {
+    let mut host = cpal::platform::JackHost::new()?;
+    host.set_connect_automatically(settings.jack_connect_ports);
+    host.set_start_server_automatically(settings.jack_start_server);
+    Ok(host.into())
+}
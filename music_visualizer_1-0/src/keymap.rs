@@ -0,0 +1,288 @@
+//! Keyboard shortcuts
+//!
+//! Maps keys to application actions. The bindings come from the `keymap`
+//! section of the config file, written as key name → action name, e.g.
+//! `"Space": "play_pause"`. Actions the config file leaves unbound keep
+//! their default keys, so config files written before an action existed
+//! still get it. Keys the menu uses itself (typing in the search field,
+//! moving through lists, Tab and Escape) take precedence.
+
+use crate::ui::Label;
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Height of a line in the help overlay, in pixels
+const HELP_LINE_HEIGHT: f32 = 26.0;
+
+/// Something a keyboard shortcut can do
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    PlayPause,
+    SeekBackward,
+    SeekForward,
+    NextSong,
+    PreviousSong,
+    CycleVisualizer,
+    ToggleFullscreen,
+    VolumeUp,
+    VolumeDown,
+    ToggleMute,
+    Search,
+    TogglePanel,
+    ToggleHelp,
+}
+
+impl Action {
+    /// Returns the description shown in the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::PlayPause => "Play / pause",
+            Action::SeekBackward => "Skip back 10 seconds",
+            Action::SeekForward => "Skip forward 10 seconds",
+            Action::NextSong => "Next song",
+            Action::PreviousSong => "Previous song",
+            Action::CycleVisualizer => "Next visualizer",
+            Action::ToggleFullscreen => "Toggle fullscreen",
+            Action::VolumeUp => "Volume up",
+            Action::VolumeDown => "Volume down",
+            Action::ToggleMute => "Mute / unmute",
+            Action::Search => "Search the library",
+            Action::TogglePanel => "Show / hide the menu",
+            Action::ToggleHelp => "Show / hide this help",
+        }
+    }
+}
+
+/// Names used for keys in the config file
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("A", Key::A),
+    ("B", Key::B),
+    ("C", Key::C),
+    ("D", Key::D),
+    ("E", Key::E),
+    ("F", Key::F),
+    ("G", Key::G),
+    ("H", Key::H),
+    ("I", Key::I),
+    ("J", Key::J),
+    ("K", Key::K),
+    ("L", Key::L),
+    ("M", Key::M),
+    ("N", Key::N),
+    ("O", Key::O),
+    ("P", Key::P),
+    ("Q", Key::Q),
+    ("R", Key::R),
+    ("S", Key::S),
+    ("T", Key::T),
+    ("U", Key::U),
+    ("V", Key::V),
+    ("W", Key::W),
+    ("X", Key::X),
+    ("Y", Key::Y),
+    ("Z", Key::Z),
+    ("0", Key::Key0),
+    ("1", Key::Key1),
+    ("2", Key::Key2),
+    ("3", Key::Key3),
+    ("4", Key::Key4),
+    ("5", Key::Key5),
+    ("6", Key::Key6),
+    ("7", Key::Key7),
+    ("8", Key::Key8),
+    ("9", Key::Key9),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+    ("Space", Key::Space),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Insert", Key::Insert),
+    ("Delete", Key::Delete),
+    ("/", Key::Slash),
+    ("\\", Key::Backslash),
+    (",", Key::Comma),
+    (".", Key::Period),
+    (";", Key::Semicolon),
+    ("'", Key::Apostrophe),
+    ("-", Key::Minus),
+    ("=", Key::Equals),
+    ("[", Key::LBracket),
+    ("]", Key::RBracket),
+    ("`", Key::Grave),
+];
+
+/// Looks up a key by its config file name (case-insensitive)
+fn parse_key(name: &str) -> Option<Key> {
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|&(_, key)| key)
+}
+
+/// Returns the config file name of a key
+fn key_name(key: Key) -> &'static str {
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, k)| k == key)
+        .map_or("?", |&(name, _)| name)
+}
+
+/// Returns the bindings used when the config file has no keymap
+pub fn default_bindings() -> BTreeMap<String, Action> {
+    [
+        ("Space", Action::PlayPause),
+        ("Left", Action::SeekBackward),
+        ("Right", Action::SeekForward),
+        ("N", Action::NextSong),
+        ("P", Action::PreviousSong),
+        ("V", Action::CycleVisualizer),
+        ("F", Action::ToggleFullscreen),
+        ("Up", Action::VolumeUp),
+        ("Down", Action::VolumeDown),
+        ("=", Action::VolumeUp),
+        ("-", Action::VolumeDown),
+        ("M", Action::ToggleMute),
+        ("/", Action::Search),
+        ("C", Action::TogglePanel),
+        ("H", Action::ToggleHelp),
+        ("F1", Action::ToggleHelp),
+    ]
+    .into_iter()
+    .map(|(key, action)| (key.to_string(), action))
+    .collect()
+}
+
+/// Active key bindings
+pub struct Keymap {
+    /// Bound keys and their actions, sorted by action for the help overlay
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the config file's bindings
+    ///
+    /// Unknown key names are reported and skipped. Actions without a binding
+    /// get their default keys, unless the config file uses those keys for
+    /// something else.
+    ///
+    /// # Arguments
+    /// * `bindings` - Key names mapped to actions
+    pub fn from_config(bindings: &BTreeMap<String, Action>) -> Self {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for (name, &action) in bindings {
+            match parse_key(name) {
+                Some(key) => keymap.bindings.push((key, action)),
+                None => eprintln!("Unknown key in keymap: {}", name),
+            }
+        }
+        let configured: Vec<Action> = bindings.values().copied().collect();
+        for (name, action) in default_bindings() {
+            if let Some(key) = parse_key(&name)
+                && !configured.contains(&action)
+                && keymap.action(key).is_none()
+            {
+                keymap.bindings.push((key, action));
+            }
+        }
+        keymap.bindings.sort_by_key(|&(_, action)| action);
+        keymap
+    }
+
+    /// Returns the action bound to a key, if any
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|&(_, action)| action)
+    }
+
+    /// Renders the help overlay listing every active binding
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `rect` - Area the overlay covers
+    pub fn draw_help(&self, draw: &Draw, rect: Rect) {
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgba(0.0, 0.0, 0.0, 0.8));
+
+        let column_width = (rect.w() * 0.8).min(420.0);
+        let lines = self.bindings.len() + 2;
+        let top = rect.y() + lines as f32 * HELP_LINE_HEIGHT / 2.0;
+        let line_rect = |line: usize, x_offset: f32, width: f32| {
+            Rect::from_x_y_w_h(
+                rect.x() + x_offset,
+                top - (line as f32 + 0.5) * HELP_LINE_HEIGHT,
+                width,
+                HELP_LINE_HEIGHT,
+            )
+        };
+
+        Label::new("KEYBOARD SHORTCUTS", line_rect(0, 0.0, column_width))
+            .font_size(22)
+            .draw(draw);
+        for (i, &(key, action)) in self.bindings.iter().enumerate() {
+            let line = i + 2;
+            Label::new(
+                key_name(key),
+                line_rect(line, -column_width * 0.35, column_width * 0.3),
+            )
+            .color(rgb(0.6, 0.8, 1.0))
+            .draw(draw);
+            Label::new(
+                action.description(),
+                line_rect(line, column_width * 0.15, column_width * 0.7),
+            )
+            .draw(draw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrows_seek_and_change_the_volume() {
+        let keymap = Keymap::from_config(&default_bindings());
+        assert_eq!(keymap.action(Key::Left), Some(Action::SeekBackward));
+        assert_eq!(keymap.action(Key::Right), Some(Action::SeekForward));
+        assert_eq!(keymap.action(Key::Up), Some(Action::VolumeUp));
+        assert_eq!(keymap.action(Key::Down), Some(Action::VolumeDown));
+    }
+
+    #[test]
+    fn old_keymaps_get_the_defaults_of_unbound_actions() {
+        let bindings = [("Space", Action::PlayPause), ("M", Action::CycleVisualizer)]
+            .into_iter()
+            .map(|(key, action)| (key.to_string(), action))
+            .collect();
+        let keymap = Keymap::from_config(&bindings);
+
+        assert_eq!(keymap.action(Key::Up), Some(Action::VolumeUp));
+        // M is taken, so mute gets no key, and V stays free
+        assert_eq!(keymap.action(Key::M), Some(Action::CycleVisualizer));
+        assert_eq!(keymap.action(Key::V), None);
+    }
+}
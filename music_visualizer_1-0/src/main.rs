@@ -14,27 +14,81 @@
 // - rename song and edit song.rs to be stronger and a better model
 // - use idvf file types to load .wav files
 
+/// Module containing audio analysis (spectrum) used by the visualizers
+mod analysis;
+/// Module containing the command line options
+mod cli;
+/// Module containing the persistent user settings
+mod config;
 /// Module containing the controller logic for managing application state
 mod controller;
+/// Module containing the draggable divider between menu and view
+mod divider;
+/// Module containing the signal processing in the audio path, such as the equalizer
+mod dsp;
+/// Module containing the fades around pause, resume, seek and track changes
+mod fade;
+/// Module containing playback without a window
+mod headless;
+/// Module containing the audio host (ALSA, JACK, ...) selection
+mod host;
+/// Module containing the keyboard shortcuts and their help overlay
+mod keymap;
 /// Module containing the menu UI and interaction logic
 mod menu;
 mod music_library;
+/// Module containing the menu screens and back navigation
+mod navigation;
+/// Module containing the loudness normalization and the library's loudness index
+mod normalization;
+/// Module containing the audio output, its mixer and the backends it plays through
+mod output;
+/// Module containing the playback state machine and its commands and events
+mod player;
+/// Module containing the waveform overview seek bar
+mod seek_bar;
 /// Module handling audio playback and song management
 mod song;
+/// Module containing the immediate-mode widgets the menu is built from
+mod ui;
 /// Module responsible for visual rendering
 mod view;
+/// Module containing the visualizer trait and the individual visual modes
+mod visualizer;
+/// Module containing the master volume, mute and balance settings
+mod volume;
 
 use controller::Controller;
 
 /// Main entry point for the application
 ///
-/// Initializes and runs the Nannou application with:
+/// Handles the command line options that exit right away and headless runs,
+/// then initializes and runs the Nannou application with:
 /// - `model` for initialization
 /// - `update` for the main loop
+/// - `event` for window events
 /// - `view` for rendering
 /// - A simple window for display
 fn main() {
-    nannou::app(model).update(update).simple_window(view).run();
+    let args = cli::Args::from_env();
+    if args.list_hosts {
+        for name in host::available_host_names() {
+            println!("{}", name);
+        }
+        return;
+    }
+    if args.headless {
+        if !headless::run(&args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    nannou::app(model)
+        .update(update)
+        .event(event)
+        .simple_window(view)
+        .run();
 }
 
 /// The main application state container
@@ -58,9 +112,11 @@ struct Model {
 fn model(app: &nannou::App) -> Model {
     let window = app.main_window();
     let win_rect = window.rect();
+    // Options were checked in `main`; the model function cannot be handed them
+    let args = cli::Args::from_env();
 
     Model {
-        controller: Controller::new(win_rect),
+        controller: Controller::new(win_rect, &args),
     }
 }
 
@@ -76,6 +132,25 @@ fn update(app: &nannou::App, model: &mut Model, _update: nannou::prelude::Update
     model.controller.update(app);
 }
 
+/// Window event handler
+///
+/// Called for every event; window events (keyboard, mouse wheel, ...) are
+/// passed on to the controller.
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `event` - The event to handle
+fn event(app: &nannou::App, model: &mut Model, event: nannou::prelude::Event) {
+    if let nannou::prelude::Event::WindowEvent {
+        simple: Some(window_event),
+        ..
+    } = event
+    {
+        model.controller.event(app, &window_event);
+    }
+}
+
 /// Main rendering function
 ///
 /// Called once per frame to render the current application state.
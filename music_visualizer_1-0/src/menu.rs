@@ -1,219 +1,1537 @@
 //! User interface menu module
 //!
 //! Handles the interactive control panel for the application, including:
-//! - Play/pause button
+//! - Screen tabs and back navigation
+//! - Transport buttons (play/pause, stop, previous, next, skip ±10s)
+//! - Elapsed / remaining time display
+//! - Master volume, mute and balance
+//! - Loudness normalization mode, target and measured loudness
+//! - Visualizer selection and parameter sliders
+//! - Crossfade length and curve
+//! - Equalizer bands, response curve and presets
+//! - Effect chain order, bypass switches and parameters
+//! - Output limiter settings, gain reduction and clip indicator
+//! - Output device selection
+//! - Searchable song list and queue
 //! - Menu layout and rendering
-//! - Mouse interaction handling
+//! - Mouse and keyboard interaction handling
 //!
-//! The menu provides visual feedback and translates user input into playback commands.
+//! The menu provides visual feedback and translates user input into commands
+//! for the `Player`, whose state it reads back when updating and drawing.
+//! Screens are built from the widgets in `ui`; every screen has one function
+//! that lays its widgets out, shared by `update` and `draw` so that what is
+//! clicked is always what is shown.
 
-use crate::music_library::MusicLibrary;
+use crate::dsp::{
+    EqBand, EqControl, EqMode, EqSettings, GRAPHIC_FREQUENCIES, LimiterControl, MAX_BANDS,
+    MAX_CEILING_DB, MAX_FREQUENCY, MAX_GAIN_DB, MAX_Q, MAX_RELEASE_MS, MIN_CEILING_DB,
+    MIN_FREQUENCY, MIN_Q, MIN_RELEASE_MS, preset_names,
+};
+use crate::fade::{CrossfadeControl, MAX_CROSSFADE_SECONDS};
+use crate::navigation::{Navigation, Screen};
+use crate::normalization::{
+    MAX_TARGET_LUFS, MIN_TARGET_LUFS, NormalizationMode, NormalizationSettings,
+};
+use crate::player::{Player, PlayerCommand, PlayerState, SKIP_SECONDS};
+use crate::ui::{Button, Graph, Input, Label, Layout, ListView, Meter, Slider, TextInput, Toggle};
+use crate::visualizer::VisualizerParam;
+use crate::volume::{MAX_VOLUME_DB, MIN_VOLUME_DB, VolumeControl};
 use nannou::prelude::*;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Height of a visualizer parameter row in pixels
+const PARAM_ROW_HEIGHT: f32 = 40.0;
+/// Space reserved for the menu title at the top of the panel, in pixels
+const TITLE_HEIGHT: f32 = 60.0;
+/// Height of the row of screen tabs below the title, in pixels
+const TAB_HEIGHT: f32 = 30.0;
+/// Height of the row of transport buttons below play/pause, in pixels
+const TRANSPORT_HEIGHT: f32 = 36.0;
+/// Height of the library search field, in pixels
+const SEARCH_HEIGHT: f32 = 30.0;
+/// Height of the volume and balance rows on the Now Playing screen, in pixels
+const VOLUME_ROW_HEIGHT: f32 = 30.0;
+/// Width of the mute button next to the volume slider, in pixels
+const MUTE_BUTTON_WIDTH: f32 = 60.0;
+/// Height of an output device row on the Settings screen, in pixels
+const DEVICE_ROW_HEIGHT: f32 = 24.0;
+/// Height of the equalizer response curve, in pixels
+const EQ_CURVE_HEIGHT: f32 = 80.0;
+/// Gain at the top and bottom of the response curve; more than one band can
+/// boost, so this is larger than a single band's range
+const EQ_CURVE_RANGE_DB: f32 = 18.0;
+/// Points the response curve is drawn through, spaced evenly in octaves
+const EQ_CURVE_POINTS: usize = 96;
+/// Height of an equalizer band row, in pixels
+const EQ_ROW_HEIGHT: f32 = 18.0;
+/// Height of the area holding the equalizer bands, the same in both modes
+/// so the presets below do not move
+const EQ_BANDS_HEIGHT: f32 = 210.0;
+/// Gain reduction that fills the limiter meter, in dB
+const LIMITER_METER_RANGE_DB: f32 = 12.0;
+/// Time the clip indicator stays lit after the output clipped
+const CLIP_HOLD: Duration = Duration::from_millis(1500);
+
+/// Widgets of the Now Playing screen
+struct NowPlayingWidgets {
+    /// Title of the selected song
+    title: Label,
+    /// Play/pause toggle
+    play: Toggle,
+    /// Previous, skip backward, stop, skip forward and next
+    transport: Vec<Button>,
+    /// Elapsed and remaining time
+    time: Label,
+    /// Mute/unmute button
+    mute: Button,
+    /// Master volume in dB
+    volume: Slider,
+    /// Left/right balance
+    balance: Slider,
+    /// Normalization mode and clipping prevention buttons, with the settings
+    /// each one switches to
+    normalization: Vec<(NormalizationSettings, Button)>,
+    /// Target loudness in LUFS
+    target: Slider,
+    /// Measured loudness and peak of the song, and the gain it plays at
+    loudness: Label,
+    /// Gain reduction of the output limiter and the clip indicator
+    limiter: Meter,
+}
+
+/// What an equalizer slider changes
+#[derive(Copy, Clone, Debug)]
+enum EqSlider {
+    /// Gain of the graphic band with this index
+    GraphicGain(usize),
+    /// Frequency of the selected parametric band, as log10 of Hz
+    Frequency,
+    /// Gain of the selected parametric band
+    Gain,
+    /// Q of the selected parametric band, as log10
+    Q,
+}
+
+/// Widgets of the Equalizer screen
+struct EqualizerWidgets {
+    /// Combined frequency response of all bands
+    curve: Graph,
+    /// Switches the equalizer on and off
+    enabled: Toggle,
+    /// Mode switch, reset and, in parametric mode, band editing buttons
+    buttons: Vec<Button>,
+    /// Selects a parametric band, paired with the band index
+    band_tabs: Vec<(usize, Button)>,
+    /// Band sliders, paired with what they change
+    sliders: Vec<(EqSlider, Slider)>,
+    /// Heading of the preset list
+    preset_heading: Label,
+    /// Bounds of the preset name field
+    preset_name: Rect,
+    /// One button per saved preset, paired with the preset name
+    presets: Vec<(String, Button)>,
+}
+
+/// Widgets of the Effects screen
+struct EffectsWidgets {
+    /// Bypass switch per effect, paired with the effect's position and
+    /// whether it is bypassed
+    bypass: Vec<(usize, bool, Button)>,
+    /// Buttons moving an effect up or down, paired with the positions it
+    /// moves from and to
+    moves: Vec<((usize, usize), Button)>,
+    /// Parameter sliders, paired with the effect's position and the
+    /// parameter name
+    params: Vec<((usize, &'static str), Slider)>,
+    /// Heading of the output limiter settings
+    limiter_heading: Label,
+    /// Switches the output limiter on and off
+    limiter: Toggle,
+    /// Highest level that reaches the device, in dBFS
+    ceiling: Slider,
+    /// Time the limiter takes to recover, in milliseconds
+    release: Slider,
+    /// Gain reduction of the output limiter and the clip indicator
+    reduction: Meter,
+}
+
+/// Widgets of the Settings screen
+struct SettingsWidgets {
+    /// Shows the active visualizer; cycles to the next one when clicked
+    visualizer: Button,
+    /// One slider per visualizer parameter, paired with the parameter name
+    params: Vec<(&'static str, Slider)>,
+    /// Heading of the crossfade settings
+    crossfade_heading: Label,
+    /// Time songs overlap when advancing through the queue; 0 plays them
+    /// back to back
+    crossfade: Slider,
+    /// Shows the crossfade curve; cycles to the next one when clicked
+    crossfade_curve: Button,
+    /// Heading of the output device list
+    device_heading: Label,
+    /// One button per output device, paired with the device name; `None` is
+    /// the system default
+    devices: Vec<(Option<String>, Button)>,
+    /// Looks for output devices again
+    rescan: Button,
+    /// Latency and buffer size of the open stream
+    latency: Label,
+    /// Underruns and late callbacks of the open stream
+    dropouts: Label,
+}
 
 /// Represents the interactive control menu
 ///
 /// Manages:
-/// - Playback state tracking
-/// - Button layout and rendering
-/// - Mouse interaction handling
+/// - Screen navigation and widget layout
+/// - Mouse and keyboard interaction handling
 /// - Visual feedback
-///
-
-pub struct MenuButton {
-    title: String,
-    tag: String,
-    rect: Rect,
-}
-
 pub struct Menu {
-    /// Current playback state (true when audio is playing)
-    is_playing: bool,
     /// Rectangle defining the entire menu area
     menu_rect: Rect,
-    /// Collection of all interactive buttons
-    buttons: Vec<MenuButton>,
-    /// Tracks mouse state from previous frame for click detection
-    was_mouse_pressed: bool,
-    pub music_library: MusicLibrary,
+    /// Mouse state of the current frame, shared by update and draw
+    input: Input,
+    /// Sends the user's playback requests to the player
+    commands: Sender<PlayerCommand>,
+    /// Volume, mute and balance shared with the audio callback
+    volume: Arc<VolumeControl>,
+    /// Equalizer settings shared with the audio callback
+    eq: Arc<EqControl>,
+    /// Parametric band edited on the Equalizer screen
+    eq_band: usize,
+    /// Name the equalizer settings are saved as
+    preset_name: TextInput,
+    /// Names of the saved equalizer presets
+    presets: Vec<String>,
+    /// Limiter settings and readouts shared with the audio callback
+    limiter: Arc<LimiterControl>,
+    /// Clip count of the limiter when the menu last looked
+    clips_seen: usize,
+    /// When the output last clipped, while the clip indicator is lit
+    last_clip: Option<Instant>,
+    /// Crossfade settings shared with the audio callback
+    crossfade: Arc<CrossfadeControl>,
+    /// Screen currently shown and the history for going back
+    navigation: Navigation,
+    /// Search field filtering the library
+    search: TextInput,
+    /// Indices into the library of the songs matching the search
+    library_matches: Vec<usize>,
+    /// Scrollable list of the songs matching the search
+    song_list: ListView,
+    /// Scrollable list of the songs after the selected one
+    queue_list: ListView,
+    /// Name of the visualizer currently shown in the view
+    visualizer_name: String,
+    /// Parameters of the active visualizer, refreshed every frame
+    visualizer_params: Vec<VisualizerParam>,
+    /// Set when the user asked for the next visualizer
+    cycle_visualizer_requested: bool,
+    /// Parameter changes made since the controller last collected them
+    param_changes: Vec<(&'static str, f32)>,
 }
 
 impl Menu {
-    /// Creates a new Menu with default layout
+    /// Creates a new Menu showing the library
     ///
     /// # Arguments
     /// * `menu_rect` - The bounding rectangle for the entire menu panel
+    /// * `player` - The player the menu controls
+    /// * `volume` - Volume settings changed by the volume controls
+    /// * `eq` - Equalizer settings changed on the Equalizer screen
+    /// * `limiter` - Limiter settings changed on the Effects screen
+    /// * `crossfade` - Crossfade settings changed on the Settings screen
     ///
     /// # Layout
-    /// - Play/pause button is centered horizontally
-    /// - Button takes up 80% of menu width
-    /// - Positioned 30% down from top of menu
-    pub fn new(menu_rect: Rect) -> Self {
+    /// - Title at the top, with screen tabs directly below it
+    /// - The current screen fills the rest of the panel
+    pub fn new(
+        menu_rect: Rect,
+        player: &Player,
+        volume: Arc<VolumeControl>,
+        eq: Arc<EqControl>,
+        limiter: Arc<LimiterControl>,
+        crossfade: Arc<CrossfadeControl>,
+    ) -> Self {
+        let library_matches = (0..player.library().songs.len()).collect();
+        let content_rect = content_rect(menu_rect);
+
         Menu {
-            is_playing: false,
-            music_library: MusicLibrary::new(),
             menu_rect,
-            buttons: vec![MenuButton {
-                title: "PLAY".to_string(),
-                tag: "play_button".to_string(),
-                rect: Rect::from_x_y_w_h(
-                    menu_rect.x(),
-                    menu_rect.y() + menu_rect.h() * 0.3,
-                    menu_rect.w() * 0.8,
-                    50.0,
-                ),
-            }],
-            was_mouse_pressed: false,
+            input: Input::default(),
+            commands: player.sender(),
+            volume,
+            eq,
+            eq_band: 0,
+            preset_name: TextInput::new("Preset name..."),
+            presets: preset_names(),
+            clips_seen: limiter.clip_count(),
+            limiter,
+            last_clip: None,
+            crossfade,
+            navigation: Navigation::new(Screen::Library),
+            search: TextInput::new("Search..."),
+            library_matches,
+            song_list: ListView::new(library_layout(content_rect).1),
+            queue_list: ListView::new(content_rect),
+            visualizer_name: String::new(),
+            visualizer_params: Vec::new(),
+            cycle_visualizer_requested: false,
+            param_changes: Vec::new(),
         }
     }
 
+    /// Moves the menu to a new area, e.g. after the window was resized
+    ///
+    /// Widgets are laid out from the menu area every frame, so only the lists,
+    /// which keep a scroll position, need to be told about the change.
+    ///
+    /// # Arguments
+    /// * `menu_rect` - The new bounding rectangle for the menu panel
+    /// * `player` - The player the menu controls
+    pub fn set_rect(&mut self, menu_rect: Rect, player: &Player) {
+        self.menu_rect = menu_rect;
+        let content_rect = content_rect(menu_rect);
+        self.song_list
+            .set_rect(library_layout(content_rect).1, self.library_matches.len());
+        self.queue_list
+            .set_rect(content_rect, player.library().up_next().len());
+    }
+
     /// Updates menu state based on user input
     ///
     /// Handles:
     /// - Mouse position tracking
     /// - Click detection (only triggers on new presses)
-    /// - Button state toggling
+    /// - Tab and back navigation
+    /// - The widgets of the current screen
     ///
     /// # Arguments
     /// * `app` - Reference to Nannou application for input access
-    pub fn update(&mut self, app: &App) {
-        let mouse = app.mouse.position();
+    /// * `player` - The player the menu controls
+    pub fn update(&mut self, app: &App, player: &Player) {
         let is_mouse_pressed = app.mouse.buttons.pressed().next().is_some();
+        self.input = Input::new(app.mouse.position(), is_mouse_pressed, self.input.is_down);
+        let input = self.input;
 
-        // Only trigger on new presses, not while holding
-        if is_mouse_pressed && !self.was_mouse_pressed {
-            for (i, button) in self.buttons.iter().enumerate() {
-                if button.rect.contains(mouse) {
-                    match i {
-                        0 => self.is_playing = !self.is_playing,
-                        _ => {}
+        let clips = self.limiter.clip_count();
+        if clips != self.clips_seen {
+            self.clips_seen = clips;
+            self.last_clip = Some(Instant::now());
+        }
+        if self
+            .last_clip
+            .is_some_and(|time| time.elapsed() >= CLIP_HOLD)
+        {
+            self.last_clip = None;
+        }
+
+        if self.back_button().clicked(&input) {
+            self.navigation.back();
+            return;
+        }
+        if let Some(screen) = self
+            .tab_buttons()
+            .into_iter()
+            .find_map(|(screen, tab)| tab.clicked(&input).then_some(screen))
+        {
+            self.navigation.open(screen);
+            return;
+        }
+
+        match self.navigation.current() {
+            Screen::Library => {
+                let (search_rect, _) = library_layout(self.content_rect());
+                self.search.update(search_rect, &input);
+                let count = self.library_matches.len();
+                if let Some(row) = self.song_list.update(&input, count) {
+                    self.play_song(self.library_matches[row]);
+                }
+            }
+            Screen::NowPlaying => {
+                let widgets = self.now_playing_widgets(player);
+                // Ask for the state the toggle offers rather than a toggle
+                if widgets.play.toggled(&input) {
+                    self.send(match player.state() {
+                        PlayerState::Playing => PlayerCommand::Pause,
+                        _ => PlayerCommand::Play,
+                    });
+                }
+                // Only handle one button per click
+                if let Some(button) = widgets.transport.iter().find(|b| b.clicked(&input)) {
+                    self.press_button(button.tag);
+                }
+                if widgets.mute.clicked(&input) {
+                    self.press_button(widgets.mute.tag);
+                }
+                if let Some(volume_db) = widgets.volume.dragged(&input) {
+                    self.volume.set_volume_db(volume_db);
+                }
+                if let Some(balance) = widgets.balance.dragged(&input) {
+                    self.volume.set_balance(balance);
+                }
+                if let Some((settings, _)) = widgets
+                    .normalization
+                    .iter()
+                    .find(|(_, b)| b.clicked(&input))
+                {
+                    self.send(PlayerCommand::SetNormalization(*settings));
+                }
+                if let Some(target_lufs) = widgets.target.dragged(&input) {
+                    let mut settings = player.normalization();
+                    settings.target_lufs = target_lufs;
+                    self.send(PlayerCommand::SetNormalization(settings));
+                }
+            }
+            Screen::Queue => {
+                let library = player.library();
+                if let Some(row) = self.queue_list.update(&input, library.up_next().len()) {
+                    self.play_song(library.queue_start() + row);
+                }
+            }
+            Screen::Equalizer => {
+                let band_count = self.eq.settings().bands.len();
+                self.eq_band = self.eq_band.min(band_count.saturating_sub(1));
+                let widgets = self.equalizer_widgets(player);
+                self.preset_name.update(widgets.preset_name, &input);
+                if widgets.enabled.toggled(&input) {
+                    self.eq.update(|eq| eq.enabled = !eq.enabled);
+                }
+                if let Some(button) = widgets.buttons.iter().find(|b| b.clicked(&input)) {
+                    self.press_button(button.tag);
+                }
+                if let Some((band, _)) = widgets.band_tabs.iter().find(|(_, b)| b.clicked(&input)) {
+                    self.eq_band = *band;
+                }
+                for (target, slider) in &widgets.sliders {
+                    if let Some(value) = slider.dragged(&input) {
+                        self.set_eq_value(*target, value);
                     }
-                    break; // Only handle one button per click
+                }
+                if let Some((name, _)) = widgets.presets.iter().find(|(_, b)| b.clicked(&input)) {
+                    self.load_preset(name);
+                }
+            }
+            Screen::Effects => {
+                let widgets = self.effects_widgets(player);
+                if let Some((index, bypassed, _)) =
+                    widgets.bypass.iter().find(|(_, _, b)| b.clicked(&input))
+                {
+                    self.send(PlayerCommand::SetEffectBypassed(*index, !bypassed));
+                }
+                if let Some(((from, to), _)) = widgets.moves.iter().find(|(_, b)| b.clicked(&input))
+                {
+                    self.send(PlayerCommand::MoveEffect(*from, *to));
+                }
+                for ((index, name), slider) in widgets.params {
+                    if let Some(value) = slider.dragged(&input) {
+                        self.send(PlayerCommand::SetEffectParam(index, name, value));
+                    }
+                }
+                if widgets.limiter.toggled(&input) {
+                    self.limiter.set_enabled(!self.limiter.settings().enabled);
+                }
+                if let Some(ceiling_db) = widgets.ceiling.dragged(&input) {
+                    self.limiter.set_ceiling_db(ceiling_db);
+                }
+                if let Some(release_ms) = widgets.release.dragged(&input) {
+                    self.limiter.set_release_ms(release_ms);
+                }
+            }
+            Screen::Settings => {
+                let widgets = self.settings_widgets(player);
+                if widgets.visualizer.clicked(&input) {
+                    self.press_button(widgets.visualizer.tag);
+                }
+                for (name, slider) in widgets.params {
+                    if let Some(value) = slider.dragged(&input) {
+                        self.param_changes.push((name, value));
+                    }
+                }
+                if let Some(seconds) = widgets.crossfade.dragged(&input) {
+                    self.crossfade.set_seconds(seconds);
+                }
+                if widgets.crossfade_curve.clicked(&input) {
+                    self.press_button(widgets.crossfade_curve.tag);
+                }
+                if let Some((device, _)) = widgets
+                    .devices
+                    .into_iter()
+                    .find(|(_, button)| button.clicked(&input))
+                {
+                    self.send(PlayerCommand::SetOutputDevice(device));
+                }
+                if widgets.rescan.clicked(&input) {
+                    self.press_button(widgets.rescan.tag);
                 }
             }
         }
+    }
+
+    /// Handles window events that are not available through polling
+    ///
+    /// - Typing goes into the library search field or the preset name while
+    ///   focused
+    /// - Tab / Shift+Tab switch between screens
+    /// - Backspace and Escape go back to the previous screen
+    /// - Mouse wheel and list keys scroll the song list or queue when shown
+    /// - Mouse wheel over a visualizer parameter steps its value
+    ///
+    /// # Arguments
+    /// * `app` - Reference to Nannou application for input access
+    /// * `event` - The window event to handle
+    /// * `player` - The player the menu controls
+    ///
+    /// # Returns
+    /// Whether the menu used the event; unused key presses are left for the
+    /// keyboard shortcuts
+    pub fn event(&mut self, app: &App, event: &WindowEvent, player: &Player) -> bool {
+        let screen = self.navigation.current();
+        if screen == Screen::Library && self.search.event(event) {
+            self.refresh_library_matches(player);
+            return true;
+        }
+        if screen == Screen::Equalizer && self.preset_name.event(event) {
+            return true;
+        }
+        let library = player.library();
+
+        match event {
+            KeyPressed(Key::Tab) => self.navigation.cycle_tab(!app.keys.mods.shift()),
+            KeyPressed(Key::Back | Key::Escape) => self.navigation.back(),
+            MouseWheel(delta, _) => {
+                let mouse = app.mouse.position();
+                match screen {
+                    Screen::Library => {
+                        let count = self.library_matches.len();
+                        self.song_list.scroll_wheel(mouse, *delta, count);
+                    }
+                    Screen::Queue => {
+                        let count = library.up_next().len();
+                        self.queue_list.scroll_wheel(mouse, *delta, count);
+                    }
+                    Screen::Settings => self.scroll_param(mouse, *delta, player),
+                    Screen::NowPlaying | Screen::Equalizer | Screen::Effects => {}
+                }
+            }
+            KeyPressed(key) if ListView::handles(*key) => match screen {
+                Screen::Library => {
+                    let count = self.library_matches.len();
+                    if let Some(row) = self.song_list.key_pressed(*key, count) {
+                        self.play_song(self.library_matches[row]);
+                    }
+                }
+                Screen::Queue => {
+                    let count = library.up_next().len();
+                    if let Some(row) = self.queue_list.key_pressed(*key, count) {
+                        self.play_song(library.queue_start() + row);
+                    }
+                }
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Shows the library and puts the keyboard focus in its search field
+    pub fn focus_search(&mut self) {
+        self.navigation.open(Screen::Library);
+        self.search.focus();
+    }
+
+    /// Steps the visualizer parameter under the mouse by one wheel notch
+    fn scroll_param(&mut self, mouse: Point2, delta: MouseScrollDelta, player: &Player) {
+        let steps = match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines.signum() as i32,
+            MouseScrollDelta::PixelDelta(position) => position.y.signum() as i32,
+        };
+        let sliders = self.settings_widgets(player).params;
+        if let Some((param, _)) = self
+            .visualizer_params
+            .iter()
+            .zip(sliders)
+            .find(|(_, (_, slider))| slider.rect.contains(mouse))
+        {
+            self.param_changes.push((param.name, param.stepped(steps)));
+        }
+    }
+
+    /// Performs the action of the button with the given tag
+    ///
+    /// Transport buttons become player commands; the player ignores those
+    /// that make no sense in its current state.
+    ///
+    /// # Arguments
+    /// * `tag` - Tag of the pressed button
+    pub fn press_button(&mut self, tag: &str) {
+        let command = match tag {
+            "visualizer_button" => {
+                self.cycle_visualizer_requested = true;
+                return;
+            }
+            "mute_button" => {
+                self.volume.set_muted(!self.volume.is_muted());
+                return;
+            }
+            "crossfade_curve_button" => {
+                let curve = self.crossfade.crossfade().curve;
+                self.crossfade.set_curve(curve.next());
+                return;
+            }
+            "play_button" => PlayerCommand::TogglePlayPause,
+            "stop_button" => PlayerCommand::Stop,
+            "skip_backward_button" => PlayerCommand::SeekBy(-SKIP_SECONDS),
+            "skip_forward_button" => PlayerCommand::SeekBy(SKIP_SECONDS),
+            "previous_button" => PlayerCommand::Previous,
+            "next_button" => PlayerCommand::Next,
+            "rescan_devices_button" => PlayerCommand::RescanOutputDevices,
+            tag if tag.starts_with("eq_") => {
+                self.press_eq_button(tag);
+                return;
+            }
+            _ => {
+                eprintln!("Unknown menu button: {}", tag);
+                return;
+            }
+        };
+        self.send(command);
+    }
 
-        self.was_mouse_pressed = is_mouse_pressed;
+    /// Performs the action of an Equalizer screen button
+    ///
+    /// # Arguments
+    /// * `tag` - Tag of the pressed button
+    fn press_eq_button(&mut self, tag: &str) {
+        let band = self.eq_band;
+        match tag {
+            "eq_mode_button" => self.eq.update(|eq| {
+                eq.mode = match eq.mode {
+                    EqMode::Graphic => EqMode::Parametric,
+                    EqMode::Parametric => EqMode::Graphic,
+                }
+            }),
+            "eq_flat_button" => self.eq.update(EqSettings::flatten),
+            "eq_add_band_button" => {
+                let count = self.eq.settings().bands.len();
+                if count < MAX_BANDS {
+                    self.eq.update(|eq| eq.bands.push(EqBand::new(1000.0)));
+                    self.eq_band = count;
+                }
+            }
+            "eq_remove_band_button" => {
+                self.eq.update(|eq| {
+                    if band < eq.bands.len() {
+                        eq.bands.remove(band);
+                    }
+                });
+                self.eq_band = band.saturating_sub(1);
+            }
+            "eq_band_kind_button" => self.eq.update(|eq| {
+                if let Some(band) = eq.bands.get_mut(band) {
+                    band.kind = band.kind.next();
+                }
+            }),
+            "eq_save_preset_button" => {
+                let name = self.preset_name.text().trim().to_string();
+                match self.eq.settings().save_preset(&name) {
+                    Ok(()) => self.presets = preset_names(),
+                    Err(e) => eprintln!("Failed to save preset {}: {}", name, e),
+                }
+            }
+            _ => eprintln!("Unknown menu button: {}", tag),
+        }
+    }
+
+    /// Applies a dragged equalizer slider
+    ///
+    /// # Arguments
+    /// * `target` - What the slider changes
+    /// * `value` - New value of the slider
+    fn set_eq_value(&mut self, target: EqSlider, value: f32) {
+        let band = self.eq_band;
+        self.eq.update(|eq| {
+            if let EqSlider::GraphicGain(index) = target {
+                eq.graphic_gains[index] = value;
+                return;
+            }
+            let Some(band) = eq.bands.get_mut(band) else {
+                return;
+            };
+            match target {
+                EqSlider::GraphicGain(_) => {}
+                EqSlider::Frequency => band.frequency = 10f32.powf(value),
+                EqSlider::Gain => band.gain_db = value,
+                EqSlider::Q => band.q = 10f32.powf(value),
+            }
+        });
+    }
+
+    /// Switches the equalizer to a saved preset and turns it on
+    fn load_preset(&mut self, name: &str) {
+        match EqSettings::load_preset(name) {
+            Ok(mut preset) => {
+                preset.enabled = true;
+                self.eq.update(|eq| *eq = preset);
+                self.eq_band = 0;
+            }
+            Err(e) => eprintln!("Failed to load preset {}: {}", name, e),
+        }
+    }
+
+    /// Sends a command to the player
+    fn send(&self, command: PlayerCommand) {
+        // The player outlives the menu, so this cannot fail while running
+        let _ = self.commands.send(command);
+    }
+
+    /// Asks the player to play a library song and shows its controls
+    fn play_song(&mut self, index: usize) {
+        self.send(PlayerCommand::Load(index));
+        self.navigation.open(Screen::NowPlaying);
+    }
+
+    /// Recomputes which library songs match the search text
+    ///
+    /// Matching is a case-insensitive substring search on the title.
+    fn refresh_library_matches(&mut self, player: &Player) {
+        let query = self.search.text().to_lowercase();
+        self.library_matches = player
+            .library()
+            .songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| song.title.to_lowercase().contains(&query))
+            .map(|(index, _)| index)
+            .collect();
+        self.song_list.reset();
     }
 
     /// Renders the menu and all its components
     ///
     /// Draws:
     /// - Menu background panel
-    /// - Play/pause button with state-appropriate color
-    /// - Button text label
-    /// - Menu title
+    /// - The widgets of the current screen
+    /// - Screen title, back button and tabs
     ///
     /// # Arguments
     /// * `draw` - Nannou Draw context for rendering
-    pub fn draw(&self, draw: &Draw) {
+    /// * `player` - The player the menu controls
+    pub fn draw(&self, draw: &Draw, player: &Player) {
         // Draw menu background
         draw.rect()
             .xy(self.menu_rect.xy())
             .wh(self.menu_rect.wh())
             .color(rgb(0.1, 0.1, 0.1));
 
-        if self.music_library.has_selected_song() {
-            self.draw_playback_controls(draw);
+        let input = &self.input;
+        let library = player.library();
+        match self.navigation.current() {
+            Screen::Library => {
+                let (search_rect, _) = library_layout(self.content_rect());
+                self.search.draw(draw, search_rect, input);
+                self.song_list
+                    .draw(draw, self.library_matches.len(), |row| {
+                        &library.songs[self.library_matches[row]].title
+                    });
+            }
+            Screen::NowPlaying => {
+                let widgets = self.now_playing_widgets(player);
+                widgets.title.draw(draw);
+                widgets.play.draw(draw, input);
+                for button in &widgets.transport {
+                    button.draw(draw, input);
+                }
+                widgets.time.draw(draw);
+                widgets.mute.draw(draw, input);
+                widgets.volume.draw(draw, input);
+                widgets.balance.draw(draw, input);
+                for (_, button) in &widgets.normalization {
+                    button.draw(draw, input);
+                }
+                widgets.target.draw(draw, input);
+                widgets.loudness.draw(draw);
+                widgets.limiter.draw(draw);
+            }
+            Screen::Queue => {
+                let up_next = library.up_next();
+                self.queue_list
+                    .draw(draw, up_next.len(), |row| &up_next[row].title);
+            }
+            Screen::Equalizer => {
+                let widgets = self.equalizer_widgets(player);
+                widgets.curve.draw(draw);
+                widgets.enabled.draw(draw, input);
+                for button in &widgets.buttons {
+                    button.draw(draw, input);
+                }
+                for (_, tab) in &widgets.band_tabs {
+                    tab.draw(draw, input);
+                }
+                for (_, slider) in &widgets.sliders {
+                    slider.draw(draw, input);
+                }
+                widgets.preset_heading.draw(draw);
+                self.preset_name.draw(draw, widgets.preset_name, input);
+                for (_, button) in &widgets.presets {
+                    button.draw(draw, input);
+                }
+            }
+            Screen::Effects => {
+                let widgets = self.effects_widgets(player);
+                for (_, _, button) in &widgets.bypass {
+                    button.draw(draw, input);
+                }
+                for (_, button) in &widgets.moves {
+                    button.draw(draw, input);
+                }
+                for (_, slider) in &widgets.params {
+                    slider.draw(draw, input);
+                }
+                widgets.limiter_heading.draw(draw);
+                widgets.limiter.draw(draw, input);
+                widgets.ceiling.draw(draw, input);
+                widgets.release.draw(draw, input);
+                widgets.reduction.draw(draw);
+            }
+            Screen::Settings => {
+                let widgets = self.settings_widgets(player);
+                widgets.visualizer.draw(draw, input);
+                for (_, slider) in &widgets.params {
+                    slider.draw(draw, input);
+                }
+                widgets.crossfade_heading.draw(draw);
+                widgets.crossfade.draw(draw, input);
+                widgets.crossfade_curve.draw(draw, input);
+                widgets.device_heading.draw(draw);
+                for (_, button) in &widgets.devices {
+                    button.draw(draw, input);
+                }
+                widgets.rescan.draw(draw, input);
+                widgets.latency.draw(draw);
+                widgets.dropouts.draw(draw);
+            }
+        }
+
+        // Draw menu title
+        Label::new(self.navigation.current().title(), self.title_rect())
+            .font_size(24)
+            .draw(draw);
+
+        if self.navigation.can_go_back() {
+            self.back_button().draw(draw, input);
+        }
+        for (_, tab) in self.tab_buttons() {
+            tab.draw(draw, input);
+        }
+    }
+
+    /// Returns the area below the title and tabs used by the screens
+    fn content_rect(&self) -> Rect {
+        content_rect(self.menu_rect)
+    }
+
+    /// Returns the area of the title at the top of the panel
+    fn title_rect(&self) -> Rect {
+        Layout::vertical(self.menu_rect).next(TITLE_HEIGHT)
+    }
+
+    /// Builds the back button, left of the title
+    fn back_button(&self) -> Button {
+        let title = self.title_rect();
+        let rect = Rect::from_x_y_w_h(title.left() + 20.0, title.y(), 24.0, 24.0);
+        Button::new("<", "back_button", rect)
+            .enabled(self.navigation.can_go_back())
+            .font_size(18)
+    }
+
+    /// Builds one tab button per screen, below the title
+    fn tab_buttons(&self) -> Vec<(Screen, Button)> {
+        let mut layout = Layout::vertical(self.menu_rect);
+        layout.skip(TITLE_HEIGHT);
+        let row = layout.next(TAB_HEIGHT);
+        let tabs = Layout::horizontal(row)
+            .spacing(2.0)
+            .split(Screen::ALL.len());
+
+        Screen::ALL
+            .into_iter()
+            .zip(tabs)
+            .map(|(screen, rect)| {
+                let color = if screen == self.navigation.current() {
+                    rgb(0.3, 0.3, 0.35)
+                } else {
+                    rgb(0.15, 0.15, 0.15)
+                };
+                let tab = Button::new(screen.tab_label(), "tab_button", rect)
+                    .color(color)
+                    .font_size(12);
+                (screen, tab)
+            })
+            .collect()
+    }
+
+    /// Builds the widgets of the Now Playing screen
+    ///
+    /// Without a selected song every playback control is disabled; the
+    /// volume and normalization controls always work.
+    fn now_playing_widgets(&self, player: &Player) -> NowPlayingWidgets {
+        let content = self.content_rect();
+        let column = content
+            .pad_left(content.w() * 0.1)
+            .pad_right(content.w() * 0.1)
+            .pad_top(10.0);
+        let mut layout = Layout::vertical(column).spacing(10.0);
+
+        let library = player.library();
+        let state = player.state();
+        let has_song = library.has_selected_song();
+        let has_next = has_song && library.queue_start() < library.songs.len();
+        let song = player.song();
+
+        let title = match state {
+            PlayerState::Loading => "Loading...",
+            _ if has_song => song.title.as_str(),
+            PlayerState::Error => "Could not load song",
+            _ => "No song selected",
+        };
+        let title = Label::new(title, layout.next(30.0)).color(if has_song {
+            rgb(1.0, 1.0, 1.0)
         } else {
-            self.draw_song_selection_controls(draw);
+            rgb(0.5, 0.5, 0.5)
+        });
+
+        let is_playing = state == PlayerState::Playing;
+        let play = Toggle::new(layout.next(50.0), is_playing, "PAUSE", "PLAY").enabled(has_song);
+
+        let transport = [
+            ("|<", "previous_button", has_song),
+            ("-10", "skip_backward_button", has_song),
+            ("STOP", "stop_button", has_song),
+            ("+10", "skip_forward_button", has_song),
+            (">|", "next_button", has_next),
+        ];
+        let rects = Layout::horizontal(layout.next(TRANSPORT_HEIGHT))
+            .spacing(4.0)
+            .split(transport.len());
+        let transport = transport
+            .into_iter()
+            .zip(rects)
+            .map(|((label, tag, enabled), rect)| {
+                Button::new(label, tag, rect).enabled(enabled).font_size(12)
+            })
+            .collect();
+
+        let elapsed = song.position_seconds();
+        let remaining = (song.duration_seconds() - elapsed).max(0.0);
+        let time = Label::new(
+            format!("{} / -{}", format_time(elapsed), format_time(remaining)),
+            layout.next(24.0),
+        );
+
+        let mut volume_row = Layout::horizontal(layout.next(VOLUME_ROW_HEIGHT)).spacing(4.0);
+        let is_muted = self.volume.is_muted();
+        let mute = Button::new(
+            if is_muted { "UNMUTE" } else { "MUTE" },
+            "mute_button",
+            volume_row.next(MUTE_BUTTON_WIDTH),
+        )
+        .color(if is_muted {
+            rgb(0.6, 0.2, 0.2)
+        } else {
+            rgb(0.2, 0.2, 0.2)
+        })
+        .font_size(12);
+
+        let volume_db = self.volume.volume_db();
+        let volume_label = if volume_db <= MIN_VOLUME_DB {
+            "Volume: off".to_string()
+        } else {
+            format!("Volume: {:.0} dB", volume_db)
+        };
+        let volume = Slider::new(
+            volume_label,
+            volume_row.rest(),
+            volume_db,
+            MIN_VOLUME_DB,
+            MAX_VOLUME_DB,
+        )
+        .step(1.0);
+
+        let balance = self.volume.balance();
+        let balance_label = if balance.abs() < 0.01 {
+            "Balance: center".to_string()
+        } else if balance < 0.0 {
+            format!("Balance: L {:.0}%", -balance * 100.0)
+        } else {
+            format!("Balance: R {:.0}%", balance * 100.0)
+        };
+        let balance = Slider::new(
+            balance_label,
+            layout.next(VOLUME_ROW_HEIGHT),
+            balance,
+            -1.0,
+            1.0,
+        )
+        .step(0.05);
+
+        let settings = player.normalization();
+        let mut next_mode = settings;
+        next_mode.mode = settings.mode.next();
+        let mut toggled_clipping = settings;
+        toggled_clipping.prevent_clipping = !settings.prevent_clipping;
+        let is_normalizing = settings.mode != NormalizationMode::Off;
+        let rects = Layout::horizontal(layout.next(VOLUME_ROW_HEIGHT))
+            .spacing(4.0)
+            .split(2);
+        let normalization = vec![
+            (
+                next_mode,
+                Button::new(
+                    format!("Normalize: {}", settings.mode.label()),
+                    "normalization_mode_button",
+                    rects[0],
+                )
+                .font_size(12),
+            ),
+            (
+                toggled_clipping,
+                Button::new(
+                    if settings.prevent_clipping {
+                        "No clipping: on"
+                    } else {
+                        "No clipping: off"
+                    },
+                    "prevent_clipping_button",
+                    rects[1],
+                )
+                .enabled(is_normalizing)
+                .font_size(12),
+            ),
+        ];
+        let target = Slider::new(
+            format!("Target: {:.0} LUFS", settings.target_lufs),
+            layout.next(VOLUME_ROW_HEIGHT),
+            settings.target_lufs,
+            MIN_TARGET_LUFS,
+            MAX_TARGET_LUFS,
+        )
+        .step(1.0);
+
+        let loudness = match library.loudness.get(&song.filename) {
+            _ if !has_song => String::new(),
+            None => "Measuring loudness...".to_string(),
+            Some(track) => {
+                let lufs = track
+                    .track_lufs()
+                    .map_or("silent".to_string(), |lufs| format!("{:.1} LUFS", lufs));
+                let peak_db = 20.0 * track.track_peak().max(1e-5).log10();
+                let gain_db = library.loudness.gain_db(&song.filename, settings);
+                if is_normalizing {
+                    format!(
+                        "{}, peak {:.1} dBTP, gain {:+.1} dB",
+                        lufs, peak_db, gain_db
+                    )
+                } else {
+                    format!("{}, peak {:.1} dBTP", lufs, peak_db)
+                }
+            }
+        };
+        let loudness = Label::new(loudness, layout.next(24.0))
+            .font_size(12)
+            .color(rgb(0.7, 0.7, 0.7));
+        let limiter = self.limiter_meter(layout.next(EQ_ROW_HEIGHT));
+
+        NowPlayingWidgets {
+            title,
+            play,
+            transport,
+            time,
+            mute,
+            volume,
+            balance,
+            normalization,
+            target,
+            loudness,
+            limiter,
+        }
+    }
+
+    /// Builds the widgets of the Equalizer screen
+    ///
+    /// The graphic mode shows a gain slider per band. The parametric mode
+    /// shows a tab per band plus add and remove buttons, and the filter kind,
+    /// frequency, gain and Q of the selected band. Frequency and Q sliders
+    /// move in octaves rather than hertz.
+    fn equalizer_widgets(&self, player: &Player) -> EqualizerWidgets {
+        let settings = self.eq.settings();
+        let mut layout = self.settings_layout();
+
+        let sample_rate = player.output().format().unwrap_or_default().sample_rate;
+        let response = (0..EQ_CURVE_POINTS)
+            .map(|point| {
+                let octaves = point as f32 / (EQ_CURVE_POINTS - 1) as f32;
+                let frequency = MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(octaves);
+                settings.response_db(frequency, sample_rate)
+            })
+            .collect();
+        let curve = Graph::new(
+            layout.next(EQ_CURVE_HEIGHT),
+            response,
+            -EQ_CURVE_RANGE_DB,
+            EQ_CURVE_RANGE_DB,
+        );
+
+        let row = Layout::horizontal(layout.next(30.0)).spacing(4.0).split(3);
+        let enabled = Toggle::new(row[0], settings.enabled, "ON", "OFF");
+        let mode = match settings.mode {
+            EqMode::Graphic => "GRAPHIC",
+            EqMode::Parametric => "PARAMETRIC",
+        };
+        let mut buttons = vec![
+            Button::new(mode, "eq_mode_button", row[1]).font_size(12),
+            Button::new("FLAT", "eq_flat_button", row[2]).font_size(12),
+        ];
+
+        let mut band_tabs = Vec::new();
+        let mut sliders = Vec::new();
+        let mut bands = Layout::vertical(layout.next(EQ_BANDS_HEIGHT)).spacing(3.0);
+        match settings.mode {
+            EqMode::Graphic => {
+                for (index, (&frequency, &gain_db)) in GRAPHIC_FREQUENCIES
+                    .iter()
+                    .zip(&settings.graphic_gains)
+                    .enumerate()
+                {
+                    let slider = Slider::new(
+                        format!("{}: {:+.1} dB", format_frequency(frequency), gain_db),
+                        bands.next(EQ_ROW_HEIGHT),
+                        gain_db,
+                        -MAX_GAIN_DB,
+                        MAX_GAIN_DB,
+                    )
+                    .step(0.5);
+                    sliders.push((EqSlider::GraphicGain(index), slider));
+                }
+            }
+            EqMode::Parametric => {
+                let count = settings.bands.len();
+                let mut tabs = Layout::horizontal(bands.next(EQ_ROW_HEIGHT))
+                    .spacing(2.0)
+                    .split(MAX_BANDS + 2);
+                let remove = tabs.pop().unwrap_or_default();
+                let add = tabs.pop().unwrap_or_default();
+                for (index, rect) in tabs.into_iter().take(count).enumerate() {
+                    let color = if index == self.eq_band {
+                        rgb(0.3, 0.3, 0.35)
+                    } else {
+                        rgb(0.15, 0.15, 0.15)
+                    };
+                    let tab = Button::new((index + 1).to_string(), "eq_band_button", rect)
+                        .color(color)
+                        .font_size(12);
+                    band_tabs.push((index, tab));
+                }
+                buttons.push(
+                    Button::new("+", "eq_add_band_button", add)
+                        .enabled(count < MAX_BANDS)
+                        .font_size(12),
+                );
+                buttons.push(
+                    Button::new("-", "eq_remove_band_button", remove)
+                        .enabled(count > 0)
+                        .font_size(12),
+                );
+
+                if let Some(band) = settings.bands.get(self.eq_band) {
+                    buttons.push(
+                        Button::new(
+                            band.kind.label(),
+                            "eq_band_kind_button",
+                            bands.next(EQ_ROW_HEIGHT),
+                        )
+                        .font_size(12),
+                    );
+                    let frequency = Slider::new(
+                        format!("Frequency: {}", format_frequency(band.frequency)),
+                        bands.next(EQ_ROW_HEIGHT),
+                        band.frequency.log10(),
+                        MIN_FREQUENCY.log10(),
+                        MAX_FREQUENCY.log10(),
+                    );
+                    let gain_label = if band.kind.uses_gain() {
+                        format!("Gain: {:+.1} dB", band.gain_db)
+                    } else {
+                        "Gain: unused".to_string()
+                    };
+                    let gain = Slider::new(
+                        gain_label,
+                        bands.next(EQ_ROW_HEIGHT),
+                        band.gain_db,
+                        -MAX_GAIN_DB,
+                        MAX_GAIN_DB,
+                    )
+                    .step(0.5);
+                    let q = Slider::new(
+                        format!("Q: {:.2}", band.q),
+                        bands.next(EQ_ROW_HEIGHT),
+                        band.q.log10(),
+                        MIN_Q.log10(),
+                        MAX_Q.log10(),
+                    );
+                    sliders.push((EqSlider::Frequency, frequency));
+                    sliders.push((EqSlider::Gain, gain));
+                    sliders.push((EqSlider::Q, q));
+                }
+            }
+        }
+
+        let preset_heading = Label::new("Presets", layout.next(DEVICE_ROW_HEIGHT));
+        let mut save_row = Layout::horizontal(layout.next(SEARCH_HEIGHT)).spacing(4.0);
+        let row_width = save_row.rest().w();
+        let preset_name = save_row.next(row_width - MUTE_BUTTON_WIDTH - 4.0);
+        buttons.push(
+            Button::new("SAVE", "eq_save_preset_button", save_row.rest())
+                .enabled(!self.preset_name.text().trim().is_empty())
+                .font_size(12),
+        );
+        let presets = self
+            .presets
+            .iter()
+            .map(|name| {
+                let button = Button::new(
+                    name.as_str(),
+                    "eq_preset_button",
+                    layout.next(DEVICE_ROW_HEIGHT),
+                )
+                .color(rgb(0.15, 0.15, 0.15))
+                .font_size(12);
+                (name.clone(), button)
+            })
+            .collect();
+
+        EqualizerWidgets {
+            curve,
+            enabled,
+            buttons,
+            band_tabs,
+            sliders,
+            preset_heading,
+            preset_name,
+            presets,
         }
     }
 
-    fn draw_playback_controls(&self, draw: &Draw) {
-        // Draw buttons with state-dependent color
-        let button_color = if self.is_playing { GREEN } else { RED };
+    /// Builds the widgets of the Effects screen
+    ///
+    /// Every effect gets a row with its bypass switch and buttons moving it
+    /// up or down the chain, followed by a slider per parameter.
+    fn effects_widgets(&self, player: &Player) -> EffectsWidgets {
+        let effects = player.effects();
+        let settings = effects.settings();
+        let last = settings.len().saturating_sub(1);
+        let mut layout = Layout::vertical(self.settings_layout().rest()).spacing(3.0);
 
-        let play_button = self.get_button("play_button");
+        let mut bypass = Vec::new();
+        let mut moves = Vec::new();
+        let mut params = Vec::new();
+        for (index, effect) in settings.iter().enumerate() {
+            let mut row = Layout::horizontal(layout.next(DEVICE_ROW_HEIGHT)).spacing(2.0);
+            let row_width = row.rest().w();
+            let (label, color) = if effect.bypassed {
+                (format!("{}: bypassed", effect.name), rgb(0.2, 0.2, 0.2))
+            } else {
+                (format!("{}: on", effect.name), rgb(0.0, 0.5, 0.0))
+            };
+            let switch = Button::new(
+                label,
+                "effect_bypass_button",
+                row.next(row_width - 2.0 * (DEVICE_ROW_HEIGHT + 2.0)),
+            )
+            .color(color)
+            .font_size(12);
+            bypass.push((index, effect.bypassed, switch));
+            let up = Button::new("^", "effect_up_button", row.next(DEVICE_ROW_HEIGHT))
+                .enabled(index > 0)
+                .font_size(12);
+            let down = Button::new("v", "effect_down_button", row.rest())
+                .enabled(index < last)
+                .font_size(12);
+            moves.push(((index, index.saturating_sub(1)), up));
+            moves.push(((index, index + 1), down));
 
-        draw.rect()
-            .xy(play_button.unwrap().rect.xy())
-            .wh(play_button.unwrap().rect.wh())
-            .color(button_color);
+            for param in effects.params(index) {
+                let value = effect
+                    .params
+                    .get(param.name)
+                    .copied()
+                    .unwrap_or(param.default);
+                let slider = Slider::new(
+                    format!("{}: {}", param.name, param.display_value(value)),
+                    layout.next(EQ_ROW_HEIGHT),
+                    value,
+                    param.min,
+                    param.max,
+                )
+                .step(param.step);
+                params.push(((index, param.name), slider));
+            }
+            layout.skip(6.0);
+        }
 
-        // Draw button text
-        draw.text(if self.is_playing { "PAUSE" } else { "PLAY" })
-            .xy(play_button.unwrap().rect.xy())
-            .color(BLACK)
-            .font_size(24);
+        let limiter_settings = self.limiter.settings();
+        let limiter_heading = Label::new("Output limiter", layout.next(DEVICE_ROW_HEIGHT))
+            .font_size(14)
+            .color(rgb(0.7, 0.7, 0.7));
+        let limiter = Toggle::new(
+            layout.next(DEVICE_ROW_HEIGHT),
+            limiter_settings.enabled,
+            "LIMITER ON",
+            "LIMITER OFF",
+        );
+        let ceiling = Slider::new(
+            format!("Ceiling: {:.1} dBFS", limiter_settings.ceiling_db),
+            layout.next(EQ_ROW_HEIGHT),
+            limiter_settings.ceiling_db,
+            MIN_CEILING_DB,
+            MAX_CEILING_DB,
+        )
+        .step(0.1);
+        let release = Slider::new(
+            format!("Release: {:.0} ms", limiter_settings.release_ms),
+            layout.next(EQ_ROW_HEIGHT),
+            limiter_settings.release_ms,
+            MIN_RELEASE_MS,
+            MAX_RELEASE_MS,
+        )
+        .step(10.0);
+        let reduction = self.limiter_meter(layout.next(EQ_ROW_HEIGHT));
 
-        // Draw menu title
-        draw.text("CONTROLS")
-            .xy(pt2(self.menu_rect.x(), self.menu_rect.top() - 30.0))
-            .color(WHITE)
-            .font_size(30);
-    }
-
-    fn draw_song_selection_controls(&self, draw: &Draw) {
-        for (index, name) in self.music_library.get_song_names().iter().enumerate() {
-            draw.text(&name)
-                .xy(pt2(
-                    self.menu_rect.x(),
-                    self.menu_rect.top() - (150.0 + (100.0 * index as f32)),
-                ))
-                .color(WHITE)
-                .font_size(30);
+        EffectsWidgets {
+            bypass,
+            moves,
+            params,
+            limiter_heading,
+            limiter,
+            ceiling,
+            release,
+            reduction,
         }
-        // Draw menu title
-        draw.text("SONG SELECTION")
-            .xy(pt2(self.menu_rect.x(), self.menu_rect.top() - 30.0))
-            .color(WHITE)
-            .font_size(30);
-    }
-
-    fn get_button(&self, tag: &str) -> Option<&MenuButton> {
-        self.buttons.iter().find(|b| b.tag == tag)
-    }
-
-    // fn create_buttons(&mut self, menu_rect: Rect) -> Vec<MenuButton> {
-    //     let mut buttons = Vec::new();
-
-    //     let button_height = 50.0;
-    //     let button_width = menu_rect.w() * 0.8;
-
-    //     let play_button = Rect::from_x_y_w_h(
-    //         menu_rect.x(),
-    //         menu_rect.y() + menu_rect.h() * 0.3,
-    //         button_width,
-    //         button_height,
-    //     );
-
-    //     buttons.push(MenuButton {
-    //         title: "PLAY".to_string(),
-    //         tag: "play_button".to_string(),
-    //         rect: play_button,
-    //     });
-
-    //     for button in Self::create_song_buttons(&self) {
-    //         buttons.push(button);
-    //     }
-
-    //     buttons
-    // }
-
-    // fn create_song_buttons(&self) {
-    //     let mut buttons = Vec::new();
-    //     for (index, name) in self.music_library.get_song_names().iter().enumerate() {
-    //         let button_rect = Rect::from_x_y_w_h(
-    //             self.menu_rect.x(),
-    //             self.menu_rect.top() - (100.0 + (100.0 * index as f32)),
-    //             self.menu_rect.w(),
-    //             50.0,
-    //         );
-    //         buttons.push(MenuButton {
-    //             title: name.clone(),
-    //             tag: format!("song_{}", index),
-    //             rect: button_rect,
-    //         });
-    //     }
-
-    //     self.buttons = buttons;
-    // }
-
-    /// Returns current playback state
+    }
+
+    /// Builds the meter showing the limiter's gain reduction
+    ///
+    /// The meter turns red and says so while the output has clipped recently.
+    fn limiter_meter(&self, rect: Rect) -> Meter {
+        let reduction_db = self.limiter.gain_reduction_db();
+        if self.last_clip.is_some() {
+            return Meter::new("CLIP", rect, 1.0, 1.0).color(rgb(0.8, 0.1, 0.1));
+        }
+        let label = if !self.limiter.settings().enabled {
+            "Limiter: off".to_string()
+        } else if reduction_db < 0.05 {
+            "Limiter: idle".to_string()
+        } else {
+            format!("Limiter: -{:.1} dB", reduction_db)
+        };
+        Meter::new(label, rect, reduction_db, LIMITER_METER_RANGE_DB).color(rgb(0.7, 0.5, 0.0))
+    }
+
+    /// Builds the widgets of the Settings screen
+    ///
+    /// The chosen output device is highlighted, and marked as unavailable
+    /// while the output falls back to the default device. The health of the
+    /// open stream is shown below the devices.
+    fn settings_widgets(&self, player: &Player) -> SettingsWidgets {
+        let mut layout = self.settings_layout();
+        let visualizer = Button::new(
+            self.visualizer_name.as_str(),
+            "visualizer_button",
+            layout.next(50.0),
+        )
+        .font_size(20);
+
+        let params = self
+            .visualizer_params
+            .iter()
+            .map(|param| {
+                let slider = Slider::new(
+                    format!("{}: {}", param.name, param.display_value()),
+                    layout.next(PARAM_ROW_HEIGHT - 10.0),
+                    param.value,
+                    param.min,
+                    param.max,
+                )
+                .step(param.step);
+                (param.name, slider)
+            })
+            .collect();
+
+        let crossfade = self.crossfade.crossfade();
+        let crossfade_heading = Label::new("Crossfade", layout.next(DEVICE_ROW_HEIGHT))
+            .font_size(14)
+            .color(rgb(0.7, 0.7, 0.7));
+        let length = if crossfade.is_enabled() {
+            format!("Length: {:.1} s", crossfade.seconds)
+        } else {
+            "Length: off".to_string()
+        };
+        let crossfade_slider = Slider::new(
+            length,
+            layout.next(PARAM_ROW_HEIGHT - 10.0),
+            crossfade.seconds,
+            0.0,
+            MAX_CROSSFADE_SECONDS,
+        )
+        .step(0.5);
+        let crossfade_curve = Button::new(
+            format!("Curve: {}", crossfade.curve.label()),
+            "crossfade_curve_button",
+            layout.next(DEVICE_ROW_HEIGHT),
+        )
+        .font_size(12);
+
+        let output = player.output();
+        let heading = match output.format() {
+            Some(format) => format!(
+                "Output device ({}, {} Hz)",
+                output.backend_name(),
+                format.sample_rate
+            ),
+            None => format!("Output device ({})", output.backend_name()),
+        };
+        let device_heading = Label::new(heading, layout.next(DEVICE_ROW_HEIGHT));
+        let chosen = output.device();
+        let is_fallback = chosen.is_some()
+            && output
+                .active_device()
+                .is_some_and(|active| Some(active) != chosen);
+        let devices = std::iter::once(None)
+            .chain(output.devices().iter().map(|name| Some(name.as_str())))
+            .map(|device| {
+                let mut label = device.unwrap_or("System default").to_string();
+                if is_fallback && device == chosen {
+                    label.push_str(" (unavailable)");
+                }
+                let color = if device == chosen {
+                    rgb(0.3, 0.3, 0.35)
+                } else {
+                    rgb(0.15, 0.15, 0.15)
+                };
+                let button = Button::new(label, "device_button", layout.next(DEVICE_ROW_HEIGHT))
+                    .color(color)
+                    .font_size(12);
+                (device.map(str::to_string), button)
+            })
+            .collect();
+        let rescan = Button::new(
+            "Rescan devices",
+            "rescan_devices_button",
+            layout.next(DEVICE_ROW_HEIGHT),
+        )
+        .font_size(12);
+
+        let status = output.status();
+        let latency = match output.format() {
+            Some(_) => {
+                let buffer = output
+                    .buffer_frames()
+                    .map_or("default".to_string(), |frames| frames.to_string());
+                format!(
+                    "Latency: {:.1} ms, buffer: {}",
+                    output.latency().as_secs_f64() * 1000.0,
+                    buffer
+                )
+            }
+            None => "Output closed".to_string(),
+        };
+        let latency = Label::new(latency, layout.next(DEVICE_ROW_HEIGHT)).font_size(12);
+        let dropouts = Label::new(
+            format!(
+                "Underruns: {}, late callbacks: {}",
+                status.underruns(),
+                status.late_callbacks()
+            ),
+            layout.next(DEVICE_ROW_HEIGHT),
+        )
+        .font_size(12);
+
+        SettingsWidgets {
+            visualizer,
+            params,
+            crossfade_heading,
+            crossfade: crossfade_slider,
+            crossfade_curve,
+            device_heading,
+            devices,
+            rescan,
+            latency,
+            dropouts,
+        }
+    }
+
+    /// Returns the layout the Settings screen stacks its widgets in
+    fn settings_layout(&self) -> Layout {
+        let content = self.content_rect();
+        let column = content
+            .pad_left(content.w() * 0.05)
+            .pad_right(content.w() * 0.05)
+            .pad_top(10.0);
+        Layout::vertical(column).spacing(10.0)
+    }
+
+    /// Refreshes the visualizer information shown in the menu
+    ///
+    /// # Arguments
+    /// * `name` - Name of the active visualizer
+    /// * `params` - Current parameters of the active visualizer
+    pub fn set_visualizer_info(&mut self, name: &str, params: Vec<VisualizerParam>) {
+        if self.visualizer_name != name {
+            self.visualizer_name = name.to_string();
+        }
+        self.visualizer_params = params;
+    }
+
+    /// Returns whether the user asked for the next visualizer, clearing the request
+    pub fn take_visualizer_cycle(&mut self) -> bool {
+        std::mem::take(&mut self.cycle_visualizer_requested)
+    }
+
+    /// Returns the parameter changes made since the last call
     ///
     /// # Returns
-    /// `true` if audio should be playing, `false` if paused
-    pub fn is_playing(&self) -> bool {
-        self.is_playing
+    /// `(name, value)` pairs in the order the user made them
+    pub fn take_param_changes(&mut self) -> Vec<(&'static str, f32)> {
+        std::mem::take(&mut self.param_changes)
+    }
+}
+
+/// Returns the area of a menu panel below the title and tabs
+fn content_rect(menu_rect: Rect) -> Rect {
+    menu_rect.pad_top(TITLE_HEIGHT + TAB_HEIGHT)
+}
+
+/// Splits the Library screen into the search field and the song list
+fn library_layout(content_rect: Rect) -> (Rect, Rect) {
+    let mut layout = Layout::vertical(content_rect.pad(6.0)).spacing(6.0);
+    let search = layout.next(SEARCH_HEIGHT);
+    (search, layout.rest())
+}
+
+/// Formats a frequency as `63 Hz` or `1.2 kHz`
+fn format_frequency(frequency: f32) -> String {
+    if frequency >= 1000.0 {
+        format!("{:.1} kHz", frequency / 1000.0)
+    } else {
+        format!("{:.0} Hz", frequency)
     }
 }
+
+/// Formats a number of seconds as `m:ss`
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
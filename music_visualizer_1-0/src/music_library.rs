@@ -1,47 +1,108 @@
 // Import required modules and types
+use crate::normalization::LoudnessIndex; // Loudness measurements of the songs
 use crate::song::Song; // Song struct from local song module
 use std::fs; // Standard filesystem operations
+use std::path::{Path, PathBuf};
 
-/// Loads all WAV files from the music library directory into Song objects
+/// Directory scanned for songs
+pub const LIBRARY_DIR: &str = "music_library";
+/// Directory inside the library holding its index and per-track caches
+const INDEX_DIR_NAME: &str = ".index";
+
+/// Loads all WAV files from a music library directory as library entries
+///
+/// Only the file names are read; audio is decoded when a song is selected,
+/// so large libraries load instantly.
+///
+/// # Arguments
+/// * `dir` - Directory to scan
 ///
 /// # Returns
-/// A vector containing Song objects for all WAV files found
-fn load_library() -> Vec<Song> {
+/// A vector of entries for all WAV files found, sorted by title
+fn load_library(dir: &Path) -> Vec<LibraryEntry> {
     let mut songs = Vec::new(); // Create empty vector to store songs
 
-    // Get list of all WAV files in music library directory
-    let wav_files = MusicLibrary::get_file_names("music_library");
+    // Get list of all files in music library directory
+    let file_names = MusicLibrary::get_file_names(dir);
 
-    // Convert each filename to a Song object and add to vector
-    for file_name in wav_files {
-        // Create song from file path (format adds directory prefix)
-        let song = Song::from_file(&format!("{}", file_name));
-        songs.push(song);
+    // Convert each WAV filename to an entry and add to vector
+    for file_name in file_names {
+        if !file_name.to_lowercase().ends_with(".wav") {
+            continue; // Skip anything we cannot play
+        }
+        songs.push(LibraryEntry {
+            title: Song::parse_title(&file_name),
+            filename: file_name,
+        });
     }
 
+    // Directory order is arbitrary, so sort for a stable list
+    songs.sort_by(|a, b| a.title.cmp(&b.title));
+
     songs // Return populated vector
 }
 
+/// Lightweight description of a song file in the library
+#[derive(Clone, Debug)]
+pub struct LibraryEntry {
+    pub title: String,    // Display title parsed from the filename
+    pub filename: String, // Filename inside the library directory
+}
+
 /// Represents a collection of songs with selection capabilities
 pub struct MusicLibrary {
-    pub songs: Vec<Song>,    // All songs in the library
-    pub selected_song: Song, // Currently selected song for playback
+    dir: PathBuf,                      // Directory the songs are read from
+    pub songs: Vec<LibraryEntry>,      // All songs in the library
+    pub selected_song: Song,           // Currently selected song for playback
+    pub selected_index: Option<usize>, // Position of the selected song in `songs`
+    pub loudness: LoudnessIndex,       // Loudness of every song, measured in the background
 }
 
 impl MusicLibrary {
     /// Creates a new MusicLibrary instance
     ///
+    /// # Arguments
+    /// * `dir` - Directory holding the songs, usually `LIBRARY_DIR`
+    ///
     /// # Returns
-    /// Initialized MusicLibrary with all songs loaded and default selection
-    pub fn new() -> Self {
+    /// Initialized MusicLibrary with all songs loaded and nothing selected;
+    /// songs whose loudness is unknown are measured in the background
+    pub fn new(dir: &Path) -> Self {
+        let songs = load_library(dir); // Load all songs from directory
+        let file_names = songs.iter().map(|entry| entry.filename.clone()).collect();
         MusicLibrary {
-            songs: load_library(), // Load all songs from directory
-            // Set default selected song (using a popular track as example)
-            selected_song: Song::from_file("charleston-girl-live.wav"),
-            // Song::from_file("charleston-girl-live.wav")
+            dir: dir.to_path_buf(),
+            songs,
+            selected_song: Song::empty(),
+            selected_index: None,
+            loudness: LoudnessIndex::new(dir, &dir.join(INDEX_DIR_NAME), file_names),
         }
     }
 
+    /// Returns the directory the songs are read from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Builds the path of a song file
+    ///
+    /// # Arguments
+    /// * `file_name` - Filename of the song inside the library directory
+    pub fn path(&self, file_name: &str) -> PathBuf {
+        self.dir.join(file_name)
+    }
+
+    /// Builds the path of a per-track cache file in the index directory
+    ///
+    /// # Arguments
+    /// * `file_name` - Filename of the song the cache belongs to
+    /// * `extension` - Extension describing the kind of cache (e.g. `peaks.json`)
+    pub fn cache_path(&self, file_name: &str, extension: &str) -> PathBuf {
+        self.dir
+            .join(INDEX_DIR_NAME)
+            .join(format!("{}.{}", file_name, extension))
+    }
+
     /// Gets all filenames from a directory
     ///
     /// # Arguments
@@ -49,7 +110,7 @@ impl MusicLibrary {
     ///
     /// # Returns
     /// Vector of filenames as Strings
-    fn get_file_names(dir_path: &str) -> Vec<String> {
+    fn get_file_names(dir_path: &Path) -> Vec<String> {
         let mut file_names = Vec::new(); // Create empty vector for results
 
         // Attempt to read directory entries
@@ -71,28 +132,50 @@ impl MusicLibrary {
         file_names // Return collected filenames
     }
 
-    /// Selects a song from the library by title
+    /// Selects a decoded song from the library by its position in `songs`
+    ///
+    /// Starts the song's waveform overview and beat analysis.
     ///
     /// # Arguments
-    /// * `title` - Title of song to select
-    pub fn select_song(&mut self, title: &str) {
-        // Search through all songs for matching title
-        for song in &self.songs {
-            if song.title == title {
-                // Create new Song instance from filename when found
-                self.selected_song = Song::from_file(&song.filename);
-                break; // Exit loop after first match
-            }
+    /// * `index` - Index of the song to select (ignored if out of range)
+    /// * `song` - The song, decoded from `songs[index]`
+    pub fn select_index(&mut self, index: usize, song: Song) {
+        if index < self.songs.len() {
+            let source = self.path(&song.filename);
+            let peaks_cache = self.cache_path(&song.filename, "peaks.json");
+            let beats_cache = self.cache_path(&song.filename, "beats.json");
+            self.selected_song = song;
+            self.selected_song.load_peaks(source.clone(), peaks_cache);
+            self.selected_song.analyze_beats(source, beats_cache);
+            self.selected_index = Some(index);
         }
     }
 
-    /// Gets all song titles in the library
+    /// Gets the index of the song after the selected one, if there is one
+    pub fn next_index(&self) -> Option<usize> {
+        self.selected_index
+            .map(|index| index + 1)
+            .filter(|&index| index < self.songs.len())
+    }
+
+    /// Gets the index of the song before the selected one, if there is one
+    pub fn previous_index(&self) -> Option<usize> {
+        self.selected_index.and_then(|index| index.checked_sub(1))
+    }
+
+    /// Gets the songs that follow the selected one, in library order
     ///
     /// # Returns
-    /// Vector of song title strings
-    pub fn get_song_names(&self) -> Vec<String> {
-        // Map each song to its title and collect into vector
-        self.songs.iter().map(|song| song.title.clone()).collect()
+    /// Slice of upcoming songs (the whole library if nothing is selected)
+    pub fn up_next(&self) -> &[LibraryEntry] {
+        let start = self.queue_start();
+        &self.songs[start..]
+    }
+
+    /// Gets the index in `songs` of the first entry returned by `up_next`
+    pub fn queue_start(&self) -> usize {
+        self.selected_index
+            .map_or(0, |index| (index + 1).min(self.songs.len()))
     }
 
     /// Checks if a song is currently selected
@@ -0,0 +1,137 @@
+//! Menu navigation model
+//!
+//! The menu is split into screens that are reached through a row of tabs:
+//! - Library: every song in the music library
+//! - Now Playing: playback controls for the selected song
+//! - Queue: the songs that follow the selected one
+//! - Equalizer: equalizer bands, response curve and presets
+//! - Effects: effect chain order, bypass switches and parameters
+//! - Settings: visualizer selection and parameters, output device
+//!
+//! Every screen change is remembered, so "back" returns to the previous screen
+//! the way a browser history does.
+
+/// Most screens remembered for back navigation
+const MAX_HISTORY: usize = 32;
+
+/// A screen of the menu
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Screen {
+    Library,
+    NowPlaying,
+    Queue,
+    Equalizer,
+    Effects,
+    Settings,
+}
+
+impl Screen {
+    /// All screens in tab order
+    pub const ALL: [Screen; 6] = [
+        Screen::Library,
+        Screen::NowPlaying,
+        Screen::Queue,
+        Screen::Equalizer,
+        Screen::Effects,
+        Screen::Settings,
+    ];
+
+    /// Returns the title shown at the top of the menu
+    pub fn title(self) -> &'static str {
+        match self {
+            Screen::Library => "LIBRARY",
+            Screen::NowPlaying => "NOW PLAYING",
+            Screen::Queue => "QUEUE",
+            Screen::Equalizer => "EQUALIZER",
+            Screen::Effects => "EFFECTS",
+            Screen::Settings => "SETTINGS",
+        }
+    }
+
+    /// Returns the short label shown on the screen's tab
+    pub fn tab_label(self) -> &'static str {
+        match self {
+            Screen::Library => "LIB",
+            Screen::NowPlaying => "NOW",
+            Screen::Queue => "QUEUE",
+            Screen::Equalizer => "EQ",
+            Screen::Effects => "FX",
+            Screen::Settings => "SET",
+        }
+    }
+
+    /// Returns the position of the screen in the tab row
+    pub fn tab_index(self) -> usize {
+        Screen::ALL.iter().position(|&s| s == self).unwrap_or(0)
+    }
+}
+
+/// Current screen plus the history used by back navigation
+pub struct Navigation {
+    /// Screen currently shown
+    current: Screen,
+    /// Previously shown screens, most recent last
+    history: Vec<Screen>,
+}
+
+impl Navigation {
+    /// Creates a navigation model starting at a screen, with empty history
+    ///
+    /// # Arguments
+    /// * `start` - The first screen shown
+    pub fn new(start: Screen) -> Self {
+        Navigation {
+            current: start,
+            history: Vec::new(),
+        }
+    }
+
+    /// Returns the screen currently shown
+    pub fn current(&self) -> Screen {
+        self.current
+    }
+
+    /// Returns whether there is a screen to go back to
+    pub fn can_go_back(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Shows a screen, remembering the current one for back navigation
+    ///
+    /// Opening the screen that is already shown does nothing.
+    ///
+    /// # Arguments
+    /// * `screen` - The screen to show
+    pub fn open(&mut self, screen: Screen) {
+        if screen == self.current {
+            return;
+        }
+        if self.history.len() == MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(self.current);
+        self.current = screen;
+    }
+
+    /// Returns to the previously shown screen, if any
+    pub fn back(&mut self) {
+        if let Some(previous) = self.history.pop() {
+            self.current = previous;
+        }
+    }
+
+    /// Opens the tab next to the current one, wrapping around the ends
+    ///
+    /// # Arguments
+    /// * `forward` - True for the tab to the right, false for the one to the left
+    pub fn cycle_tab(&mut self, forward: bool) {
+        let count = Screen::ALL.len();
+        let index = self.current.tab_index();
+        let next = if forward {
+            (index + 1) % count
+        } else {
+            (index + count - 1) % count
+        };
+        self.open(Screen::ALL[next]);
+    }
+}
@@ -0,0 +1,547 @@
+//! Loudness normalization
+//!
+//! Plays every song at about the same loudness, so quiet masters do not
+//! need turning up and loud ones do not blast:
+//! - A background scanner measures the EBU R128 loudness and true peak of
+//!   every library song once and keeps the results in the library index
+//! - ReplayGain tags in a file win over the measurement, since they were
+//!   usually computed from the original master
+//! - Songs are normalized on their own, or as part of their album so the
+//!   quiet and loud songs of an album keep their difference
+//! - Optionally, the gain is lowered so the loudest peak stays below full
+//!   scale
+//!
+//! The gain is applied per track in the audio path through its `TrackGain`.
+
+use crate::analysis::{Loudness, SourceStamp};
+use crate::song::Song;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Loudness ReplayGain tags are computed against, in LUFS
+pub const REPLAY_GAIN_REFERENCE_LUFS: f32 = -18.0;
+/// Lowest target loudness offered, in LUFS
+pub const MIN_TARGET_LUFS: f32 = -30.0;
+/// Highest target loudness offered, in LUFS
+pub const MAX_TARGET_LUFS: f32 = -6.0;
+/// File in the index directory the measurements are stored in
+const INDEX_FILE: &str = "loudness.json";
+/// Shortest time between two writes of the index while scanning
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How songs are normalized
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationMode {
+    /// Songs play at their own level
+    #[default]
+    Off,
+    /// Every song is brought to the target loudness
+    Track,
+    /// Every album is brought to the target loudness as a whole; songs
+    /// without an album are normalized on their own
+    Album,
+}
+
+impl NormalizationMode {
+    /// Returns the label shown in the menu
+    pub fn label(self) -> &'static str {
+        match self {
+            NormalizationMode::Off => "Off",
+            NormalizationMode::Track => "Track",
+            NormalizationMode::Album => "Album",
+        }
+    }
+
+    /// Returns the mode after this one, wrapping around
+    pub fn next(self) -> Self {
+        match self {
+            NormalizationMode::Off => NormalizationMode::Track,
+            NormalizationMode::Track => NormalizationMode::Album,
+            NormalizationMode::Album => NormalizationMode::Off,
+        }
+    }
+}
+
+/// Normalization settings
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizationSettings {
+    /// Whether and how songs are normalized
+    pub mode: NormalizationMode,
+    /// Loudness songs are brought to, in LUFS
+    pub target_lufs: f32,
+    /// True if the gain is lowered where the song's peak would clip
+    pub prevent_clipping: bool,
+}
+
+impl Default for NormalizationSettings {
+    fn default() -> Self {
+        NormalizationSettings {
+            mode: NormalizationMode::Off,
+            target_lufs: REPLAY_GAIN_REFERENCE_LUFS,
+            prevent_clipping: true,
+        }
+    }
+}
+
+/// ReplayGain tags of a file
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayGain {
+    /// Gain that brings the track to the reference loudness, in dB
+    pub track_gain_db: Option<f32>,
+    /// Peak of the track, linear
+    pub track_peak: Option<f32>,
+    /// Gain that brings the album to the reference loudness, in dB
+    pub album_gain_db: Option<f32>,
+    /// Peak of the album, linear
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Takes a tag if it is one of the ReplayGain ones
+    ///
+    /// # Arguments
+    /// * `name` - Tag name, e.g. `REPLAYGAIN_TRACK_GAIN` (any case)
+    /// * `value` - Tag value, e.g. `-6.50 dB` or `0.988547`
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        let number = value
+            .strip_suffix("dB")
+            .or_else(|| value.strip_suffix("db"))
+            .unwrap_or(value)
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|number| number.is_finite());
+        match name.trim().to_ascii_uppercase().as_str() {
+            "REPLAYGAIN_TRACK_GAIN" => self.track_gain_db = number,
+            "REPLAYGAIN_TRACK_PEAK" => self.track_peak = number,
+            "REPLAYGAIN_ALBUM_GAIN" => self.album_gain_db = number,
+            "REPLAYGAIN_ALBUM_PEAK" => self.album_peak = number,
+            _ => {}
+        }
+    }
+}
+
+/// What is known about the loudness of one song file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackLoudness {
+    /// Version of the file that was measured
+    source: Option<SourceStamp>,
+    /// Album the file is tagged with
+    pub album: Option<String>,
+    /// Length of the song, which weighs its loudness within the album
+    pub duration_seconds: f64,
+    /// Measured loudness and true peak
+    pub loudness: Loudness,
+    /// ReplayGain tags of the file
+    pub replay_gain: ReplayGain,
+}
+
+impl TrackLoudness {
+    /// Returns the loudness of the song in LUFS, preferring its tags
+    pub fn track_lufs(&self) -> Option<f32> {
+        self.replay_gain
+            .track_gain_db
+            .map(|gain| REPLAY_GAIN_REFERENCE_LUFS - gain)
+            .or(self.loudness.integrated_lufs)
+    }
+
+    /// Returns the linear peak of the song, preferring its tags
+    pub fn track_peak(&self) -> f32 {
+        self.replay_gain
+            .track_peak
+            .unwrap_or(self.loudness.true_peak)
+    }
+}
+
+/// Loudness of the library songs, kept up to date in the background
+pub struct LoudnessIndex {
+    /// Measurements by filename inside the library directory
+    tracks: BTreeMap<String, TrackLoudness>,
+    /// File the measurements are stored in
+    path: PathBuf,
+    /// Asks the scanner to measure a song before the others
+    requests: Sender<String>,
+    /// Measurements finished by the scanner
+    results: Receiver<(String, TrackLoudness)>,
+    /// True if measurements arrived since the index was last written
+    dirty: bool,
+    /// When the index was last written
+    last_save: Instant,
+}
+
+impl LoudnessIndex {
+    /// Loads the stored measurements and starts scanning the library
+    ///
+    /// Measurements of files no longer in the library are dropped. Songs that
+    /// are missing or changed since they were measured are measured again,
+    /// one at a time.
+    ///
+    /// # Arguments
+    /// * `library_dir` - Directory holding the songs
+    /// * `index_dir` - Directory the measurements are stored in
+    /// * `file_names` - Filenames of all songs in the library
+    pub fn new(library_dir: &Path, index_dir: &Path, file_names: Vec<String>) -> Self {
+        let path = index_dir.join(INDEX_FILE);
+        let mut tracks = Self::load(&path).unwrap_or_default();
+        tracks.retain(|file_name, _| file_names.contains(file_name));
+        let measured = tracks
+            .iter()
+            .map(|(file_name, track)| (file_name.clone(), track.source.clone()))
+            .collect();
+
+        let (requests, request_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        let library_dir = library_dir.to_path_buf();
+        thread::spawn(move || {
+            scan(
+                &library_dir,
+                file_names,
+                measured,
+                request_receiver,
+                result_sender,
+            )
+        });
+
+        LoudnessIndex {
+            tracks,
+            path,
+            requests,
+            results,
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Returns what is known about a song, or `None` until it is measured
+    pub fn get(&self, file_name: &str) -> Option<&TrackLoudness> {
+        self.tracks.get(file_name)
+    }
+
+    /// Has the scanner measure a song next, e.g. because it was selected
+    pub fn prioritize(&self, file_name: &str) {
+        // The scanner only stops when the index is dropped
+        let _ = self.requests.send(file_name.to_string());
+    }
+
+    /// Collects finished measurements, writing the index now and then
+    ///
+    /// Called once per frame; never blocks.
+    ///
+    /// # Returns
+    /// Whether any measurement arrived
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((file_name, track)) = self.results.try_recv() {
+            self.tracks.insert(file_name, track);
+            changed = true;
+        }
+        self.dirty |= changed;
+        if self.dirty && self.last_save.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+        changed
+    }
+
+    /// Computes the gain a song is played at
+    ///
+    /// # Arguments
+    /// * `file_name` - Filename of the song inside the library directory
+    /// * `settings` - Normalization mode, target and clipping prevention
+    ///
+    /// # Returns
+    /// Gain in dB; 0.0 when normalization is off or the song is not measured
+    pub fn gain_db(&self, file_name: &str, settings: NormalizationSettings) -> f32 {
+        let Some(track) = self.tracks.get(file_name) else {
+            return 0.0;
+        };
+        let (lufs, peak) = match settings.mode {
+            NormalizationMode::Off => return 0.0,
+            NormalizationMode::Track => (track.track_lufs(), track.track_peak()),
+            NormalizationMode::Album => self
+                .album_loudness(track)
+                .unwrap_or((track.track_lufs(), track.track_peak())),
+        };
+        let Some(lufs) = lufs else {
+            return 0.0;
+        };
+
+        let gain = settings.target_lufs - lufs;
+        if settings.prevent_clipping && peak > 0.0 {
+            gain.min(-20.0 * peak.log10())
+        } else {
+            gain
+        }
+    }
+
+    /// Returns the loudness in LUFS and linear peak of a song's album
+    ///
+    /// Album tags are used when present. Otherwise the album's measured songs
+    /// are combined, weighing each by its length.
+    ///
+    /// # Returns
+    /// `None` if the song has no album
+    fn album_loudness(&self, track: &TrackLoudness) -> Option<(Option<f32>, f32)> {
+        let album = track.album.as_ref()?;
+        if let Some(gain) = track.replay_gain.album_gain_db {
+            let peak = track.replay_gain.album_peak.unwrap_or(track.track_peak());
+            return Some((Some(REPLAY_GAIN_REFERENCE_LUFS - gain), peak));
+        }
+
+        let mut power = 0.0;
+        let mut duration = 0.0;
+        let mut peak = 0.0f32;
+        for other in self.tracks.values() {
+            if other.album.as_ref() != Some(album) {
+                continue;
+            }
+            peak = peak.max(other.track_peak());
+            if let Some(lufs) = other.track_lufs() {
+                power += other.duration_seconds * 10f64.powf(lufs as f64 / 10.0);
+                duration += other.duration_seconds;
+            }
+        }
+        let lufs = (power > 0.0).then(|| (10.0 * (power / duration).log10()) as f32);
+        Some((lufs, peak))
+    }
+
+    /// Reads the stored measurements, returning `None` if there are none
+    fn load(path: &Path) -> Option<BTreeMap<String, TrackLoudness>> {
+        let json = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Writes the measurements to the index file, reporting failures
+    fn save(&mut self) {
+        let result = (|| -> io::Result<()> {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&self.path, serde_json::to_string(&self.tracks)?)
+        })();
+        if let Err(e) = result {
+            eprintln!("Failed to save the loudness index: {}", e);
+        }
+        self.dirty = false;
+        self.last_save = Instant::now();
+    }
+}
+
+impl Drop for LoudnessIndex {
+    fn drop(&mut self) {
+        if self.dirty {
+            self.save();
+        }
+    }
+}
+
+/// Measures library songs until every one is up to date; runs on its own thread
+///
+/// Songs asked for through `requests` go first. Once the library is done the
+/// scanner waits for requests, and stops when the index is dropped.
+///
+/// # Arguments
+/// * `library_dir` - Directory holding the songs
+/// * `queue` - Songs to check, in order
+/// * `measured` - Version of every song already measured
+/// * `requests` - Songs to measure next
+/// * `results` - Receives every finished measurement
+fn scan(
+    library_dir: &Path,
+    queue: Vec<String>,
+    mut measured: BTreeMap<String, Option<SourceStamp>>,
+    requests: Receiver<String>,
+    results: Sender<(String, TrackLoudness)>,
+) {
+    let mut queue = VecDeque::from(queue);
+    loop {
+        loop {
+            match requests.try_recv() {
+                Ok(file_name) => queue.push_front(file_name),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        let file_name = match queue.pop_front() {
+            Some(file_name) => file_name,
+            None => match requests.recv() {
+                Ok(file_name) => file_name,
+                Err(_) => return,
+            },
+        };
+
+        let path = library_dir.join(&file_name);
+        let stamp = SourceStamp::of(&path);
+        if stamp.is_some() && measured.get(&file_name) == Some(&stamp) {
+            continue;
+        }
+        let audio = match Song::decode(&path) {
+            Ok(audio) => audio,
+            Err(e) => {
+                eprintln!("Failed to measure the loudness of {}: {}", file_name, e);
+                // Not retried until the file changes
+                measured.insert(file_name, stamp);
+                continue;
+            }
+        };
+
+        let spec = audio.spec();
+        let frames = audio.samples().len() / spec.channels.max(1) as usize;
+        let track = TrackLoudness {
+            source: stamp.clone(),
+            album: audio.album().map(str::to_string),
+            duration_seconds: frames as f64 / spec.sample_rate.max(1) as f64,
+            loudness: Loudness::measure(audio.samples(), spec.channels, spec.sample_rate),
+            replay_gain: audio.replay_gain(),
+        };
+        measured.insert(file_name.clone(), stamp);
+        if results.send((file_name, track)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a measured song
+    fn track(lufs: f32, peak: f32, album: Option<&str>, duration_seconds: f64) -> TrackLoudness {
+        TrackLoudness {
+            source: None,
+            album: album.map(str::to_string),
+            duration_seconds,
+            loudness: Loudness {
+                integrated_lufs: Some(lufs),
+                true_peak: peak,
+            },
+            replay_gain: ReplayGain::default(),
+        }
+    }
+
+    /// Builds an index of measured songs without scanning anything
+    fn index(tracks: Vec<(&str, TrackLoudness)>) -> LoudnessIndex {
+        let (requests, _) = mpsc::channel();
+        let (_, results) = mpsc::channel();
+        LoudnessIndex {
+            tracks: tracks
+                .into_iter()
+                .map(|(file_name, track)| (file_name.to_string(), track))
+                .collect(),
+            path: PathBuf::new(),
+            requests,
+            results,
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    fn settings(mode: NormalizationMode, prevent_clipping: bool) -> NormalizationSettings {
+        NormalizationSettings {
+            mode,
+            target_lufs: -18.0,
+            prevent_clipping,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn replay_gain_tags_take_decibel_suffixes() {
+        let mut replay_gain = ReplayGain::default();
+        replay_gain.set_tag("REPLAYGAIN_TRACK_GAIN", "-6.50 dB");
+        replay_gain.set_tag("replaygain_album_gain", " +1.5db ");
+        replay_gain.set_tag("REPLAYGAIN_TRACK_PEAK", "0.988547");
+        replay_gain.set_tag("REPLAYGAIN_ALBUM_PEAK", "loud");
+        replay_gain.set_tag("REPLAYGAIN_REFERENCE_LOUDNESS", "89.0 dB");
+        assert_eq!(
+            replay_gain,
+            ReplayGain {
+                track_gain_db: Some(-6.5),
+                track_peak: Some(0.988547),
+                album_gain_db: Some(1.5),
+                album_peak: None,
+            }
+        );
+    }
+
+    #[test]
+    fn brings_tracks_to_the_target() {
+        let mut tagged = track(-14.0, 0.5, None, 60.0);
+        tagged.replay_gain.track_gain_db = Some(-6.5);
+        let index = index(vec![
+            ("measured", track(-14.0, 0.5, None, 60.0)),
+            ("tagged", tagged),
+        ]);
+        let track_mode = settings(NormalizationMode::Track, false);
+
+        assert_close(index.gain_db("measured", track_mode), -4.0);
+        // Tags win over the measurement
+        assert_close(index.gain_db("tagged", track_mode), -6.5);
+        assert_eq!(index.gain_db("unknown", track_mode), 0.0);
+        assert_eq!(
+            index.gain_db("measured", settings(NormalizationMode::Off, false)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn album_mode_falls_back_to_the_track_gain() {
+        let mut tagged = track(-14.0, 0.5, Some("Tagged"), 60.0);
+        tagged.replay_gain.album_gain_db = Some(2.0);
+        let index = index(vec![
+            ("single", track(-12.0, 0.5, None, 60.0)),
+            ("quiet", track(-20.0, 0.5, Some("Album"), 60.0)),
+            (
+                "loud",
+                track(-20.0 + 10.0 * 3f32.log10(), 0.5, Some("Album"), 60.0),
+            ),
+            ("tagged", tagged),
+        ]);
+        let album_mode = settings(NormalizationMode::Album, false);
+
+        // Without an album a song is normalized on its own
+        assert_close(index.gain_db("single", album_mode), -6.0);
+        // The loud song has three times the power of the equally long quiet
+        // one, so the album is twice as powerful as the quiet song: 3 dB up
+        assert_close(
+            index.gain_db("quiet", album_mode),
+            -18.0 - (-20.0 + 10.0 * 2f32.log10()),
+        );
+        assert_close(
+            index.gain_db("quiet", album_mode),
+            index.gain_db("loud", album_mode),
+        );
+        assert_close(index.gain_db("tagged", album_mode), 2.0);
+    }
+
+    #[test]
+    fn prevent_clipping_limits_the_gain_to_the_peak() {
+        let index = index(vec![("quiet", track(-24.0, 0.8, None, 60.0))]);
+
+        assert_close(
+            index.gain_db("quiet", settings(NormalizationMode::Track, false)),
+            6.0,
+        );
+        // 0.8 reaches full scale 1.94 dB up
+        assert_close(
+            index.gain_db("quiet", settings(NormalizationMode::Track, true)),
+            -20.0 * 0.8f32.log10(),
+        );
+    }
+}
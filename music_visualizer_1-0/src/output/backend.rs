@@ -0,0 +1,408 @@
+//! The interface between the output and whatever consumes its audio
+//!
+//! An `OutputBackend` opens streams that repeatedly ask a render function
+//! for the next buffer of interleaved samples. The render function is built
+//! once the stream format is known, since the fades and crossfades depend on
+//! the sample rate.
+//!
+//! Backends report the health of their stream through a `StreamStatus`:
+//! whether the device went away, the output latency, and how often the
+//! device ran dry (an underrun) or a callback came later than expected.
+//! Besides a backend reporting it outright, a device counts as gone when its
+//! stream fails several times in a row, or when it stops asking for buffers.
+//!
+//! Backends without sound hardware drive the render function from a thread
+//! of their own, at the pace of a real device or as fast as possible. They
+//! only pass on buffers that hold audio: while nothing plays, e.g. while
+//! paused, stopped, or waiting for the next song, nothing is written and an
+//! unthrottled thread waits instead of rendering silence as fast as it can.
+//! Tests step the output deterministically through a backend that keeps
+//! the render function and calls it directly.
+
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Default frames per buffer of backends that run their own thread
+pub const PUMP_BUFFER_FRAMES: u32 = 512;
+/// Time an unthrottled thread waits before asking again while nothing plays
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Stream errors in a row, without a buffer rendered between them, after
+/// which the device counts as gone
+const LOST_AFTER_ERRORS: u64 = 3;
+/// Buffer periods without a callback after which the device counts as gone
+const STALLED_BUFFERS: u32 = 8;
+/// Shortest time without a callback after which the device counts as gone,
+/// so that small buffers do not turn a scheduling hiccup into a reopen
+const MIN_STALL: Duration = Duration::from_millis(500);
+
+/// Channel count and sample rate of an output stream
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StreamFormat {
+    /// Number of interleaved channels
+    pub channels: usize,
+    /// Frames per second
+    pub sample_rate: u32,
+}
+
+impl Default for StreamFormat {
+    fn default() -> Self {
+        StreamFormat {
+            channels: 2,
+            sample_rate: 48_000,
+        }
+    }
+}
+
+/// Fills a buffer of interleaved samples; runs on the audio thread
+///
+/// Returns false if there was nothing to play, leaving the buffer silent.
+/// It stays that way until the main thread changes something, e.g. resumes
+/// playback or queues a song; backends that decide themselves when to render
+/// may skip such buffers.
+pub type RenderFn = Box<dyn FnMut(&mut [f32]) -> bool + Send>;
+
+/// Errors that can occur while opening an output stream
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("no audio output device available")]
+    NoDevice,
+    #[error("could not configure the output device: {0}")]
+    Config(String),
+    #[error("unsupported sample format: {0}")]
+    UnsupportedFormat(String),
+    #[error("could not start the output stream: {0}")]
+    Stream(String),
+    #[error("could not create the output file: {0}")]
+    File(#[from] hound::Error),
+}
+
+/// Health of an output stream, updated from its audio thread
+pub struct StreamStatus {
+    /// When the status was created, just before the stream was opened
+    opened: Instant,
+    /// Set when the device goes away while the stream runs
+    device_lost: AtomicBool,
+    /// Stream errors since the last callback
+    consecutive_errors: AtomicU64,
+    /// When the last callback ran, in microseconds after `opened`
+    last_callback_micros: AtomicU64,
+    /// Length of the last callback's buffer, in microseconds; 0 before the
+    /// first callback
+    buffer_micros: AtomicU64,
+    /// Time from rendering a sample until it is heard, in microseconds
+    latency_micros: AtomicU64,
+    /// Buffers the device played before the next one was ready
+    underruns: AtomicU64,
+    /// Callbacks that came more than half a buffer later than expected
+    late_callbacks: AtomicU64,
+}
+
+impl Default for StreamStatus {
+    fn default() -> Self {
+        StreamStatus {
+            opened: Instant::now(),
+            device_lost: AtomicBool::new(false),
+            consecutive_errors: AtomicU64::new(0),
+            last_callback_micros: AtomicU64::new(0),
+            buffer_micros: AtomicU64::new(0),
+            latency_micros: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            late_callbacks: AtomicU64::new(0),
+        }
+    }
+}
+
+impl StreamStatus {
+    /// Reports that the device went away
+    pub fn set_device_lost(&self) {
+        self.device_lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Counts a stream error; enough of them in a row mean the device went
+    /// away
+    pub fn add_stream_error(&self) {
+        let errors = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        if errors >= LOST_AFTER_ERRORS {
+            self.set_device_lost();
+        }
+    }
+
+    /// Records that the stream asked for a buffer
+    ///
+    /// # Arguments
+    /// * `buffer_duration` - Time the buffer lasts when played
+    pub fn record_callback(&self, buffer_duration: Duration) {
+        let micros = self.opened.elapsed().as_micros() as u64;
+        self.last_callback_micros.store(micros, Ordering::Relaxed);
+        self.buffer_micros
+            .store(buffer_duration.as_micros() as u64, Ordering::Relaxed);
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns whether the stream stopped asking for buffers
+    ///
+    /// Only meaningful while something plays; a stream that was never called
+    /// counts from when it was opened.
+    pub fn is_stalled(&self) -> bool {
+        self.is_stalled_at(Instant::now())
+    }
+
+    /// Returns whether the stream had stopped asking for buffers at `now`
+    fn is_stalled_at(&self, now: Instant) -> bool {
+        let last_callback =
+            self.opened + Duration::from_micros(self.last_callback_micros.load(Ordering::Relaxed));
+        let buffer = Duration::from_micros(self.buffer_micros.load(Ordering::Relaxed));
+        let limit = (buffer * STALLED_BUFFERS).max(MIN_STALL);
+        now.saturating_duration_since(last_callback) > limit
+    }
+
+    /// Returns whether the device went away
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Records the latency measured in a callback
+    pub fn set_latency(&self, latency: Duration) {
+        self.latency_micros
+            .store(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the last latency measured
+    pub fn latency(&self) -> Duration {
+        Duration::from_micros(self.latency_micros.load(Ordering::Relaxed))
+    }
+
+    /// Counts an underrun
+    pub fn add_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of underruns so far
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Counts a late callback
+    pub fn add_late_callback(&self) {
+        self.late_callbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of late callbacks so far
+    pub fn late_callbacks(&self) -> u64 {
+        self.late_callbacks.load(Ordering::Relaxed)
+    }
+}
+
+/// A running output stream; dropping it stops the stream
+pub struct ActiveStream {
+    /// Name of the device the stream plays on
+    device: String,
+    /// Channels and sample rate of the stream
+    format: StreamFormat,
+    /// Frames per buffer, if known; `None` for the device's default
+    buffer_frames: Option<u32>,
+    /// Whatever keeps the stream running while held
+    _handle: Box<dyn Any>,
+}
+
+impl ActiveStream {
+    /// Wraps a backend's stream handle
+    ///
+    /// # Arguments
+    /// * `device` - Name of the device the stream plays on
+    /// * `format` - Channels and sample rate of the stream
+    /// * `buffer_frames` - Frames per buffer; `None` for the device's default
+    /// * `handle` - Keeps the stream running until dropped
+    pub fn new(
+        device: String,
+        format: StreamFormat,
+        buffer_frames: Option<u32>,
+        handle: impl Any,
+    ) -> Self {
+        ActiveStream {
+            device,
+            format,
+            buffer_frames,
+            _handle: Box::new(handle),
+        }
+    }
+
+    /// Returns the name of the device the stream plays on
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Returns the channels and sample rate of the stream
+    pub fn format(&self) -> StreamFormat {
+        self.format
+    }
+
+    /// Returns the frames per buffer, if known
+    pub fn buffer_frames(&self) -> Option<u32> {
+        self.buffer_frames
+    }
+}
+
+/// Destination of the mixed audio
+pub trait OutputBackend {
+    /// Returns the name shown for the backend, e.g. `ALSA` or `null`
+    fn name(&self) -> &str;
+
+    /// Returns the names of the devices the backend can play on
+    fn devices(&self) -> Vec<String>;
+
+    /// Opens a stream and starts rendering into it
+    ///
+    /// # Arguments
+    /// * `device` - Name of the device to play on; `None` or a missing device
+    ///   uses the default one
+    /// * `buffer_frames` - Frames per buffer; `None` uses the device default,
+    ///   and sizes the device does not support are clamped to what it does
+    /// * `source_format` - Format of the audio about to play, if known;
+    ///   backends free to choose their format open the stream in it, so it
+    ///   needs no conversion
+    /// * `status` - Receives the health of the stream while it runs
+    /// * `make_render` - Builds the render function for the stream's format
+    fn open(
+        &self,
+        device: Option<&str>,
+        buffer_frames: Option<u32>,
+        source_format: Option<StreamFormat>,
+        status: Arc<StreamStatus>,
+        make_render: &mut dyn FnMut(StreamFormat) -> RenderFn,
+    ) -> Result<ActiveStream, BackendError>;
+}
+
+/// How fast a backend without sound hardware consumes audio
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pace {
+    /// As fast as a device playing the stream would
+    RealTime,
+    /// As fast as the audio can be rendered
+    Unthrottled,
+}
+
+/// Thread rendering buffers and handing them to a sink until dropped
+pub struct PumpThread {
+    /// Cleared to stop the thread
+    running: Arc<AtomicBool>,
+    /// The thread, joined when dropped
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PumpThread {
+    /// Starts rendering on a new thread
+    ///
+    /// At real-time pace the latency is one buffer, and rendering that falls
+    /// behind by more than a buffer counts as an underrun. Buffers without
+    /// audio are not handed to the sink; unthrottled, the thread then waits
+    /// briefly before asking again.
+    ///
+    /// # Arguments
+    /// * `format` - Channels and sample rate of the rendered audio
+    /// * `buffer_frames` - Frames per buffer
+    /// * `pace` - How fast to render
+    /// * `status` - Receives the latency and underruns
+    /// * `render` - Fills each buffer
+    /// * `sink` - Consumes each rendered buffer
+    pub fn spawn(
+        format: StreamFormat,
+        buffer_frames: u32,
+        pace: Pace,
+        status: Arc<StreamStatus>,
+        mut render: RenderFn,
+        mut sink: impl FnMut(&[f32]) + Send + 'static,
+    ) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let buffer_duration =
+            Duration::from_secs_f64(buffer_frames as f64 / format.sample_rate as f64);
+        if pace == Pace::RealTime {
+            status.set_latency(buffer_duration);
+        }
+        let thread = {
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut buffer = vec![0.0; buffer_frames as usize * format.channels];
+                let mut started = Instant::now();
+                let mut buffers_rendered = 0u32;
+                while running.load(Ordering::Relaxed) {
+                    status.record_callback(buffer_duration);
+                    let has_audio = render(&mut buffer);
+                    if has_audio {
+                        sink(&buffer);
+                    } else if pace == Pace::Unthrottled {
+                        thread::sleep(IDLE_POLL_INTERVAL);
+                    }
+                    buffers_rendered += 1;
+                    if pace == Pace::RealTime {
+                        // Sleep until the device would have played the buffer,
+                        // measured from the start so that errors do not add up
+                        let due = buffer_duration * buffers_rendered;
+                        let elapsed = started.elapsed();
+                        if let Some(wait) = due.checked_sub(elapsed) {
+                            thread::sleep(wait);
+                        } else if elapsed > due + buffer_duration {
+                            // Fell behind; start counting again from now
+                            status.add_underrun();
+                            started = Instant::now();
+                            buffers_rendered = 0;
+                        }
+                    }
+                }
+            })
+        };
+        PumpThread {
+            running,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for PumpThread {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            eprintln!("Audio render thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_stream_errors_lose_the_device() {
+        let status = StreamStatus::default();
+        status.add_stream_error();
+        status.add_stream_error();
+        // A buffer in between shows the stream still works
+        status.record_callback(Duration::from_millis(10));
+        status.add_stream_error();
+        status.add_stream_error();
+        assert!(!status.is_device_lost());
+        status.add_stream_error();
+        assert!(status.is_device_lost());
+    }
+
+    #[test]
+    fn stream_without_callbacks_stalls() {
+        let status = StreamStatus::default();
+        let opened = status.opened;
+        assert!(!status.is_stalled_at(opened + MIN_STALL));
+        assert!(status.is_stalled_at(opened + MIN_STALL * 2));
+
+        // Large buffers get eight periods
+        status.record_callback(Duration::from_millis(100));
+        let last =
+            opened + Duration::from_micros(status.last_callback_micros.load(Ordering::Relaxed));
+        assert!(!status.is_stalled_at(last + Duration::from_millis(790)));
+        assert!(status.is_stalled_at(last + Duration::from_millis(810)));
+    }
+}
@@ -0,0 +1,228 @@
+//! Output through cpal to the sound hardware
+//!
+//! Streams use the device's default configuration, except for the buffer
+//! size when one is configured. The timestamps cpal passes to every callback
+//! give the output latency and reveal underruns and late callbacks.
+
+use super::backend::{
+    ActiveStream, BackendError, OutputBackend, RenderFn, StreamFormat, StreamStatus,
+};
+use crate::host::{self, HostSettings};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Plays through a device of a cpal audio host
+pub struct CpalBackend {
+    /// Audio host the devices are looked up in
+    host: cpal::Host,
+}
+
+impl CpalBackend {
+    /// Opens the configured audio host
+    ///
+    /// # Arguments
+    /// * `settings` - Which host to open and how to set it up
+    pub fn new(settings: &HostSettings) -> Self {
+        CpalBackend {
+            host: host::open(settings),
+        }
+    }
+}
+
+impl OutputBackend for CpalBackend {
+    fn name(&self) -> &str {
+        self.host.id().name()
+    }
+
+    fn devices(&self) -> Vec<String> {
+        output_device_names(&self.host)
+    }
+
+    fn open(
+        &self,
+        device: Option<&str>,
+        buffer_frames: Option<u32>,
+        _source_format: Option<StreamFormat>,
+        status: Arc<StreamStatus>,
+        make_render: &mut dyn FnMut(StreamFormat) -> RenderFn,
+    ) -> Result<ActiveStream, BackendError> {
+        let device = find_device(&self.host, device).ok_or(BackendError::NoDevice)?;
+        let supported = device
+            .default_output_config()
+            .map_err(|e| BackendError::Config(e.to_string()))?;
+        if supported.sample_format() != cpal::SampleFormat::F32 {
+            return Err(BackendError::UnsupportedFormat(format!(
+                "{:?}",
+                supported.sample_format()
+            )));
+        }
+        let format = StreamFormat {
+            channels: supported.channels().max(1) as usize,
+            sample_rate: supported.sample_rate().0,
+        };
+
+        let mut config = supported.config();
+        let mut buffer_frames = buffer_frames.map(|frames| clamp_buffer(frames, &supported));
+        if let Some(frames) = buffer_frames {
+            config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+        let stream = match build_stream(&device, &config, format, &status, make_render(format)) {
+            Err(e) if buffer_frames.is_some() => {
+                eprintln!(
+                    "Output buffer size not accepted, using the device default: {}",
+                    e
+                );
+                config.buffer_size = cpal::BufferSize::Default;
+                buffer_frames = None;
+                build_stream(&device, &config, format, &status, make_render(format))
+            }
+            stream => stream,
+        }
+        .map_err(|e| BackendError::Stream(e.to_string()))?;
+        stream
+            .play()
+            .map_err(|e| BackendError::Stream(e.to_string()))?;
+
+        let name = device.name().unwrap_or_default();
+        Ok(ActiveStream::new(name, format, buffer_frames, stream))
+    }
+}
+
+/// Limits a buffer size to the range the device supports
+///
+/// # Arguments
+/// * `frames` - Requested frames per buffer
+/// * `supported` - Configuration the stream is opened with
+fn clamp_buffer(frames: u32, supported: &cpal::SupportedStreamConfig) -> u32 {
+    match *supported.buffer_size() {
+        cpal::SupportedBufferSize::Range { min, max } => {
+            let clamped = frames.clamp(min, max);
+            if clamped != frames {
+                eprintln!(
+                    "Output buffer of {} frames not supported, using {} (supported: {} to {})",
+                    frames, clamped, min, max
+                );
+            }
+            clamped
+        }
+        // The device does not say; let it accept or reject the size
+        cpal::SupportedBufferSize::Unknown => frames.max(1),
+    }
+}
+
+/// Builds a stream that measures its timing and calls `render` for each buffer
+fn build_stream(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    format: StreamFormat,
+    status: &Arc<StreamStatus>,
+    mut render: RenderFn,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let mut timer = CallbackTimer::default();
+    let callback_status = status.clone();
+    let error_status = status.clone();
+    device.build_output_stream(
+        config,
+        move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+            let frames = data.len() / format.channels;
+            timer.record(
+                &callback_status,
+                info.timestamp(),
+                frames,
+                format.sample_rate,
+            );
+            render(data);
+        },
+        move |err| match err {
+            cpal::StreamError::DeviceNotAvailable => error_status.set_device_lost(),
+            err => {
+                eprintln!("an error occurred on stream: {}", err);
+                error_status.add_stream_error();
+            }
+        },
+        None,
+    )
+}
+
+/// Measures latency, underruns and late callbacks from stream timestamps
+#[derive(Default)]
+struct CallbackTimer {
+    /// When the previous callback ran
+    previous_callback: Option<cpal::StreamInstant>,
+    /// When the device finishes playing the previous buffer
+    previous_end: Option<cpal::StreamInstant>,
+}
+
+impl CallbackTimer {
+    /// Records the timing of a callback; runs in the audio callback
+    ///
+    /// # Arguments
+    /// * `status` - Receives the measurements
+    /// * `timestamp` - When the callback runs and its buffer will be heard
+    /// * `frames` - Frames in the buffer
+    /// * `sample_rate` - Frames per second
+    fn record(
+        &mut self,
+        status: &StreamStatus,
+        timestamp: cpal::OutputStreamTimestamp,
+        frames: usize,
+        sample_rate: u32,
+    ) {
+        let buffer_duration = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+        status.record_callback(buffer_duration);
+        if let Some(latency) = timestamp.playback.duration_since(&timestamp.callback) {
+            status.set_latency(latency);
+        }
+        // The device ran dry if it finished the previous buffer before this one
+        // was even asked for
+        let ran_dry = self
+            .previous_end
+            .and_then(|end| timestamp.callback.duration_since(&end))
+            .is_some_and(|gap| !gap.is_zero());
+        if ran_dry {
+            status.add_underrun();
+        } else if self
+            .previous_callback
+            .and_then(|previous| timestamp.callback.duration_since(&previous))
+            .is_some_and(|interval| interval > buffer_duration * 3 / 2)
+        {
+            status.add_late_callback();
+        }
+        self.previous_callback = Some(timestamp.callback);
+        self.previous_end = timestamp.playback.add(buffer_duration);
+    }
+}
+
+/// Returns the names of all output devices of a host
+fn output_device_names(host: &cpal::Host) -> Vec<String> {
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(e) => {
+            eprintln!("Failed to list audio output devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Finds an output device by name, falling back to the default device
+///
+/// # Arguments
+/// * `host` - Audio host to search
+/// * `name` - Name of the wanted device; `None` picks the default device
+fn find_device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    if let Some(name) = name {
+        let found = host
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|device| device.name().is_ok_and(|n| n == name)));
+        if found.is_some() {
+            return found;
+        }
+        eprintln!(
+            "Output device \"{}\" not found, using the default device",
+            name
+        );
+    }
+    host.default_output_device()
+}
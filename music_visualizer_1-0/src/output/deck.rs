@@ -0,0 +1,689 @@
+//! Tracks played by the output and their playheads
+//!
+//! The `Deck` holds every track the mixer may read from. It is shared
+//! between the main thread, which changes and queues tracks, and the
+//! audio thread, which advances through them sample by sample.
+//!
+//! Tracks the deck lets go of are never dropped while it is locked: they go
+//! back to the main thread through a channel, since dropping a track may
+//! free a whole song, which the audio thread must not wait for.
+//!
+//! Every track has a `TrackId`. The deck publishes the id of the track it
+//! plays in an atomic, so the main thread can tell which song is heard
+//! without locking the deck.
+
+use super::StreamFormat;
+use crate::fade::Crossfade;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::SyncSender;
+
+/// Marks that no seek is waiting for the fade-out
+const NO_SEEK: usize = usize::MAX;
+/// Published while the deck plays no track; never the id of a track
+const NO_TRACK: u64 = 0;
+
+/// Id of the next track created
+static NEXT_TRACK_ID: AtomicU64 = AtomicU64::new(NO_TRACK + 1);
+
+/// Identifies one track, unlike any other created in this run
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TrackId(u64);
+
+/// Playback position in a track, shared between its song and the callback
+pub struct Playhead {
+    /// Index of the next interleaved sample to output
+    position: AtomicUsize,
+    /// Sample index to jump to once the output is silent, or `NO_SEEK`
+    pending_seek: AtomicUsize,
+}
+
+impl Playhead {
+    /// Creates a playhead at the start of a track
+    pub fn new() -> Self {
+        Playhead {
+            position: AtomicUsize::new(0),
+            pending_seek: AtomicUsize::new(NO_SEEK),
+        }
+    }
+
+    /// Returns the position, counting a seek that still waits as done
+    ///
+    /// # Returns
+    /// Index of the next interleaved sample the audio callback will output
+    pub fn position(&self) -> usize {
+        match self.pending_seek.load(Ordering::Relaxed) {
+            NO_SEEK => self.position.load(Ordering::Relaxed),
+            sample => sample,
+        }
+    }
+
+    /// Asks the callback to move the playhead once the output is silent
+    ///
+    /// A later seek replaces one that is still waiting.
+    ///
+    /// # Arguments
+    /// * `sample` - Index of the interleaved sample to continue from
+    pub fn seek(&self, sample: usize) {
+        self.pending_seek.store(sample, Ordering::Relaxed);
+    }
+
+    /// Returns whether a seek waits for the output to fade out
+    fn has_pending_seek(&self) -> bool {
+        self.pending_seek.load(Ordering::Relaxed) != NO_SEEK
+    }
+
+    /// Applies a waiting seek, if any
+    fn apply_seek(&self) {
+        let sample = self.pending_seek.swap(NO_SEEK, Ordering::Relaxed);
+        if sample != NO_SEEK {
+            self.position.store(sample, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for Playhead {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gain a track is played at, shared between its song and the callback
+///
+/// Lets the main thread change the level of a track that is already queued
+/// or playing, e.g. once its loudness has been measured.
+pub struct TrackGain {
+    /// Linear gain as `f32` bits
+    gain: AtomicU32,
+}
+
+impl TrackGain {
+    /// Creates a gain that leaves the track unchanged
+    pub fn new() -> Self {
+        TrackGain {
+            gain: AtomicU32::new(1.0f32.to_bits()),
+        }
+    }
+
+    /// Sets the gain in decibels
+    pub fn set_db(&self, db: f32) {
+        let gain = 10f32.powf(db / 20.0);
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the linear gain
+    fn get(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for TrackGain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle to a song's audio for the output
+///
+/// The track is converted to the stream's format as it plays:
+/// - The sample rate by linear interpolation between neighbouring frames
+/// - The channels by `mix_channels`
+pub struct Track {
+    /// Tells the track apart from every other
+    id: TrackId,
+    /// Interleaved samples of the whole song
+    samples: Arc<Vec<f32>>,
+    /// Position shared with the song
+    playhead: Arc<Playhead>,
+    /// Level shared with the song
+    gain: Arc<TrackGain>,
+    /// Number of interleaved channels in `samples`
+    channels: usize,
+    /// Frames per second of `samples`
+    sample_rate: u32,
+    /// How far the output is between the frame under the playhead and the
+    /// next one, in units of one output sample rate (counted exactly, so
+    /// long tracks do not drift); only used by the audio thread
+    phase: u64,
+}
+
+impl Track {
+    /// Creates a track starting where its playhead is
+    ///
+    /// # Arguments
+    /// * `samples` - Interleaved samples of the whole song
+    /// * `playhead` - Position shared with the song
+    /// * `gain` - Level shared with the song
+    /// * `channels` - Number of interleaved channels in `samples`
+    /// * `sample_rate` - Frames per second of `samples`
+    pub fn new(
+        samples: Arc<Vec<f32>>,
+        playhead: Arc<Playhead>,
+        gain: Arc<TrackGain>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> Self {
+        Track {
+            id: TrackId(NEXT_TRACK_ID.fetch_add(1, Ordering::Relaxed)),
+            samples,
+            playhead,
+            gain,
+            channels: channels.max(1) as usize,
+            sample_rate: sample_rate.max(1),
+            phase: 0,
+        }
+    }
+
+    /// Returns the id of the track
+    pub fn id(&self) -> TrackId {
+        self.id
+    }
+
+    /// Returns the channels and sample rate of the track
+    pub fn format(&self) -> StreamFormat {
+        StreamFormat {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Returns how many samples the callback has yet to output
+    fn remaining(&self) -> usize {
+        let position = self.playhead.position.load(Ordering::Relaxed);
+        self.samples.len().saturating_sub(position)
+    }
+
+    /// Returns how long the track plays until its end, in seconds
+    fn remaining_seconds(&self) -> f32 {
+        let frames = self.remaining() / self.channels;
+        frames as f32 / self.sample_rate as f32
+    }
+
+    /// Returns whether the callback has output every sample
+    fn is_at_end(&self) -> bool {
+        self.remaining() < self.channels
+    }
+
+    /// Applies a waiting seek, starting exactly on the frame sought to
+    fn apply_seek(&mut self) {
+        if self.playhead.has_pending_seek() {
+            self.playhead.apply_seek();
+            self.phase = 0;
+        }
+    }
+
+    /// Adds the next output frame, at the track's gain, and advances the
+    /// playhead past the source frames it used
+    ///
+    /// # Arguments
+    /// * `output` - Output frame to add to
+    /// * `sample_rate` - Frames per second of the output
+    /// * `gain` - Gain on top of the track's own, e.g. of a crossfade
+    ///
+    /// # Returns
+    /// Whether the track had a frame left to add
+    fn mix_next_frame(&mut self, output: &mut [f32], sample_rate: u32, gain: f32) -> bool {
+        let position = self.playhead.position.load(Ordering::Relaxed);
+        let frame = position / self.channels;
+        let start = frame * self.channels;
+        let Some(current) = self.samples.get(start..start + self.channels) else {
+            return false;
+        };
+        // Past the last frame the last one holds, so the track ends without a step
+        let next = self
+            .samples
+            .get(start + self.channels..start + 2 * self.channels)
+            .unwrap_or(current);
+        let output_rate = sample_rate.max(1) as u64;
+        // Only a stream reopened at a lower rate can leave the phase too large
+        self.phase = self.phase.min(output_rate - 1);
+        let fraction = self.phase as f32 / output_rate as f32;
+        let source =
+            |channel: usize| current[channel] + (next[channel] - current[channel]) * fraction;
+        mix_channels(source, self.channels, output, gain * self.gain.get());
+
+        self.phase += self.sample_rate as u64;
+        let frames_passed = (self.phase / output_rate) as usize;
+        self.phase %= output_rate;
+        self.playhead
+            .position
+            .store(start + frames_passed * self.channels, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Adds a source frame to an output frame, mapping between their channels
+///
+/// - The same count: every channel goes to its own
+/// - Mono: the channel goes to every output channel
+/// - To mono: the channels are averaged
+/// - 5.1 (L, R, C, LFE, Ls, Rs) to stereo: centre and surrounds are folded
+///   into the sides at -3 dB, without the LFE
+/// - Otherwise the channels both have go through, and extra output channels
+///   stay silent
+///
+/// # Arguments
+/// * `source` - Returns a channel of the source frame
+/// * `source_channels` - Number of channels `source` has
+/// * `output` - Output frame to add to
+/// * `gain` - Gain applied to the source
+fn mix_channels(
+    source: impl Fn(usize) -> f32,
+    source_channels: usize,
+    output: &mut [f32],
+    gain: f32,
+) {
+    const MINUS_3_DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    match (source_channels, output.len()) {
+        (1, _) => {
+            let sample = source(0) * gain;
+            output.iter_mut().for_each(|out| *out += sample);
+        }
+        (channels, 1) => {
+            let sum: f32 = (0..channels).map(&source).sum();
+            output[0] += sum / channels as f32 * gain;
+        }
+        (6, 2) => {
+            let centre = source(2) * MINUS_3_DB;
+            output[0] += (source(0) + centre + source(4) * MINUS_3_DB) * gain;
+            output[1] += (source(1) + centre + source(5) * MINUS_3_DB) * gain;
+        }
+        (channels, _) => {
+            for (channel, out) in output.iter_mut().enumerate().take(channels) {
+                *out += source(channel) * gain;
+            }
+        }
+    }
+}
+
+/// Track playing out underneath the next one during a crossfade
+struct Outgoing {
+    /// The track that was current before the crossfade
+    track: Track,
+    /// Samples it had left when the crossfade started
+    length: usize,
+}
+
+impl Outgoing {
+    /// Returns how far the crossfade has come, from 0.0 to 1.0
+    fn progress(&self) -> f32 {
+        1.0 - self.track.remaining() as f32 / self.length.max(1) as f32
+    }
+}
+
+/// Id of the track the deck plays, shared with the main thread
+pub struct PlayingTrack {
+    /// Id of the current track, or `NO_TRACK`
+    id: AtomicU64,
+}
+
+impl PlayingTrack {
+    /// Returns the id of the track the deck plays, if any
+    pub fn get(&self) -> Option<TrackId> {
+        match self.id.load(Ordering::Relaxed) {
+            NO_TRACK => None,
+            id => Some(TrackId(id)),
+        }
+    }
+}
+
+/// Tracks the audio callback reads from
+pub struct Deck {
+    /// Track being played
+    current: Option<Track>,
+    /// Track following the current one
+    next: Option<Track>,
+    /// Whether `next` crossfades with the current track instead of following
+    /// it without a gap
+    crossfade_next: bool,
+    /// Track to change to once the output has faded out
+    switch: Option<Track>,
+    /// Previous track, fading out during a crossfade
+    outgoing: Option<Outgoing>,
+    /// Takes the tracks the deck lets go of to the main thread
+    retired: SyncSender<Track>,
+    /// Publishes the id of `current`
+    playing: Arc<PlayingTrack>,
+}
+
+impl Deck {
+    /// Creates an empty deck
+    ///
+    /// # Arguments
+    /// * `retired` - Receives the tracks the deck lets go of, to be dropped
+    ///   on the main thread
+    pub fn new(retired: SyncSender<Track>) -> Self {
+        Deck {
+            current: None,
+            next: None,
+            crossfade_next: false,
+            switch: None,
+            outgoing: None,
+            retired,
+            playing: Arc::new(PlayingTrack {
+                id: AtomicU64::new(NO_TRACK),
+            }),
+        }
+    }
+
+    /// Changes to another track once the output has faded out
+    ///
+    /// Any track queued with `queue_next` is dropped.
+    pub fn change_track(&mut self, track: Track) {
+        let replaced = self.switch.replace(track);
+        self.retire(replaced);
+        let dropped = self.next.take();
+        self.retire(dropped);
+    }
+
+    /// Queues a track to follow the current one
+    ///
+    /// # Arguments
+    /// * `track` - The track to play next
+    /// * `crossfade` - Whether to crossfade into it instead of following
+    ///   without a gap
+    pub fn queue_next(&mut self, track: Track, crossfade: bool) {
+        let replaced = self.next.replace(track);
+        self.retire(replaced);
+        self.crossfade_next = crossfade;
+    }
+
+    /// Drops the track queued with `queue_next`
+    ///
+    /// # Returns
+    /// Whether a track was queued; false once it took over
+    pub fn clear_next(&mut self) -> bool {
+        let dropped = self.next.take();
+        let was_queued = dropped.is_some();
+        self.retire(dropped);
+        was_queued
+    }
+
+    /// Returns whether a seek or track change waits for the output to fade out
+    pub fn must_fade_out(&self) -> bool {
+        self.switch.is_some()
+            || self
+                .current
+                .as_ref()
+                .is_some_and(|track| track.playhead.has_pending_seek())
+    }
+
+    /// Performs the changes that wait for silence
+    ///
+    /// A crossfade in progress is cut short, since its outgoing track no
+    /// longer leads into what plays next.
+    pub fn apply_switch_and_seek(&mut self) {
+        if self.must_fade_out() {
+            let outgoing = self.outgoing.take();
+            self.retire(outgoing.map(|outgoing| outgoing.track));
+        }
+        if let Some(track) = self.switch.take() {
+            let replaced = self.replace_current(track);
+            self.retire(replaced);
+        }
+        if let Some(track) = &mut self.current {
+            track.apply_seek();
+        }
+    }
+
+    /// Lets the next track take over when it is due
+    ///
+    /// # Arguments
+    /// * `crossfade_seconds` - Time the tracks overlap when crossfading; 0.0
+    ///   lets even a track queued to crossfade follow without a gap
+    ///
+    /// # Returns
+    /// Whether the next track became the current one
+    pub fn advance(&mut self, crossfade_seconds: f32) -> bool {
+        let Some(current) = &self.current else {
+            return false;
+        };
+        let remaining = current.remaining();
+        let crossfading = self.crossfade_next && crossfade_seconds > 0.0;
+        let is_due = if crossfading {
+            // Wait for the previous crossfade to finish before starting another
+            current.remaining_seconds() <= crossfade_seconds && self.outgoing.is_none()
+        } else {
+            current.is_at_end()
+        };
+        if !is_due {
+            return false;
+        }
+        let Some(next) = self.next.take() else {
+            return false;
+        };
+
+        let previous = self.replace_current(next);
+        if crossfading && let Some(track) = previous {
+            self.outgoing = Some(Outgoing {
+                track,
+                length: remaining,
+            });
+        } else {
+            self.retire(previous);
+        }
+        true
+    }
+
+    /// Mixes the next output frame, advancing the playheads
+    ///
+    /// # Arguments
+    /// * `output` - Output frame to fill
+    /// * `sample_rate` - Frames per second of the output
+    /// * `crossfade` - Curve used while a track is crossfading out
+    ///
+    /// # Returns
+    /// Whether any track played in the frame; false once every track ended
+    pub fn next_frame(
+        &mut self,
+        output: &mut [f32],
+        sample_rate: u32,
+        crossfade: &Crossfade,
+    ) -> bool {
+        output.fill(0.0);
+        let Some(outgoing) = &mut self.outgoing else {
+            return self
+                .current
+                .as_mut()
+                .is_some_and(|track| track.mix_next_frame(output, sample_rate, 1.0));
+        };
+
+        let (outgoing_gain, incoming_gain) = crossfade.curve.gains(outgoing.progress());
+        let mut played = outgoing
+            .track
+            .mix_next_frame(output, sample_rate, outgoing_gain);
+        if outgoing.track.is_at_end() {
+            let outgoing = self.outgoing.take();
+            self.retire(outgoing.map(|outgoing| outgoing.track));
+        }
+        if let Some(track) = &mut self.current {
+            played |= track.mix_next_frame(output, sample_rate, incoming_gain);
+        }
+        played
+    }
+
+    /// Returns where the deck publishes the id of the track it plays
+    pub fn playing(&self) -> Arc<PlayingTrack> {
+        self.playing.clone()
+    }
+
+    /// Makes a track the current one, publishing its id
+    ///
+    /// # Returns
+    /// The track it replaced
+    fn replace_current(&mut self, track: Track) -> Option<Track> {
+        self.playing.id.store(track.id.0, Ordering::Relaxed);
+        self.current.replace(track)
+    }
+
+    /// Hands a track the deck let go of to the main thread
+    ///
+    /// Only if the main thread has fallen so far behind that the channel is
+    /// full is the track dropped where it is.
+    fn retire(&self, track: Option<Track>) {
+        if let Some(track) = track {
+            let _ = self.retired.try_send(track);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fade::CrossfadeCurve;
+    use std::sync::mpsc;
+
+    const NO_CROSSFADE: Crossfade = Crossfade {
+        seconds: 0.0,
+        curve: CrossfadeCurve::EqualPower,
+    };
+
+    /// Returns a track of the given samples at full gain
+    fn track(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Track {
+        Track::new(
+            Arc::new(samples),
+            Arc::new(Playhead::new()),
+            Arc::new(TrackGain::new()),
+            channels,
+            sample_rate,
+        )
+    }
+
+    /// Plays a track on its own into `frames` output frames
+    fn play(mut track: Track, channels: usize, sample_rate: u32, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames * channels];
+        for frame in output.chunks_mut(channels) {
+            track.mix_next_frame(frame, sample_rate, 1.0);
+        }
+        output
+    }
+
+    #[test]
+    fn matching_format_plays_unchanged() {
+        let samples: Vec<f32> = (0..20).map(|n| n as f32 / 20.0).collect();
+        let output = play(track(samples.clone(), 2, 48_000), 2, 48_000, 12);
+        assert_eq!(output[..20], samples[..]);
+        assert!(output[20..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn mono_goes_to_every_channel() {
+        let output = play(track(vec![0.5, -0.25], 1, 48_000), 2, 48_000, 2);
+        assert_eq!(output, [0.5, 0.5, -0.25, -0.25]);
+    }
+
+    #[test]
+    fn other_rates_are_resampled() {
+        // Half the output rate: every other frame lies between two source frames
+        let ramp: Vec<f32> = (0..10).map(|n| n as f32).collect();
+        let output = play(track(ramp, 1, 24_000), 1, 48_000, 6);
+        assert_eq!(output, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+
+        // One second at 44.1 kHz lasts one second at 48 kHz
+        let second = track(vec![0.1; 44_100], 1, 44_100);
+        let playhead = second.playhead.clone();
+        let output = play(second, 1, 48_000, 48_001);
+        assert!(output[..48_000].iter().all(|&s| s == 0.1));
+        assert_eq!(output[48_000], 0.0);
+        assert_eq!(playhead.position(), 44_100);
+    }
+
+    #[test]
+    fn surround_folds_down_to_stereo() {
+        let frame = vec![1.0, 0.5, 0.5, 1.0, 0.25, 0.0];
+        let output = play(track(frame, 6, 48_000), 2, 48_000, 1);
+        let minus_3_db = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((output[0] - (1.0 + 0.75 * minus_3_db)).abs() < 1e-6);
+        assert!((output[1] - (0.5 + 0.5 * minus_3_db)).abs() < 1e-6);
+
+        let mono = play(track(vec![1.0, 0.5], 2, 48_000), 1, 48_000, 1);
+        assert_eq!(mono, [0.75]);
+    }
+
+    #[test]
+    fn seeking_restarts_on_a_whole_frame() {
+        let (retire, _retired) = mpsc::sync_channel(4);
+        let mut deck = Deck::new(retire);
+        let ramp: Vec<f32> = (0..100).map(|n| n as f32).collect();
+        let track = track(ramp, 1, 32_000);
+        let playhead = track.playhead.clone();
+        deck.change_track(track);
+        deck.apply_switch_and_seek();
+
+        let mut frame = [0.0];
+        deck.next_frame(&mut frame, 48_000, &NO_CROSSFADE);
+        deck.next_frame(&mut frame, 48_000, &NO_CROSSFADE);
+        assert!(frame[0].fract() != 0.0);
+        playhead.seek(50);
+        deck.apply_switch_and_seek();
+        deck.next_frame(&mut frame, 48_000, &NO_CROSSFADE);
+        assert_eq!(frame[0], 50.0);
+    }
+
+    /// Plays `first` with `second` queued after it, advancing as the mixer
+    /// does, and returns the mono output
+    fn play_queued(
+        first: Vec<f32>,
+        second: Vec<f32>,
+        queue_crossfade: bool,
+        crossfade: Crossfade,
+    ) -> Vec<f32> {
+        let (retire, _retired) = mpsc::sync_channel(4);
+        let mut deck = Deck::new(retire);
+        let length = first.len() + second.len();
+        deck.change_track(track(first, 1, 8_000));
+        deck.apply_switch_and_seek();
+        deck.queue_next(track(second, 1, 8_000), queue_crossfade);
+
+        let mut output = vec![0.0; length];
+        let mut advances = 0;
+        for frame in output.chunks_mut(1) {
+            if deck.advance(crossfade.seconds) {
+                advances += 1;
+            }
+            deck.next_frame(frame, 8_000, &crossfade);
+        }
+        assert_eq!(advances, 1);
+        output
+    }
+
+    #[test]
+    fn tracks_not_crossfaded_follow_without_a_gap() {
+        let crossfade = Crossfade {
+            seconds: 1.0,
+            curve: CrossfadeCurve::Linear,
+        };
+        let expected = [1.0, 2.0, 3.0, 10.0, 11.0, 12.0];
+        // e.g. songs of one album, while crossfades are on
+        let output = play_queued(
+            vec![1.0, 2.0, 3.0],
+            vec![10.0, 11.0, 12.0],
+            false,
+            crossfade,
+        );
+        assert_eq!(output, expected);
+        // Queued to crossfade, but crossfades were turned off since
+        let output = play_queued(
+            vec![1.0, 2.0, 3.0],
+            vec![10.0, 11.0, 12.0],
+            true,
+            NO_CROSSFADE,
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn crossfaded_tracks_overlap() {
+        // Just over four frames, so the overlap does not hang on rounding
+        let crossfade = Crossfade {
+            seconds: 4.5 / 8_000.0,
+            curve: CrossfadeCurve::Linear,
+        };
+        let output = play_queued(vec![1.0; 8], vec![1.0; 8], true, crossfade);
+        // Linear gains add up to one, so the overlap keeps the level
+        for sample in &output[..12] {
+            assert!((sample - 1.0).abs() < 1e-6, "{:?}", output);
+        }
+        assert_eq!(output[12..], [0.0; 4]);
+    }
+}
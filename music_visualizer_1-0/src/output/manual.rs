@@ -0,0 +1,154 @@
+//! Output rendered only when asked, for tests
+//!
+//! The backend keeps the render function of the stream it opened instead of
+//! running it on a thread. `ManualStream::render` calls it on the calling
+//! thread, so a test steps the output buffer by buffer and sees exactly what
+//! a device would have played.
+
+use super::backend::{
+    ActiveStream, BackendError, OutputBackend, RenderFn, StreamFormat, StreamStatus,
+};
+use super::{AudioOutput, OutputSettings, OutputTarget};
+use crate::dsp::{EffectChain, EqControl, EqSettings, LimiterControl, LimiterSettings};
+use crate::fade::{Crossfade, CrossfadeControl, FadeDurations};
+use crate::host::HostSettings;
+use crate::volume::VolumeControl;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Name of the only device of the manual backend
+const DEVICE_NAME: &str = "manual";
+
+/// Opens streams that render only when asked
+pub struct ManualBackend {
+    /// Channels and sample rate of every stream
+    format: StreamFormat,
+    /// Render function of the open stream, shared with the handles
+    render: Arc<Mutex<Option<RenderFn>>>,
+}
+
+impl ManualBackend {
+    /// Creates a backend whose streams always use one format
+    ///
+    /// # Arguments
+    /// * `format` - Channels and sample rate of every stream
+    pub fn new(format: StreamFormat) -> Self {
+        ManualBackend {
+            format,
+            render: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a handle that renders the stream the backend opens
+    pub fn stream(&self) -> ManualStream {
+        ManualStream {
+            format: self.format,
+            render: self.render.clone(),
+        }
+    }
+}
+
+impl OutputBackend for ManualBackend {
+    fn name(&self) -> &str {
+        DEVICE_NAME
+    }
+
+    fn devices(&self) -> Vec<String> {
+        vec![DEVICE_NAME.to_string()]
+    }
+
+    fn open(
+        &self,
+        _device: Option<&str>,
+        buffer_frames: Option<u32>,
+        _source_format: Option<StreamFormat>,
+        _status: Arc<StreamStatus>,
+        make_render: &mut dyn FnMut(StreamFormat) -> RenderFn,
+    ) -> Result<ActiveStream, BackendError> {
+        *self.render.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(make_render(self.format));
+        Ok(ActiveStream::new(
+            DEVICE_NAME.to_string(),
+            self.format,
+            buffer_frames,
+            (),
+        ))
+    }
+}
+
+/// Handle rendering the stream of a `ManualBackend`
+pub struct ManualStream {
+    /// Channels and sample rate of the stream
+    format: StreamFormat,
+    /// Render function of the open stream
+    render: Arc<Mutex<Option<RenderFn>>>,
+}
+
+impl ManualStream {
+    /// Renders the next frames of the stream
+    ///
+    /// # Arguments
+    /// * `frames` - Number of frames to render
+    ///
+    /// # Returns
+    /// The interleaved samples, and whether they hold audio; silence if no
+    /// stream was opened yet
+    pub fn render(&self, frames: usize) -> (Vec<f32>, bool) {
+        let mut buffer = vec![0.0; frames * self.format.channels];
+        let has_audio = match self
+            .render
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            Some(render) => render(&mut buffer),
+            None => false,
+        };
+        (buffer, has_audio)
+    }
+}
+
+/// Creates an output on a manual backend, with the limiter off and unity
+/// gain everywhere
+///
+/// # Arguments
+/// * `format` - Channels and sample rate of the stream
+/// * `crossfade` - Crossfade between consecutive tracks
+/// * `effects` - Effects the output runs the audio through
+///
+/// # Returns
+/// The output, and the handle that renders its stream
+pub fn manual_output(
+    format: StreamFormat,
+    crossfade: Crossfade,
+    effects: &EffectChain,
+) -> (AudioOutput, ManualStream) {
+    let backend = ManualBackend::new(format);
+    let stream = backend.stream();
+    let limiter = LimiterSettings {
+        enabled: false,
+        ..LimiterSettings::default()
+    };
+    let output = AudioOutput::with_backend(
+        Box::new(backend),
+        Arc::new(VolumeControl::new(0.0, false, 0.0)),
+        Arc::new(EqControl::new(EqSettings::default())),
+        effects.rack(),
+        Arc::new(LimiterControl::new(limiter)),
+        Arc::new(CrossfadeControl::new(crossfade)),
+        OutputSettings {
+            target: OutputTarget::Device,
+            host: HostSettings {
+                name: None,
+                jack_connect_ports: false,
+                jack_start_server: false,
+            },
+            device: None,
+            buffer_frames: None,
+            fade_durations: FadeDurations {
+                fade_in_ms: 5.0,
+                fade_out_ms: 5.0,
+            },
+        },
+    );
+    (output, stream)
+}
@@ -0,0 +1,72 @@
+//! Mixing the deck into output buffers
+//!
+//! The `Mixer` runs in the audio thread of whichever backend plays the
+//! output. It applies the fade envelope and lets queued tracks take over.
+
+use super::StreamFormat;
+use super::deck::Deck;
+use crate::fade::{CrossfadeControl, Envelope, FadeControl, FadeDurations};
+use std::sync::Arc;
+
+/// State of the audio callback besides the deck
+pub struct Mixer {
+    /// Channels and sample rate of the stream
+    format: StreamFormat,
+    /// Fade applied around pause, resume, seek and track changes
+    envelope: Envelope,
+    /// Fade requests from the main thread
+    fade: Arc<FadeControl>,
+    /// Overlap and curve used when crossfading between tracks
+    crossfade: Arc<CrossfadeControl>,
+}
+
+impl Mixer {
+    /// Creates a mixer for a stream, starting silent
+    ///
+    /// # Arguments
+    /// * `format` - Channels and sample rate of the stream
+    /// * `fade_durations` - Lengths of the fades
+    /// * `fade` - Fade requests from the main thread
+    /// * `crossfade` - Overlap and curve used when crossfading
+    pub fn new(
+        format: StreamFormat,
+        fade_durations: FadeDurations,
+        fade: Arc<FadeControl>,
+        crossfade: Arc<CrossfadeControl>,
+    ) -> Self {
+        Mixer {
+            format,
+            envelope: Envelope::new(fade_durations, format.sample_rate),
+            fade,
+            crossfade,
+        }
+    }
+
+    /// Fills an output buffer from the deck; runs in the audio callback
+    ///
+    /// Silent frames hold the playhead, so pausing resumes exactly where the
+    /// fade-out ended. When the next track is due it takes over immediately,
+    /// so its first frame follows the current track's last one.
+    ///
+    /// # Returns
+    /// Whether any track was heard in the buffer; false while paused,
+    /// stopped, or after the last track ended
+    pub fn fill_buffer(&mut self, deck: &mut Deck, data: &mut [f32]) -> bool {
+        // Settings changed in the menu take effect from the next buffer
+        let crossfade = self.crossfade.crossfade();
+        let mut played = false;
+        for output_frame in data.chunks_mut(self.format.channels) {
+            let gain = self.envelope.next(&self.fade, deck.must_fade_out());
+            if gain == 0.0 {
+                deck.apply_switch_and_seek();
+                output_frame.fill(0.0);
+                continue;
+            }
+
+            deck.advance(crossfade.seconds);
+            played |= deck.next_frame(output_frame, self.format.sample_rate, &crossfade);
+            output_frame.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        played
+    }
+}
@@ -0,0 +1,679 @@
+//! Audio output
+//!
+//! A single output stream is opened the first time something plays and kept
+//! open for the rest of the session. Its callback reads from a `Deck` of
+//! tracks:
+//! - The current track, played from its `Playhead` at its `TrackGain` and
+//!   converted to the stream's sample rate and channels
+//! - The next track, which takes over at the exact sample the current one
+//!   ends, so consecutive tracks play without a gap; or, when crossfading,
+//!   starts while the current track is still playing out
+//! - The outgoing track of a crossfade, mixed in until it ends; the length
+//!   and curve of crossfades are read from a `CrossfadeControl` every buffer
+//! - A track the user switched to, which takes over once the output has
+//!   faded out
+//!
+//! The callback never waits for the main thread: while the main thread holds
+//! the deck it outputs silence, and tracks the deck lets go of are sent back
+//! to be dropped by `set_playing`.
+//!
+//! While paused the callback outputs silence and holds the playhead. The
+//! mixed audio then runs through the equalizer and the effect chain before
+//! the volume is applied; a limiter after the volume keeps the result from
+//! clipping.
+//!
+//! Streams are opened through an `OutputBackend`:
+//! - `CpalBackend` plays on the audio host and device chosen in the settings,
+//!   or on the host's default device when none is chosen or the chosen one is
+//!   missing
+//! - `NullBackend` throws the audio away, for runs without sound hardware
+//! - `WavBackend` records the audio to a file, one recording across every
+//!   stream it opens
+//!
+//! The null backend opens its stream in the format of the track that is
+//! about to play, so that track is not converted; later tracks in other
+//! formats are. The WAV backend does so for the first stream, which fixes
+//! the format of the file.
+//!
+//! When the device goes away while open, e.g. because headphones were
+//! unplugged, the stream is reopened on the next `update`; the deck outlives
+//! the stream, so playback resumes at the same position. A stream that keeps
+//! failing, or that stops asking for audio while something plays, is
+//! reopened the same way.
+
+mod backend;
+mod cpal_backend;
+mod deck;
+#[cfg(test)]
+mod manual;
+mod mixer;
+mod null;
+mod wav;
+
+pub use backend::{ActiveStream, OutputBackend, Pace, RenderFn, StreamFormat, StreamStatus};
+pub use cpal_backend::CpalBackend;
+pub use deck::{Playhead, Track, TrackGain, TrackId};
+#[cfg(test)]
+pub use manual::{ManualStream, manual_output};
+pub use null::NullBackend;
+pub use wav::WavBackend;
+
+use crate::dsp::{EffectRack, EqControl, Equalizer, Limiter, LimiterControl};
+use crate::fade::{CrossfadeControl, FadeControl, FadeDurations};
+use crate::host::HostSettings;
+use crate::volume::{self, VolumeControl};
+use deck::{Deck, PlayingTrack};
+use mixer::Mixer;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Time between attempts to open a stream while no device works
+const REOPEN_INTERVAL: Duration = Duration::from_secs(1);
+/// Tracks the deck can let go of before the main thread drops them
+const RETIRED_CAPACITY: usize = 16;
+/// Level below which a buffer nothing played into counts as silent (-120 dBFS)
+const SILENCE_LEVEL: f32 = 1e-6;
+
+/// Where the audio output goes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OutputTarget {
+    /// A device of the configured audio host
+    #[default]
+    Device,
+    /// Nowhere; the audio is rendered and thrown away
+    Null(Pace),
+    /// A WAV file
+    WavFile(PathBuf, Pace),
+}
+
+/// How the audio output is set up
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputSettings {
+    /// Where the audio goes
+    pub target: OutputTarget,
+    /// Audio host the output device belongs to
+    pub host: HostSettings,
+    /// Name of the output device; `None` uses the host's default device
+    pub device: Option<String>,
+    /// Frames per output buffer; `None` uses the device's default
+    pub buffer_frames: Option<u32>,
+    /// Lengths of the fades around pause, seek and track changes
+    pub fade_durations: FadeDurations,
+}
+
+/// The application's audio output stream and the tracks it plays
+pub struct AudioOutput {
+    /// Opens the streams
+    backend: Box<dyn OutputBackend>,
+    /// Output stream, opened on first play and kept open
+    stream: Option<ActiveStream>,
+    /// Name of the device chosen by the user; `None` uses the default device
+    device: Option<String>,
+    /// Names of the output devices found by the last scan
+    devices: Vec<String>,
+    /// Frames per buffer asked of the backend; `None` uses the device's default
+    buffer_frames: Option<u32>,
+    /// Health of the open stream, replaced for every stream opened
+    status: Arc<StreamStatus>,
+    /// When opening a stream was last tried, to pace retries
+    last_open_attempt: Option<Instant>,
+    /// Tracks shared with the audio callback
+    deck: Arc<Mutex<Deck>>,
+    /// Format of the last track changed to, which backends that can choose
+    /// open their stream in
+    source_format: Option<StreamFormat>,
+    /// Tracks the deck let go of, dropped here instead of on the audio thread
+    retired: Receiver<Track>,
+    /// Id of the track the callback plays
+    playing: Arc<PlayingTrack>,
+    /// Fade requests for the audio callback
+    fade: Arc<FadeControl>,
+    /// Lengths of the fades
+    fade_durations: FadeDurations,
+    /// Overlap between songs advancing through the queue
+    crossfade: Arc<CrossfadeControl>,
+    /// Volume, mute and balance applied in the audio callback
+    volume: Arc<VolumeControl>,
+    /// Equalizer settings applied in the audio callback
+    eq: Arc<EqControl>,
+    /// Effects run in the audio callback
+    effects: Arc<Mutex<EffectRack>>,
+    /// Limiter settings applied in the audio callback
+    limiter: Arc<LimiterControl>,
+}
+
+impl AudioOutput {
+    /// Creates the output for the configured target without opening a stream yet
+    ///
+    /// # Arguments
+    /// * `volume` - Volume settings applied in the audio callback
+    /// * `eq` - Equalizer settings applied in the audio callback
+    /// * `effects` - Effects run in the audio callback
+    /// * `limiter` - Limiter settings applied in the audio callback
+    /// * `crossfade` - Crossfade settings applied in the audio callback
+    /// * `settings` - Target, host, device, buffer size and fades to use
+    pub fn new(
+        volume: Arc<VolumeControl>,
+        eq: Arc<EqControl>,
+        effects: Arc<Mutex<EffectRack>>,
+        limiter: Arc<LimiterControl>,
+        crossfade: Arc<CrossfadeControl>,
+        settings: OutputSettings,
+    ) -> Self {
+        let backend: Box<dyn OutputBackend> = match &settings.target {
+            OutputTarget::Device => Box::new(CpalBackend::new(&settings.host)),
+            OutputTarget::Null(pace) => Box::new(NullBackend::new(*pace)),
+            OutputTarget::WavFile(path, pace) => Box::new(WavBackend::new(path.clone(), *pace)),
+        };
+        Self::with_backend(backend, volume, eq, effects, limiter, crossfade, settings)
+    }
+
+    /// Creates the output on a given backend without opening a stream yet
+    ///
+    /// The target and host in `settings` are not used.
+    ///
+    /// # Arguments
+    /// * `backend` - Opens the streams
+    /// * `volume` - Volume settings applied in the audio callback
+    /// * `eq` - Equalizer settings applied in the audio callback
+    /// * `effects` - Effects run in the audio callback
+    /// * `limiter` - Limiter settings applied in the audio callback
+    /// * `crossfade` - Crossfade settings applied in the audio callback
+    /// * `settings` - Device, buffer size and fades to use
+    pub fn with_backend(
+        backend: Box<dyn OutputBackend>,
+        volume: Arc<VolumeControl>,
+        eq: Arc<EqControl>,
+        effects: Arc<Mutex<EffectRack>>,
+        limiter: Arc<LimiterControl>,
+        crossfade: Arc<CrossfadeControl>,
+        settings: OutputSettings,
+    ) -> Self {
+        let (retire, retired) = mpsc::sync_channel(RETIRED_CAPACITY);
+        let deck = Deck::new(retire);
+        AudioOutput {
+            devices: backend.devices(),
+            backend,
+            stream: None,
+            device: settings.device,
+            buffer_frames: settings.buffer_frames,
+            status: Arc::new(StreamStatus::default()),
+            last_open_attempt: None,
+            playing: deck.playing(),
+            deck: Arc::new(Mutex::new(deck)),
+            source_format: None,
+            retired,
+            fade: Arc::new(FadeControl::new()),
+            fade_durations: settings.fade_durations,
+            crossfade,
+            volume,
+            eq,
+            effects,
+            limiter,
+        }
+    }
+
+    /// Fades the output in or out, opening the stream the first time
+    ///
+    /// A stream whose device went away, or that stalled while playing, is
+    /// dropped here and reopened, on the default device if the chosen one is
+    /// gone.
+    ///
+    /// # Arguments
+    /// * `playing` - Whether the current track should be heard
+    pub fn set_playing(&mut self, playing: bool) {
+        // Drop the tracks the audio thread is done with
+        for _ in self.retired.try_iter() {}
+
+        let lost = self.status.is_device_lost() || (playing && self.status.is_stalled());
+        if self.stream.is_some() && lost {
+            eprintln!("Audio output device lost, reopening the output");
+            self.stream = None;
+            self.devices = self.backend.devices();
+        }
+        if playing {
+            if self.stream.is_none() && self.may_open_stream() {
+                self.open_stream();
+            }
+            self.fade.fade_in();
+        } else {
+            self.fade.fade_out();
+        }
+    }
+
+    /// Returns the name of the backend, e.g. `ALSA`, `JACK` or `null`
+    pub fn backend_name(&self) -> &str {
+        self.backend.name()
+    }
+
+    /// Returns the names of the output devices found by the last scan
+    pub fn devices(&self) -> &[String] {
+        &self.devices
+    }
+
+    /// Scans for output devices again, e.g. after one was plugged in
+    pub fn rescan_devices(&mut self) {
+        self.devices = self.backend.devices();
+    }
+
+    /// Returns the name of the device chosen by the user, if any
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Returns the name of the device the stream is open on, if it is open
+    pub fn active_device(&self) -> Option<&str> {
+        self.stream.as_ref().map(ActiveStream::device)
+    }
+
+    /// Returns the channels and sample rate of the stream, if it is open
+    pub fn format(&self) -> Option<StreamFormat> {
+        self.stream.as_ref().map(ActiveStream::format)
+    }
+
+    /// Returns the frames per buffer of the stream, if it is open and known
+    pub fn buffer_frames(&self) -> Option<u32> {
+        self.stream.as_ref().and_then(ActiveStream::buffer_frames)
+    }
+
+    /// Returns the time from rendering a sample until it is heard
+    ///
+    /// The visuals lag the playhead by this much to stay in sync with what
+    /// is heard. Zero while no stream is open.
+    pub fn latency(&self) -> Duration {
+        if self.stream.is_some() {
+            self.status.latency()
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Returns the health of the open stream: latency, underruns and late
+    /// callbacks
+    pub fn status(&self) -> &StreamStatus {
+        &self.status
+    }
+
+    /// Chooses the output device, moving an open stream over to it
+    ///
+    /// # Arguments
+    /// * `device` - Name of the device; `None` uses the default device
+    pub fn set_device(&mut self, device: Option<String>) {
+        if device == self.device {
+            return;
+        }
+        self.device = device;
+        if self.stream.take().is_some() {
+            self.open_stream();
+        }
+    }
+
+    /// Changes to another track once the output has faded out
+    ///
+    /// Any track queued with `queue_next` is dropped.
+    pub fn change_track(&mut self, track: Track) {
+        self.source_format = Some(track.format());
+        self.deck().change_track(track);
+    }
+
+    /// Queues a track to follow the current one
+    ///
+    /// # Arguments
+    /// * `track` - The track to play next
+    /// * `crossfade` - Whether to crossfade into it, if crossfades are
+    ///   enabled when it is due; otherwise it follows without a gap
+    pub fn queue_next(&self, track: Track, crossfade: bool) {
+        self.deck().queue_next(track, crossfade);
+    }
+
+    /// Drops the track queued with `queue_next`
+    ///
+    /// # Returns
+    /// Whether a track was queued; false once it took over
+    pub fn clear_next(&self) -> bool {
+        self.deck().clear_next()
+    }
+
+    /// Returns the id of the track the callback plays, if any
+    ///
+    /// A track changed to becomes the one played once the output has faded
+    /// out; a queued track when it takes over, which for a crossfade is as
+    /// the crossfade starts.
+    pub fn playing_track(&self) -> Option<TrackId> {
+        self.playing.get()
+    }
+
+    /// Locks the deck, ignoring a panic in another holder of the lock
+    fn deck(&self) -> MutexGuard<'_, Deck> {
+        self.deck.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns whether enough time has passed to try opening a stream again
+    fn may_open_stream(&self) -> bool {
+        self.last_open_attempt
+            .is_none_or(|attempt| attempt.elapsed() >= REOPEN_INTERVAL)
+    }
+
+    /// Opens the output stream on the chosen device, or the default one
+    ///
+    /// Problems are reported and leave the stream closed; opening is retried
+    /// while playing.
+    fn open_stream(&mut self) {
+        self.last_open_attempt = Some(Instant::now());
+        self.status = Arc::new(StreamStatus::default());
+        let mut make_render = |format: StreamFormat| -> RenderFn {
+            let deck = self.deck.clone();
+            let mut mixer = Mixer::new(
+                format,
+                self.fade_durations,
+                self.fade.clone(),
+                self.crossfade.clone(),
+            );
+            let mut equalizer = Equalizer::new(self.eq.clone(), format);
+            let effects = self.effects.clone();
+            effects
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .prepare(format);
+            let mut limiter = Limiter::new(self.limiter.clone(), format);
+            let volume = self.volume.clone();
+            // Start at the current settings; only later changes are ramped
+            let mut gains = volume.channel_gains();
+            Box::new(move |data: &mut [f32]| {
+                // Never wait for the main thread; it holds the deck only briefly.
+                // The rest of the chain is left as it is, so nothing is lost
+                // when a backend renders this buffer again instead.
+                let Ok(mut locked_deck) = deck.try_lock() else {
+                    data.fill(0.0);
+                    return false;
+                };
+                let played = mixer.fill_buffer(&mut locked_deck, data);
+                drop(locked_deck);
+                equalizer.process(data);
+                // Reordering holds the rack only briefly; skip the effects meanwhile
+                if let Ok(mut effects) = effects.try_lock() {
+                    effects.process(data);
+                }
+                // Ramp volume changes over the buffer so they never click
+                volume::apply_gains(data, format.channels, &mut gains, volume.channel_gains());
+                limiter.process(data);
+                // Effect and limiter tails still count as audio
+                played || data.iter().any(|sample| sample.abs() > SILENCE_LEVEL)
+            })
+        };
+
+        match self.backend.open(
+            self.device.as_deref(),
+            self.buffer_frames,
+            self.source_format,
+            self.status.clone(),
+            &mut make_render,
+        ) {
+            Ok(stream) => self.stream = Some(stream),
+            Err(e) => eprintln!("Error opening audio output: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::{AudioEffect, EffectChain};
+    use crate::fade::{Crossfade, CrossfadeCurve};
+
+    const SAMPLE_RATE: u32 = 8_000;
+    /// Frames before the first track is heard: the limiter's look-ahead at
+    /// `SAMPLE_RATE`, and the silent frame the track change happens in
+    const DELAY: usize = 12 + 1;
+    /// Frames the fades around seeks and track changes last at `SAMPLE_RATE`
+    const FADE_FRAMES: usize = 40;
+
+    /// Multiplies the audio by a factor
+    struct Scale(f32);
+
+    impl AudioEffect for Scale {
+        fn name(&self) -> &'static str {
+            "Scale"
+        }
+
+        fn prepare(&mut self, _format: StreamFormat) {}
+
+        fn process(&mut self, data: &mut [f32]) {
+            data.iter_mut().for_each(|sample| *sample *= self.0);
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    /// Adds a constant to the audio
+    struct Offset(f32);
+
+    impl AudioEffect for Offset {
+        fn name(&self) -> &'static str {
+            "Offset"
+        }
+
+        fn prepare(&mut self, _format: StreamFormat) {}
+
+        fn process(&mut self, data: &mut [f32]) {
+            data.iter_mut().for_each(|sample| *sample += self.0);
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    /// Creates an output rendering on demand at `SAMPLE_RATE`
+    fn output(
+        channels: usize,
+        crossfade: Crossfade,
+        effects: &EffectChain,
+    ) -> (AudioOutput, ManualStream) {
+        manual_output(
+            StreamFormat {
+                channels,
+                sample_rate: SAMPLE_RATE,
+            },
+            crossfade,
+            effects,
+        )
+    }
+
+    fn no_crossfade() -> Crossfade {
+        Crossfade {
+            seconds: 0.0,
+            curve: CrossfadeCurve::EqualPower,
+        }
+    }
+
+    /// Returns a track at the stream's sample rate and its playhead
+    fn track(samples: Vec<f32>, channels: u16) -> (Track, Arc<Playhead>) {
+        let playhead = Arc::new(Playhead::new());
+        let track = Track::new(
+            Arc::new(samples),
+            playhead.clone(),
+            Arc::new(TrackGain::new()),
+            channels,
+            SAMPLE_RATE,
+        );
+        (track, playhead)
+    }
+
+    #[test]
+    fn next_track_follows_without_a_gap() {
+        let (mut output, stream) = output(1, no_crossfade(), &EffectChain::new(Vec::new()));
+        let ramp = |range: std::ops::Range<usize>| range.map(|n| n as f32 / 1000.0).collect();
+        output.change_track(track(ramp(1..401), 1).0);
+        output.set_playing(true);
+        let (next, _) = track(ramp(401..801), 1);
+        let next_id = next.id();
+        output.queue_next(next, false);
+
+        let (audio, has_audio) = stream.render(1000);
+        assert!(has_audio);
+        assert_eq!(output.playing_track(), Some(next_id));
+        assert!(!output.clear_next());
+        // Past the fade-in, the second track continues the first one's ramp
+        for (frame, &sample) in audio
+            .iter()
+            .enumerate()
+            .take(DELAY + 800)
+            .skip(DELAY + FADE_FRAMES)
+        {
+            let expected = (frame - DELAY + 1) as f32 / 1000.0;
+            assert!(
+                (sample - expected).abs() < 1e-6,
+                "frame {}: {}",
+                frame,
+                sample
+            );
+        }
+        assert!(audio[DELAY + 800..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn seek_waits_for_the_fade_out() {
+        let (mut output, stream) = output(1, no_crossfade(), &EffectChain::new(Vec::new()));
+        let mut samples = vec![0.5; 4000];
+        samples.extend(vec![-0.5; 4000]);
+        let (track, playhead) = track(samples, 1);
+        output.change_track(track);
+        output.set_playing(true);
+        stream.render(800);
+
+        playhead.seek(6000);
+        let (audio, _) = stream.render(400);
+        let silent = audio.iter().position(|&sample| sample == 0.0).unwrap();
+        // The old position fades out before anything from the new one plays
+        assert!(audio[..silent].iter().all(|&sample| sample > 0.0));
+        let fading = audio[..silent]
+            .iter()
+            .filter(|&&sample| sample < 0.5)
+            .count();
+        assert!(fading >= FADE_FRAMES - 2, "{}", fading);
+        assert!(audio[silent..].iter().all(|&sample| sample <= 0.0));
+        assert_eq!(audio[audio.len() - 1], -0.5);
+        assert!(playhead.position() > 6000);
+    }
+
+    #[test]
+    fn crossfade_keeps_the_power_constant() {
+        let crossfade = Crossfade {
+            seconds: 0.05,
+            curve: CrossfadeCurve::EqualPower,
+        };
+        let (mut output, stream) = output(2, crossfade, &EffectChain::new(Vec::new()));
+        // The first track plays on the left only, the second on the right
+        output.change_track(track([0.5, 0.0].repeat(1600), 2).0);
+        output.set_playing(true);
+        let (next, _) = track([0.0, 0.5].repeat(1600), 2);
+        let next_id = next.id();
+        output.queue_next(next, true);
+
+        let (audio, _) = stream.render(2000);
+        assert_eq!(output.playing_track(), Some(next_id));
+        let overlap: Vec<&[f32]> = audio
+            .chunks(2)
+            .filter(|frame| frame[0] > 0.0 && frame[1] > 0.0)
+            .collect();
+        // 50 ms at 8 kHz
+        assert!(overlap.len().abs_diff(400) <= 2, "{}", overlap.len());
+        for pair in overlap.windows(2) {
+            assert!(pair[1][0] <= pair[0][0] && pair[1][1] >= pair[0][1]);
+        }
+        for frame in overlap {
+            let power = frame[0] * frame[0] + frame[1] * frame[1];
+            assert!((power - 0.25).abs() < 1e-3, "{}", power);
+        }
+    }
+
+    #[test]
+    fn effects_are_bypassed_and_reordered() {
+        let mut effects = EffectChain::new(vec![Box::new(Scale(2.0)), Box::new(Offset(0.25))]);
+        let (mut output, stream) = output(1, no_crossfade(), &effects);
+        output.change_track(track(vec![0.1; 8000], 1).0);
+        output.set_playing(true);
+        let last_sample = || stream.render(100).0[99];
+
+        // Every effect starts bypassed
+        assert!((last_sample() - 0.1).abs() < 1e-6);
+        effects.set_bypassed(0, false);
+        effects.set_bypassed(1, false);
+        assert!((last_sample() - 0.45).abs() < 1e-6);
+        effects.move_effect(1, 0);
+        assert!((last_sample() - 0.7).abs() < 1e-6);
+        assert_eq!(effects.settings()[0].name, "Offset");
+        effects.set_bypassed(0, true);
+        assert!((last_sample() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn nothing_is_rendered_once_playback_ends() {
+        let (mut output, stream) = output(1, no_crossfade(), &EffectChain::new(Vec::new()));
+        output.change_track(track(vec![0.5; 400], 1).0);
+        output.set_playing(true);
+        assert!(stream.render(400).1);
+        // Only what the limiter still held is left
+        let (tail, has_audio) = stream.render(100);
+        assert!(has_audio);
+        assert!(tail[DELAY..].iter().all(|&sample| sample == 0.0));
+        assert!(!stream.render(100).1);
+
+        let (track, playhead) = track(vec![0.5; 4000], 1);
+        output.change_track(track);
+        assert!(stream.render(400).1);
+        output.set_playing(false);
+        stream.render(100);
+        let paused_at = playhead.position();
+        assert_eq!(stream.render(100), (vec![0.0; 100], false));
+        assert_eq!(playhead.position(), paused_at);
+    }
+
+    #[test]
+    fn choosing_a_device_moves_the_stream() {
+        let (mut output, stream) = output(1, no_crossfade(), &EffectChain::new(Vec::new()));
+        // Nothing is opened before something plays
+        output.set_device(Some("manual".to_string()));
+        assert_eq!(output.active_device(), None);
+
+        let (track, playhead) = track(vec![0.25; 8000], 1);
+        output.change_track(track);
+        output.set_playing(true);
+        stream.render(400);
+        let position = playhead.position();
+        output.set_device(None);
+        assert_eq!(output.device(), None);
+        assert_eq!(output.active_device(), Some("manual"));
+        // The new stream plays on from the same position
+        let (audio, _) = stream.render(400);
+        assert!(playhead.position() > position);
+        assert!((audio[399] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lost_device_is_reopened_where_playback_was() {
+        let (mut output, stream) = output(1, no_crossfade(), &EffectChain::new(Vec::new()));
+        let (track, playhead) = track(vec![0.25; 8000], 1);
+        output.change_track(track);
+        output.set_playing(true);
+        stream.render(400);
+        let position = playhead.position();
+
+        output.status().set_device_lost();
+        output.set_playing(true);
+        assert_eq!(output.active_device(), None);
+        // Opening is retried once a second while something plays
+        let lost_at = Instant::now();
+        while output.active_device().is_none() {
+            assert!(lost_at.elapsed() < REOPEN_INTERVAL * 3);
+            std::thread::sleep(Duration::from_millis(10));
+            output.set_playing(true);
+        }
+        assert!(!output.status().is_device_lost());
+        assert_eq!(playhead.position(), position);
+        let (audio, _) = stream.render(400);
+        assert!(playhead.position() > position);
+        assert!((audio[399] - 0.25).abs() < 1e-6);
+    }
+}
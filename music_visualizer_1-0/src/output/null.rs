@@ -0,0 +1,59 @@
+//! Output that throws the audio away
+//!
+//! For runs without sound hardware. The audio is rendered on a thread, in
+//! real time or as fast as possible, in the format of the first song played.
+
+use super::backend::{
+    ActiveStream, BackendError, OutputBackend, PUMP_BUFFER_FRAMES, Pace, PumpThread, RenderFn,
+    StreamFormat, StreamStatus,
+};
+use std::sync::Arc;
+
+/// Name of the only device of the null backend
+const DEVICE_NAME: &str = "null";
+
+/// Consumes the audio without playing it
+pub struct NullBackend {
+    /// How fast to render
+    pace: Pace,
+}
+
+impl NullBackend {
+    /// Creates a null backend
+    ///
+    /// # Arguments
+    /// * `pace` - How fast to render
+    pub fn new(pace: Pace) -> Self {
+        NullBackend { pace }
+    }
+}
+
+impl OutputBackend for NullBackend {
+    fn name(&self) -> &str {
+        DEVICE_NAME
+    }
+
+    fn devices(&self) -> Vec<String> {
+        vec![DEVICE_NAME.to_string()]
+    }
+
+    fn open(
+        &self,
+        _device: Option<&str>,
+        buffer_frames: Option<u32>,
+        source_format: Option<StreamFormat>,
+        status: Arc<StreamStatus>,
+        make_render: &mut dyn FnMut(StreamFormat) -> RenderFn,
+    ) -> Result<ActiveStream, BackendError> {
+        let format = source_format.unwrap_or_default();
+        let buffer_frames = buffer_frames.unwrap_or(PUMP_BUFFER_FRAMES).max(1);
+        let render = make_render(format);
+        let thread = PumpThread::spawn(format, buffer_frames, self.pace, status, render, |_| {});
+        Ok(ActiveStream::new(
+            DEVICE_NAME.to_string(),
+            format,
+            Some(buffer_frames),
+            thread,
+        ))
+    }
+}
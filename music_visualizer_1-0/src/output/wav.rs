@@ -0,0 +1,191 @@
+//! Output recorded to a WAV file
+//!
+//! The file is created when the first stream opens, as 32-bit float samples
+//! in the format of the first song played, and written until the backend is
+//! dropped. Streams opened again later, e.g. after a stall, keep adding to
+//! the same recording in the same format. The audio is rendered on a thread,
+//! in real time or as fast as possible; stretches where nothing plays, e.g.
+//! pauses, are left out.
+
+use super::backend::{
+    ActiveStream, BackendError, OutputBackend, PUMP_BUFFER_FRAMES, Pace, PumpThread, RenderFn,
+    StreamFormat, StreamStatus,
+};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The file being written and its format
+struct Recording {
+    /// Writes the samples; `None` once writing failed
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    /// Channels and sample rate of the file
+    format: StreamFormat,
+}
+
+/// Writes the audio to a WAV file
+pub struct WavBackend {
+    /// File the audio is written to
+    path: PathBuf,
+    /// How fast to render
+    pace: Pace,
+    /// The recording, shared by every stream; `None` until the first opens
+    recording: Arc<Mutex<Option<Recording>>>,
+}
+
+impl WavBackend {
+    /// Creates a backend writing to a file
+    ///
+    /// # Arguments
+    /// * `path` - File the audio is written to
+    /// * `pace` - How fast to render
+    pub fn new(path: PathBuf, pace: Pace) -> Self {
+        WavBackend {
+            path,
+            pace,
+            recording: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl OutputBackend for WavBackend {
+    fn name(&self) -> &str {
+        "WAV file"
+    }
+
+    fn devices(&self) -> Vec<String> {
+        // The file is the only destination; there is nothing to choose
+        Vec::new()
+    }
+
+    fn open(
+        &self,
+        _device: Option<&str>,
+        buffer_frames: Option<u32>,
+        source_format: Option<StreamFormat>,
+        status: Arc<StreamStatus>,
+        make_render: &mut dyn FnMut(StreamFormat) -> RenderFn,
+    ) -> Result<ActiveStream, BackendError> {
+        let mut recording = self
+            .recording
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let format = match &*recording {
+            Some(recording) => recording.format,
+            None => {
+                let format = source_format.unwrap_or_default();
+                let spec = hound::WavSpec {
+                    channels: format.channels as u16,
+                    sample_rate: format.sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: hound::SampleFormat::Float,
+                };
+                *recording = Some(Recording {
+                    writer: Some(hound::WavWriter::create(&self.path, spec)?),
+                    format,
+                });
+                format
+            }
+        };
+        drop(recording);
+
+        let recording = self.recording.clone();
+        let path = self.path.display().to_string();
+        let sink = move |buffer: &[f32]| {
+            let mut recording = recording.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(recording) = recording.as_mut() else {
+                return;
+            };
+            let Some(file) = recording.writer.as_mut() else {
+                return;
+            };
+            if let Err(e) = buffer
+                .iter()
+                .try_for_each(|&sample| file.write_sample(sample))
+            {
+                // Stop writing; dropping the writer finishes the file
+                eprintln!("Failed to write {}: {}", path, e);
+                recording.writer = None;
+            }
+        };
+        let buffer_frames = buffer_frames.unwrap_or(PUMP_BUFFER_FRAMES).max(1);
+        let render = make_render(format);
+        let thread = PumpThread::spawn(format, buffer_frames, self.pace, status, render, sink);
+        Ok(ActiveStream::new(
+            self.path.display().to_string(),
+            format,
+            Some(buffer_frames),
+            thread,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    const BUFFER_FRAMES: u32 = 64;
+
+    /// Opens a stream that renders `buffers` buffers of `level`, then
+    /// nothing, and waits until it is done
+    fn record(backend: &WavBackend, format: StreamFormat, buffers: usize, level: f32) {
+        let rendered = Arc::new(AtomicUsize::new(0));
+        let counter = rendered.clone();
+        let mut make_render = move |_format: StreamFormat| -> RenderFn {
+            let counter = counter.clone();
+            Box::new(move |data: &mut [f32]| {
+                let count = counter.fetch_add(1, Ordering::Relaxed);
+                data.fill(level);
+                count < buffers
+            })
+        };
+        let stream = backend
+            .open(
+                None,
+                Some(BUFFER_FRAMES),
+                Some(format),
+                Arc::new(StreamStatus::default()),
+                &mut make_render,
+            )
+            .unwrap();
+        while rendered.load(Ordering::Relaxed) <= buffers {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(stream);
+    }
+
+    #[test]
+    fn reopened_streams_add_to_the_recording() {
+        let path = std::env::temp_dir().join(format!("wav_backend_{}.wav", std::process::id()));
+        let backend = WavBackend::new(path.clone(), Pace::Unthrottled);
+        assert!(backend.devices().is_empty());
+
+        let mono = StreamFormat {
+            channels: 1,
+            sample_rate: 8_000,
+        };
+        record(&backend, mono, 3, 0.25);
+        // The file keeps the format of the first stream
+        let stereo = StreamFormat {
+            channels: 2,
+            sample_rate: 44_100,
+        };
+        record(&backend, stereo, 2, 0.5);
+        drop(backend);
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, 8_000);
+        let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        let _ = std::fs::remove_file(&path);
+
+        let frames = BUFFER_FRAMES as usize;
+        assert_eq!(samples.len(), 5 * frames);
+        assert!(samples[..3 * frames].iter().all(|&s| s == 0.25));
+        assert!(samples[3 * frames..].iter().all(|&s| s == 0.5));
+    }
+}
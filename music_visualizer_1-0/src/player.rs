@@ -0,0 +1,1056 @@
+//! Playback state machine
+//!
+//! The `Player` owns the music library and the selected song and is the only
+//! place that decides what is playing. Front-ends never touch the song
+//! directly:
+//! - They send `PlayerCommand`s through a channel, so the menu, keyboard
+//!   shortcuts and future remote-control or scripting front-ends (possibly on
+//!   other threads) all go through the same path
+//! - They subscribe to `PlayerEvent`s to learn about state and track changes
+//!
+//! Commands are applied once per frame in `update`. Songs are decoded on a
+//! background thread while the player is `Loading`. The song after the
+//! selected one is decoded ahead of time and queued on the `AudioOutput`, so
+//! it follows the selected song without a gap. The player selects it once
+//! the output reports playing its track.
+//!
+//! The player also owns the effect chain the output runs the audio through;
+//! effect commands take effect on the next output buffer.
+//!
+//! Each song is played at the gain loudness normalization asks for. The gain
+//! is set again whenever the song's loudness has been measured or the
+//! normalization settings change, even while the song plays.
+
+use crate::dsp::EffectChain;
+use crate::music_library::MusicLibrary;
+use crate::normalization::NormalizationSettings;
+use crate::output::{AudioOutput, TrackId};
+use crate::song::{DecodedAudio, Song};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+/// Seconds skipped by the seek-backward and seek-forward controls
+pub const SKIP_SECONDS: f64 = 10.0;
+/// Elapsed seconds after which "previous" restarts the song instead
+const RESTART_THRESHOLD_SECONDS: f64 = 3.0;
+
+/// What the player is doing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlayerState {
+    /// Nothing selected, or stopped at the start of the song
+    Stopped,
+    /// Decoding the next song in the background
+    Loading,
+    /// Playing the selected song
+    Playing,
+    /// Holding the position in the selected song
+    Paused,
+    /// The last song could not be loaded
+    Error,
+}
+
+/// Request sent to the player by a front-end
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerCommand {
+    /// Start or resume playback
+    Play,
+    /// Pause, keeping the position
+    Pause,
+    /// Pause when playing, play otherwise
+    TogglePlayPause,
+    /// Stop and return to the start of the song
+    Stop,
+    /// Move the playhead to a position in seconds
+    Seek(f64),
+    /// Move the playhead by a number of seconds (negative goes back)
+    SeekBy(f64),
+    /// Change to the next song, keeping whether playback runs
+    Next,
+    /// Restart the song, or change to the previous one near its start
+    Previous,
+    /// Load the library song with this index and play it
+    Load(usize),
+    /// Play through the output device with this name, or the default one
+    SetOutputDevice(Option<String>),
+    /// Look for output devices again
+    RescanOutputDevices,
+    /// Skip (true) or run (false) the effect at this position in the chain
+    SetEffectBypassed(usize, bool),
+    /// Set a parameter, by name, of the effect at this position in the chain
+    SetEffectParam(usize, &'static str, f32),
+    /// Move the effect at the first position in the chain to the second
+    MoveEffect(usize, usize),
+    /// Change how songs are normalized
+    SetNormalization(NormalizationSettings),
+}
+
+/// Notification sent by the player to its subscribers
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerEvent {
+    /// The player entered a new state
+    StateChanged(PlayerState),
+    /// The library song with this index is now selected
+    TrackChanged(usize),
+    /// The playhead was moved to a position in seconds
+    Seeked(f64),
+    /// A song could not be loaded; the message describes why
+    Error(String),
+}
+
+/// Song being decoded in the background
+struct PendingLoad {
+    /// Library index of the song
+    index: usize,
+    /// Whether to start playing once it is decoded
+    play: bool,
+    /// Yields the decoded audio
+    job: Receiver<Result<DecodedAudio, hound::Error>>,
+}
+
+/// The song after the selected one, decoded ahead for gapless playback
+enum Prefetch {
+    /// Being decoded in the background
+    Decoding {
+        index: usize,
+        job: Receiver<Result<DecodedAudio, hound::Error>>,
+    },
+    /// Decoded and queued to follow the selected song as `track`
+    Ready {
+        index: usize,
+        song: Box<Song>,
+        track: TrackId,
+    },
+    /// Could not be decoded; not retried
+    Failed { index: usize },
+}
+
+impl Prefetch {
+    /// Returns the library index of the song
+    fn index(&self) -> usize {
+        match *self {
+            Prefetch::Decoding { index, .. }
+            | Prefetch::Ready { index, .. }
+            | Prefetch::Failed { index } => index,
+        }
+    }
+}
+
+/// Owner of the library and the selected song, driven by commands
+pub struct Player {
+    /// All songs and the selected one
+    library: MusicLibrary,
+    /// Stream the songs are played on
+    output: AudioOutput,
+    /// Effects the output runs the audio through
+    effects: EffectChain,
+    /// How songs are normalized
+    normalization: NormalizationSettings,
+    /// The next song, decoded ahead of time
+    prefetch: Option<Prefetch>,
+    /// What the player is doing
+    state: PlayerState,
+    /// Song being decoded while `Loading`
+    pending: Option<PendingLoad>,
+    /// Kept so `sender` can hand out new senders
+    command_sender: Sender<PlayerCommand>,
+    /// Commands waiting for the next `update`
+    commands: Receiver<PlayerCommand>,
+    /// One sender per subscriber; closed ones are dropped
+    subscribers: Vec<Sender<PlayerEvent>>,
+}
+
+impl Player {
+    /// Creates a stopped player with nothing selected
+    ///
+    /// # Arguments
+    /// * `library` - Songs to choose from
+    /// * `output` - Output the songs are played on
+    /// * `effects` - Effects the output runs the audio through
+    /// * `normalization` - How songs are normalized
+    pub fn new(
+        library: MusicLibrary,
+        output: AudioOutput,
+        effects: EffectChain,
+        normalization: NormalizationSettings,
+    ) -> Self {
+        let (command_sender, commands) = mpsc::channel();
+        Player {
+            library,
+            output,
+            effects,
+            normalization,
+            prefetch: None,
+            state: PlayerState::Stopped,
+            pending: None,
+            command_sender,
+            commands,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Returns a sender front-ends can use to control the player
+    pub fn sender(&self) -> Sender<PlayerCommand> {
+        self.command_sender.clone()
+    }
+
+    /// Queues a command for the next `update`
+    pub fn send(&self, command: PlayerCommand) {
+        // Cannot fail: the player holds the receiver
+        let _ = self.command_sender.send(command);
+    }
+
+    /// Returns a receiver for every event from now on
+    pub fn subscribe(&mut self) -> Receiver<PlayerEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Returns what the player is doing
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// Returns the music library
+    pub fn library(&self) -> &MusicLibrary {
+        &self.library
+    }
+
+    /// Returns the selected song (empty while nothing is selected)
+    pub fn song(&self) -> &Song {
+        &self.library.selected_song
+    }
+
+    /// Returns the output the songs are played on
+    pub fn output(&self) -> &AudioOutput {
+        &self.output
+    }
+
+    /// Returns the effects the output runs the audio through
+    pub fn effects(&self) -> &EffectChain {
+        &self.effects
+    }
+
+    /// Returns how songs are normalized
+    pub fn normalization(&self) -> NormalizationSettings {
+        self.normalization
+    }
+
+    /// Applies queued commands and advances playback
+    ///
+    /// Called once per frame to:
+    /// 1. Follow the output onto the next song when it took over, so that
+    ///    commands act on the song being heard
+    /// 2. Apply the commands sent since the last frame, in order
+    /// 3. Select a song that has finished decoding
+    /// 4. Decode the next song ahead of time
+    /// 5. Move on or stop at the end of the last song
+    /// 6. Update the song gains once new loudness measurements arrive
+    /// 7. Fade the output in or out to match the state, reopening it if its
+    ///    device went away
+    pub fn update(&mut self) {
+        self.follow_output();
+        while let Ok(command) = self.commands.try_recv() {
+            self.apply(command);
+        }
+        self.poll_pending();
+        self.prefetch_next();
+        self.handle_end_of_song();
+        if self.library.loudness.poll() {
+            self.normalize();
+        }
+
+        self.output.set_playing(self.state == PlayerState::Playing);
+        self.library.selected_song.poll_analysis();
+    }
+
+    /// Performs a single command
+    fn apply(&mut self, command: PlayerCommand) {
+        match command {
+            PlayerCommand::Play => self.play(),
+            PlayerCommand::Pause => self.pause(),
+            PlayerCommand::TogglePlayPause => match self.state {
+                PlayerState::Playing => self.pause(),
+                PlayerState::Loading if self.pending.as_ref().is_some_and(|p| p.play) => {
+                    self.pause()
+                }
+                _ => self.play(),
+            },
+            PlayerCommand::Stop => {
+                if self.library.has_selected_song() {
+                    self.seek(0.0);
+                    self.set_state(PlayerState::Stopped);
+                }
+            }
+            PlayerCommand::Seek(seconds) => self.seek(seconds),
+            PlayerCommand::SeekBy(seconds) => self.seek(self.song().position_seconds() + seconds),
+            PlayerCommand::Next => {
+                if let Some(index) = self.library.next_index() {
+                    self.load(index, self.will_play());
+                }
+            }
+            PlayerCommand::Previous => {
+                if self.song().position_seconds() > RESTART_THRESHOLD_SECONDS {
+                    self.seek(0.0);
+                } else if let Some(index) = self.library.previous_index() {
+                    self.load(index, self.will_play());
+                }
+            }
+            PlayerCommand::Load(index) => self.load(index, true),
+            PlayerCommand::SetOutputDevice(device) => self.output.set_device(device),
+            PlayerCommand::RescanOutputDevices => self.output.rescan_devices(),
+            PlayerCommand::SetEffectBypassed(index, bypassed) => {
+                self.effects.set_bypassed(index, bypassed)
+            }
+            PlayerCommand::SetEffectParam(index, name, value) => {
+                self.effects.set_param(index, name, value)
+            }
+            PlayerCommand::MoveEffect(from, to) => self.effects.move_effect(from, to),
+            PlayerCommand::SetNormalization(settings) => {
+                self.normalization = settings;
+                self.normalize();
+            }
+        }
+    }
+
+    /// Returns whether playback runs, or will once the pending song is loaded
+    fn will_play(&self) -> bool {
+        match &self.pending {
+            Some(pending) => pending.play,
+            None => self.state == PlayerState::Playing,
+        }
+    }
+
+    /// Starts or resumes playback of the selected song
+    fn play(&mut self) {
+        if let Some(pending) = &mut self.pending {
+            pending.play = true;
+        } else if self.library.has_selected_song() {
+            self.set_state(PlayerState::Playing);
+        }
+    }
+
+    /// Pauses playback, or keeps a song that is loading from starting
+    fn pause(&mut self) {
+        if let Some(pending) = &mut self.pending {
+            pending.play = false;
+        } else if self.state == PlayerState::Playing {
+            self.set_state(PlayerState::Paused);
+        }
+    }
+
+    /// Moves the playhead of the selected song
+    fn seek(&mut self, seconds: f64) {
+        if !self.library.has_selected_song() {
+            return;
+        }
+        let song = &self.library.selected_song;
+        song.seek(seconds);
+        let position = song.position_seconds();
+        self.emit(PlayerEvent::Seeked(position));
+    }
+
+    /// Starts decoding a library song; the current song fades out meanwhile
+    ///
+    /// A song that was already decoded ahead is selected right away.
+    ///
+    /// # Arguments
+    /// * `index` - Index of the song in the library
+    /// * `play` - Whether to start playing once it is decoded
+    fn load(&mut self, index: usize, play: bool) {
+        if let Some(song) = self.take_prefetched(index) {
+            self.pending = None;
+            self.select(index, song);
+            self.set_state(if play {
+                PlayerState::Playing
+            } else {
+                PlayerState::Paused
+            });
+            return;
+        }
+
+        let Some(entry) = self.library.songs.get(index) else {
+            return;
+        };
+        self.pending = Some(PendingLoad {
+            index,
+            play,
+            job: Song::spawn_decode(self.library.path(&entry.filename)),
+        });
+        self.set_state(PlayerState::Loading);
+    }
+
+    /// Selects the pending song once it has been decoded
+    fn poll_pending(&mut self) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        let result = match pending.job.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => return self.fail("decoder stopped".to_string()),
+        };
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+
+        match result {
+            Ok(audio) => {
+                let filename = &self.library.songs[pending.index].filename;
+                let song = Song::from_audio(filename, audio);
+                self.select(pending.index, song);
+                self.set_state(if pending.play {
+                    PlayerState::Playing
+                } else {
+                    PlayerState::Paused
+                });
+            }
+            Err(e) => self.fail(format!("Failed to load audio file: {}", e)),
+        }
+    }
+
+    /// Selects a song and has the output change to it after fading out
+    fn select(&mut self, index: usize, song: Song) {
+        song.set_gain_db(self.gain_db(&song));
+        // Changing tracks drops whatever was queued after the old one
+        self.output.change_track(song.track());
+        self.prefetch = None;
+        self.library.select_index(index, song);
+        self.prioritize_loudness();
+        self.emit(PlayerEvent::TrackChanged(index));
+    }
+
+    /// Returns the gain normalization plays a song at, in dB
+    fn gain_db(&self, song: &Song) -> f32 {
+        self.library
+            .loudness
+            .gain_db(&song.filename, self.normalization)
+    }
+
+    /// Sets the gain of the selected and the queued song
+    fn normalize(&mut self) {
+        let song = self.song();
+        song.set_gain_db(self.gain_db(song));
+        if let Some(Prefetch::Ready { song, .. }) = &self.prefetch {
+            song.set_gain_db(self.gain_db(song));
+        }
+    }
+
+    /// Has the selected song measured first if its loudness is unknown
+    fn prioritize_loudness(&self) {
+        let filename = &self.song().filename;
+        if self.library.loudness.get(filename).is_none() {
+            self.library.loudness.prioritize(filename);
+        }
+    }
+
+    /// Selects the queued song once the output plays its track
+    fn follow_output(&mut self) {
+        let is_playing = matches!(
+            &self.prefetch,
+            Some(Prefetch::Ready { track, .. }) if self.output.playing_track() == Some(*track)
+        );
+        if !is_playing {
+            return;
+        }
+        if let Some(Prefetch::Ready { index, song, .. }) = self.prefetch.take() {
+            self.library.select_index(index, *song);
+            self.prioritize_loudness();
+            self.emit(PlayerEvent::TrackChanged(index));
+        }
+    }
+
+    /// Decodes the song after the selected one and queues it on the output
+    ///
+    /// Only done while a song is playing or paused; a prefetched song that no
+    /// longer follows the selected one is dropped.
+    fn prefetch_next(&mut self) {
+        let next = match self.state {
+            PlayerState::Playing | PlayerState::Paused => self.library.next_index(),
+            _ => None,
+        };
+        if self.prefetch.as_ref().map(Prefetch::index) != next {
+            let is_queued = matches!(self.prefetch, Some(Prefetch::Ready { .. }));
+            if is_queued && !self.output.clear_next() {
+                // Too late to drop: the output moved on to it since `update`
+                // began. Follow it, and prefetch after it next frame.
+                self.follow_output();
+                self.prefetch = None;
+                return;
+            }
+            self.prefetch = None;
+            if let Some(index) = next {
+                let job =
+                    Song::spawn_decode(self.library.path(&self.library.songs[index].filename));
+                self.prefetch = Some(Prefetch::Decoding { index, job });
+            }
+        }
+
+        let Some(Prefetch::Decoding { index, job }) = &self.prefetch else {
+            return;
+        };
+        let index = *index;
+        let result = match job.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.prefetch = Some(Prefetch::Failed { index });
+                return;
+            }
+        };
+        self.prefetch = Some(match result {
+            Ok(audio) => {
+                let song = Song::from_audio(&self.library.songs[index].filename, audio);
+                song.set_gain_db(self.gain_db(&song));
+                // Songs of one album run into each other, so never crossfade them
+                let crossfade = !song.is_same_album(self.song());
+                let track = song.track();
+                let id = track.id();
+                self.output.queue_next(track, crossfade);
+                Prefetch::Ready {
+                    index,
+                    song: Box::new(song),
+                    track: id,
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to decode the next song ahead: {}", e);
+                Prefetch::Failed { index }
+            }
+        });
+    }
+
+    /// Removes and returns the prefetched song if it is the given one
+    fn take_prefetched(&mut self, index: usize) -> Option<Song> {
+        match self.prefetch.take() {
+            Some(Prefetch::Ready {
+                index: ready, song, ..
+            }) if ready == index => Some(*song),
+            other => {
+                self.prefetch = other;
+                None
+            }
+        }
+    }
+
+    /// Handles the selected song running out while playing
+    ///
+    /// A queued next song takes over in the audio callback by itself. If the
+    /// next song could not be queued in time it is loaded the normal way, and
+    /// after the last song the player stops.
+    fn handle_end_of_song(&mut self) {
+        let is_queued = matches!(self.prefetch, Some(Prefetch::Ready { .. }));
+        if self.state != PlayerState::Playing || is_queued || !self.song().is_finished() {
+            return;
+        }
+        match self.library.next_index() {
+            Some(index) => self.load(index, true),
+            None => self.apply(PlayerCommand::Stop),
+        }
+    }
+
+    /// Abandons the pending song and enters the error state
+    fn fail(&mut self, message: String) {
+        eprintln!("{}", message);
+        self.pending = None;
+        self.emit(PlayerEvent::Error(message));
+        self.set_state(PlayerState::Error);
+    }
+
+    /// Enters a state, notifying subscribers if it changed
+    fn set_state(&mut self, state: PlayerState) {
+        if self.state != state {
+            self.state = state;
+            self.emit(PlayerEvent::StateChanged(state));
+        }
+    }
+
+    /// Sends an event to every subscriber, forgetting those that went away
+    fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp;
+    use crate::fade::{Crossfade, CrossfadeCurve};
+    use crate::output::{ManualStream, StreamFormat, manual_output};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use std::{fs, thread};
+
+    const SAMPLE_RATE: u32 = 8_000;
+    /// Longest a test waits for songs to decode
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Library directory of WAV files, removed when dropped
+    struct TempLibrary(PathBuf);
+
+    impl TempLibrary {
+        /// Writes one 16-bit WAV file per song
+        ///
+        /// # Arguments
+        /// * `songs` - Filename, the level of each channel, and the length in
+        ///   seconds of every song
+        fn new(songs: &[(&str, &[f32], f64)]) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "player_test_{}_{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            for &(name, levels, seconds) in songs {
+                let spec = hound::WavSpec {
+                    channels: levels.len() as u16,
+                    sample_rate: SAMPLE_RATE,
+                    bits_per_sample: 16,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                let mut writer = hound::WavWriter::create(dir.join(name), spec).unwrap();
+                for _ in 0..(seconds * SAMPLE_RATE as f64) as usize {
+                    for &level in levels {
+                        writer
+                            .write_sample((level * i16::MAX as f32) as i16)
+                            .unwrap();
+                    }
+                }
+                writer.finalize().unwrap();
+            }
+            TempLibrary(dir)
+        }
+
+        /// Tags a song with an album, in a `LIST`/`INFO` chunk after its audio
+        fn tag_album(&self, name: &str, album: &str) {
+            let path = self.0.join(name);
+            let mut file = fs::read(&path).unwrap();
+            let mut album = album.as_bytes().to_vec();
+            album.push(0);
+            if album.len() % 2 == 1 {
+                album.push(0);
+            }
+            let info = [
+                b"INFO",
+                b"IPRD",
+                &(album.len() as u32).to_le_bytes(),
+                &album[..],
+            ]
+            .concat();
+            file.extend_from_slice(b"LIST");
+            file.extend_from_slice(&(info.len() as u32).to_le_bytes());
+            file.extend(info);
+            let riff_size = (file.len() - 8) as u32;
+            file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+            fs::write(path, file).unwrap();
+        }
+    }
+
+    impl Drop for TempLibrary {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn no_crossfade() -> Crossfade {
+        Crossfade {
+            seconds: 0.0,
+            curve: CrossfadeCurve::EqualPower,
+        }
+    }
+
+    /// Creates a player on the library, rendering on demand with the limiter
+    /// off and unity gain everywhere
+    fn player(
+        library: &TempLibrary,
+        channels: usize,
+        crossfade: Crossfade,
+        effects: EffectChain,
+    ) -> (Player, ManualStream) {
+        let format = StreamFormat {
+            channels,
+            sample_rate: SAMPLE_RATE,
+        };
+        let (output, stream) = manual_output(format, crossfade, &effects);
+        let player = Player::new(
+            MusicLibrary::new(&library.0),
+            output,
+            effects,
+            NormalizationSettings::default(),
+        );
+        (player, stream)
+    }
+
+    /// Updates the player until `done` holds, failing after `TIMEOUT`
+    fn update_until(player: &mut Player, done: impl Fn(&Player) -> bool) {
+        let start = Instant::now();
+        loop {
+            player.update();
+            if done(player) {
+                return;
+            }
+            assert!(start.elapsed() < TIMEOUT, "stuck {:?}", player.state());
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Updates the player until it is done loading and the selected song's
+    /// analysis is cached, so no cache is written after the library is removed
+    fn settle(player: &mut Player) {
+        update_until(player, |player| {
+            let song = player.song();
+            player.state() != PlayerState::Loading
+                && song.peaks().is_some()
+                && song.beat_grid().is_some()
+        });
+    }
+
+    /// Waits until the selected song's analysis is cached
+    fn finish(mut player: Player) {
+        settle(&mut player);
+    }
+
+    /// Updates the player until the next song is queued on the output
+    fn queue_next(player: &mut Player) {
+        update_until(player, |player| {
+            matches!(player.prefetch, Some(Prefetch::Ready { .. }))
+        });
+    }
+
+    #[test]
+    fn loading_ends_playing_paused_or_in_error() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 1.0), ("b.wav", &[0.5], 1.0)]);
+        fs::write(library.0.join("c.wav"), b"not a wav file").unwrap();
+        let (mut player, _stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        let events = player.subscribe();
+        assert_eq!(player.state(), PlayerState::Stopped);
+
+        // Nothing is selected, so there is nothing to play
+        player.send(PlayerCommand::Play);
+        player.update();
+        assert_eq!(player.state(), PlayerState::Stopped);
+
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        assert_eq!(player.state(), PlayerState::Playing);
+        assert_eq!(player.library().selected_index, Some(0));
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                PlayerEvent::StateChanged(PlayerState::Loading),
+                PlayerEvent::TrackChanged(0),
+                PlayerEvent::StateChanged(PlayerState::Playing),
+            ]
+        );
+
+        // Stopped, the next song is selected without playing it
+        player.send(PlayerCommand::Stop);
+        player.send(PlayerCommand::Next);
+        settle(&mut player);
+        assert_eq!(player.state(), PlayerState::Paused);
+        assert_eq!(player.library().selected_index, Some(1));
+
+        player.send(PlayerCommand::Load(2));
+        settle(&mut player);
+        assert_eq!(player.state(), PlayerState::Error);
+        let events: Vec<_> = events.try_iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ..,
+                PlayerEvent::Error(_),
+                PlayerEvent::StateChanged(PlayerState::Error)
+            ]
+        ));
+        // The song that played before stays selected
+        assert_eq!(player.library().selected_index, Some(1));
+        finish(player);
+    }
+
+    #[test]
+    fn toggling_while_loading_decides_whether_the_song_starts() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 1.0)]);
+        let (mut player, _stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+
+        let events = player.subscribe();
+        player.send(PlayerCommand::Load(0));
+        player.send(PlayerCommand::TogglePlayPause);
+        settle(&mut player);
+        assert_eq!(player.state(), PlayerState::Paused);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                PlayerEvent::StateChanged(PlayerState::Loading),
+                PlayerEvent::TrackChanged(0),
+                PlayerEvent::StateChanged(PlayerState::Paused),
+            ]
+        );
+
+        player.send(PlayerCommand::Load(0));
+        player.send(PlayerCommand::TogglePlayPause);
+        player.send(PlayerCommand::TogglePlayPause);
+        settle(&mut player);
+        assert_eq!(player.state(), PlayerState::Playing);
+        finish(player);
+    }
+
+    #[test]
+    fn previous_restarts_the_song_after_three_seconds() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 4.0), ("b.wav", &[0.5], 4.0)]);
+        let (mut player, _stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(1));
+        settle(&mut player);
+        let events = player.subscribe();
+
+        player.send(PlayerCommand::Seek(3.5));
+        player.send(PlayerCommand::Previous);
+        player.update();
+        assert_eq!(player.library().selected_index, Some(1));
+        assert_eq!(player.song().position_seconds(), 0.0);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [PlayerEvent::Seeked(3.5), PlayerEvent::Seeked(0.0)]
+        );
+
+        // Within the first three seconds it changes to the previous song
+        player.send(PlayerCommand::Seek(2.5));
+        player.send(PlayerCommand::Previous);
+        settle(&mut player);
+        assert_eq!(player.library().selected_index, Some(0));
+        assert_eq!(player.state(), PlayerState::Playing);
+
+        // The first song has nothing before it
+        player.send(PlayerCommand::Previous);
+        player.update();
+        assert_eq!(player.library().selected_index, Some(0));
+        assert_eq!(player.state(), PlayerState::Playing);
+        finish(player);
+    }
+
+    #[test]
+    fn stops_when_the_last_song_ends() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 0.1), ("b.wav", &[0.5], 0.1)]);
+        let (mut player, stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(1));
+        settle(&mut player);
+        let events = player.subscribe();
+
+        stream.render(2_000);
+        assert!(player.song().is_finished());
+        player.update();
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(player.library().selected_index, Some(1));
+        assert_eq!(player.song().position(), 0);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                PlayerEvent::Seeked(0.0),
+                PlayerEvent::StateChanged(PlayerState::Stopped),
+            ]
+        );
+        finish(player);
+    }
+
+    #[test]
+    fn selects_the_queued_song_once_the_output_plays_it() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 0.1), ("b.wav", &[0.5], 1.0)]);
+        let (mut player, stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        queue_next(&mut player);
+        let events = player.subscribe();
+
+        stream.render(1_000);
+        player.update();
+        assert_eq!(player.library().selected_index, Some(1));
+        assert_eq!(player.song().filename, "b.wav");
+        assert_eq!(player.state(), PlayerState::Playing);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [PlayerEvent::TrackChanged(1)]
+        );
+        finish(player);
+    }
+
+    #[test]
+    fn plays_the_queue_without_a_gap() {
+        let library = TempLibrary::new(&[
+            ("a.wav", &[0.25], 0.25),
+            ("b.wav", &[0.5], 0.25),
+            ("c.wav", &[0.75], 1.0),
+        ]);
+        let (mut player, stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        queue_next(&mut player);
+
+        let (audio, _) = stream.render(3_000);
+        // Once faded in, the first song runs straight into the second
+        let start = audio.iter().position(|&s| (s - 0.25).abs() < 1e-3).unwrap();
+        let end = audio.iter().position(|&s| (s - 0.5).abs() < 1e-3).unwrap();
+        assert!(audio[start..end].iter().all(|&s| (s - 0.25).abs() < 1e-3));
+        assert!(audio[end..].iter().all(|&s| (s - 0.5).abs() < 1e-3));
+        player.update();
+        assert_eq!(player.library().selected_index, Some(1));
+
+        // Changing to the queued song selects it without decoding it again
+        settle(&mut player);
+        queue_next(&mut player);
+        let events = player.subscribe();
+        player.send(PlayerCommand::Next);
+        player.update();
+        assert_eq!(player.library().selected_index, Some(2));
+        assert_eq!(player.state(), PlayerState::Playing);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [PlayerEvent::TrackChanged(2)]
+        );
+        finish(player);
+    }
+
+    #[test]
+    fn seeks_within_the_song() {
+        let library = TempLibrary::new(&[("a.wav", &[0.25], 2.0)]);
+        let (mut player, stream) =
+            player(&library, 1, no_crossfade(), EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        let events = player.subscribe();
+
+        player.send(PlayerCommand::Seek(1.0));
+        player.update();
+        assert_eq!(player.song().position_seconds(), 1.0);
+        // Playback goes on from the new position
+        stream.render(800);
+        let position = player.song().position_seconds();
+        assert!(position > 1.0 && position <= 1.1, "{}", position);
+
+        player.send(PlayerCommand::Seek(1.0));
+        player.send(PlayerCommand::SeekBy(0.5));
+        player.send(PlayerCommand::SeekBy(-5.0));
+        // Seeking past the end finishes the last song, which stops the player
+        player.send(PlayerCommand::Seek(10.0));
+        player.update();
+        assert_eq!(player.state(), PlayerState::Stopped);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                PlayerEvent::Seeked(1.0),
+                PlayerEvent::Seeked(1.0),
+                PlayerEvent::Seeked(1.5),
+                PlayerEvent::Seeked(0.0),
+                PlayerEvent::Seeked(2.0),
+                PlayerEvent::Seeked(0.0),
+                PlayerEvent::StateChanged(PlayerState::Stopped),
+            ]
+        );
+        finish(player);
+    }
+
+    #[test]
+    fn crossfades_into_the_queued_song() {
+        // The first song plays on the left only, the second on the right
+        let library = TempLibrary::new(&[("a.wav", &[0.5, 0.0], 0.5), ("b.wav", &[0.0, 0.5], 1.0)]);
+        let crossfade = Crossfade {
+            seconds: 0.1,
+            curve: CrossfadeCurve::EqualPower,
+        };
+        let (mut player, stream) = player(&library, 2, crossfade, EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        queue_next(&mut player);
+
+        let (audio, _) = stream.render(6_000);
+        let overlap = audio
+            .chunks(2)
+            .filter(|frame| frame[0] > 0.0 && frame[1] > 0.0)
+            .count();
+        // 100 ms at 8 kHz
+        assert!(overlap.abs_diff(800) <= 2, "{}", overlap);
+        player.update();
+        assert_eq!(player.library().selected_index, Some(1));
+        finish(player);
+    }
+
+    #[test]
+    fn songs_of_one_album_are_not_crossfaded() {
+        let library = TempLibrary::new(&[
+            ("a.wav", &[0.5, 0.0], 0.5),
+            ("b.wav", &[0.0, 0.5], 0.5),
+            ("c.wav", &[0.5, 0.0], 1.0),
+        ]);
+        library.tag_album("a.wav", "Album");
+        library.tag_album("b.wav", "Album");
+        let crossfade = Crossfade {
+            seconds: 0.1,
+            curve: CrossfadeCurve::EqualPower,
+        };
+        let (mut player, stream) = player(&library, 2, crossfade, EffectChain::new(Vec::new()));
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        queue_next(&mut player);
+
+        // The album runs from one song into the next without overlapping
+        let (audio, _) = stream.render(5_000);
+        assert!(
+            !audio
+                .chunks(2)
+                .any(|frame| frame[0] > 0.0 && frame[1] > 0.0)
+        );
+        assert!(audio.chunks(2).any(|frame| frame[1] > 0.0));
+        player.update();
+        assert_eq!(player.library().selected_index, Some(1));
+
+        // Leaving the album crossfades again
+        settle(&mut player);
+        queue_next(&mut player);
+        let (audio, _) = stream.render(4_000);
+        assert!(
+            audio
+                .chunks(2)
+                .any(|frame| frame[0] > 0.0 && frame[1] > 0.0)
+        );
+        player.update();
+        assert_eq!(player.library().selected_index, Some(2));
+        finish(player);
+    }
+
+    #[test]
+    fn effect_commands_change_the_audio() {
+        let library = TempLibrary::new(&[("a.wav", &[0.3], 2.0)]);
+        let effects = EffectChain::new(dsp::default_effects());
+        let (mut player, stream) = player(&library, 1, no_crossfade(), effects);
+        player.send(PlayerCommand::Load(0));
+        settle(&mut player);
+        let last_sample = |player: &mut Player| {
+            player.update();
+            stream.render(400).0[399]
+        };
+
+        // Every effect starts bypassed
+        assert!((last_sample(&mut player) - 0.3).abs() < 1e-3);
+
+        // Two bits leave the levels -0.5, 0.0 and 0.5
+        let bitcrusher = 2;
+        player.send(PlayerCommand::SetEffectBypassed(bitcrusher, false));
+        player.send(PlayerCommand::SetEffectParam(bitcrusher, "Bits", 2.0));
+        assert!((last_sample(&mut player) - 0.5).abs() < 1e-6);
+        player.send(PlayerCommand::SetEffectParam(bitcrusher, "Mix", 0.5));
+        assert!((last_sample(&mut player) - 0.4).abs() < 1e-3);
+
+        player.send(PlayerCommand::MoveEffect(bitcrusher, 0));
+        player.update();
+        assert_eq!(player.effects().settings()[0].name, "Bitcrusher");
+        player.send(PlayerCommand::SetEffectBypassed(0, true));
+        assert!((last_sample(&mut player) - 0.3).abs() < 1e-3);
+        finish(player);
+    }
+}
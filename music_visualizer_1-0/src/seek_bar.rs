@@ -0,0 +1,158 @@
+//! Waveform overview seek bar
+//!
+//! Shows the whole track as a min/max/RMS waveform strip beneath the
+//! visualization:
+//! - The played portion is drawn brighter than the rest
+//! - A playhead line marks the current position
+//! - Clicking or dragging anywhere on the strip seeks there
+
+use crate::analysis::WaveformPeaks;
+use nannou::prelude::*;
+
+/// Waveform strip that doubles as a seek control
+pub struct SeekBar {
+    /// Rectangle defining the strip's bounds and position
+    bar_rect: Rect,
+    /// True while the user is dragging the playhead
+    is_dragging: bool,
+}
+
+impl SeekBar {
+    /// Creates a new SeekBar
+    ///
+    /// # Arguments
+    /// * `bar_rect` - The bounding rectangle for the strip
+    pub fn new(bar_rect: Rect) -> Self {
+        SeekBar {
+            bar_rect,
+            is_dragging: false,
+        }
+    }
+
+    /// Moves the strip to a new area, e.g. after the window was resized
+    ///
+    /// # Arguments
+    /// * `bar_rect` - The new bounding rectangle for the strip
+    pub fn set_rect(&mut self, bar_rect: Rect) {
+        self.bar_rect = bar_rect;
+    }
+
+    /// Handles clicking and dragging on the strip
+    ///
+    /// A drag starts with a press inside the strip and keeps seeking while the
+    /// button is held, even if the mouse leaves the strip.
+    ///
+    /// # Arguments
+    /// * `app` - Reference to Nannou application for input access
+    ///
+    /// # Returns
+    /// The requested position as a fraction of the track (0.0 to 1.0), if any
+    pub fn update(&mut self, app: &App) -> Option<f32> {
+        let mouse = app.mouse.position();
+        let is_mouse_pressed = app.mouse.buttons.left().is_down();
+
+        if !is_mouse_pressed {
+            self.is_dragging = false;
+        } else if !self.is_dragging && self.bar_rect.contains(mouse) {
+            self.is_dragging = true;
+        }
+
+        self.is_dragging.then(|| self.fraction_at(mouse.x))
+    }
+
+    /// Converts an x coordinate into a fraction of the strip's width
+    fn fraction_at(&self, x: f32) -> f32 {
+        ((x - self.bar_rect.left()) / self.bar_rect.w()).clamp(0.0, 1.0)
+    }
+
+    /// Renders the waveform, played portion and playhead
+    ///
+    /// Draws one min/max column and one RMS column per pixel. While the peaks
+    /// are still being computed only the background and playhead are shown.
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `peaks` - Waveform overview of the track, if loaded
+    /// * `progress` - Playback position as a fraction of the track
+    pub fn draw(&self, draw: &Draw, peaks: Option<&WaveformPeaks>, progress: f32) {
+        let rect = self.bar_rect;
+
+        // Draw strip background
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgb(0.05, 0.05, 0.05));
+
+        let playhead_x = rect.left() + progress.clamp(0.0, 1.0) * rect.w();
+
+        if let Some(peaks) = peaks {
+            let columns = rect.w().max(1.0) as usize;
+            let half_height = rect.h() / 2.0;
+            let mut peak_triangles = Vec::with_capacity(columns * 6);
+            let mut rms_triangles = Vec::with_capacity(columns * 6);
+
+            for column in 0..columns {
+                let left = rect.left() + column as f32;
+                let right = left + 1.0;
+                let levels = peaks.column(
+                    column as f32 / columns as f32,
+                    (column + 1) as f32 / columns as f32,
+                );
+
+                // Played columns are brighter than the unplayed remainder
+                let (peak_color, rms_color) = if left < playhead_x {
+                    (rgba(0.3, 0.6, 1.0, 1.0), rgba(0.6, 0.85, 1.0, 1.0))
+                } else {
+                    (rgba(0.25, 0.25, 0.3, 1.0), rgba(0.45, 0.45, 0.5, 1.0))
+                };
+
+                push_quad(
+                    &mut peak_triangles,
+                    left,
+                    right,
+                    rect.y() + levels.min * half_height,
+                    rect.y() + levels.max * half_height,
+                    peak_color,
+                );
+                push_quad(
+                    &mut rms_triangles,
+                    left,
+                    right,
+                    rect.y() - levels.rms * half_height,
+                    rect.y() + levels.rms * half_height,
+                    rms_color,
+                );
+            }
+
+            draw.mesh().tris_colored(peak_triangles);
+            draw.mesh().tris_colored(rms_triangles);
+        }
+
+        // Draw playhead
+        draw.line()
+            .start(pt2(playhead_x, rect.top()))
+            .end(pt2(playhead_x, rect.bottom()))
+            .color(WHITE)
+            .weight(2.0);
+    }
+}
+
+/// Appends the two triangles of an axis-aligned quad to a vertex list
+fn push_quad(
+    triangles: &mut Vec<(Point3, Rgba)>,
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    color: Rgba,
+) {
+    let corners = [
+        pt3(left, bottom, 0.0),
+        pt3(right, bottom, 0.0),
+        pt3(left, top, 0.0),
+        pt3(left, top, 0.0),
+        pt3(right, bottom, 0.0),
+        pt3(right, top, 0.0),
+    ];
+    triangles.extend(corners.into_iter().map(|corner| (corner, color)));
+}
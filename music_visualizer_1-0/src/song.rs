@@ -1,146 +1,340 @@
 //! Audio playback and song management module
 //!
-//! Handles loading and playing WAV audio files using CPAL for audio output.
-//! Manages playback state and audio stream lifecycle.
+//! Handles loading WAV audio files and their background analysis. The audio
+//! itself is played by the `AudioOutput`, which reads a song's samples and
+//! moves its playhead through the song's `Track`.
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use crate::analysis::{BeatGrid, WaveformPeaks};
+use crate::normalization::ReplayGain;
+use crate::output::{Playhead, Track, TrackGain};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Samples, format and tags of a decoded WAV file
+pub struct DecodedAudio {
+    /// Interleaved samples between -1.0 and 1.0
+    samples: Vec<f32>,
+    /// Format of the file
+    spec: hound::WavSpec,
+    /// Album from the file's `INFO` tags, if tagged
+    album: Option<String>,
+    /// ReplayGain from the file's ID3 tags
+    replay_gain: ReplayGain,
+}
+
+impl DecodedAudio {
+    /// Returns the interleaved samples between -1.0 and 1.0
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Returns the format of the file
+    pub fn spec(&self) -> hound::WavSpec {
+        self.spec
+    }
+
+    /// Returns the album the file is tagged with
+    pub fn album(&self) -> Option<&str> {
+        self.album.as_deref()
+    }
+
+    /// Returns the ReplayGain tags of the file
+    pub fn replay_gain(&self) -> ReplayGain {
+        self.replay_gain
+    }
+}
+
+/// Tags read from a WAV file besides its audio
+#[derive(Default)]
+struct WavTags {
+    /// Album (`IPRD` in the `INFO` list)
+    album: Option<String>,
+    /// ReplayGain (`TXXX` frames in an ID3 chunk)
+    replay_gain: ReplayGain,
+}
 
 /// Represents an audio song with playback capabilities
 ///
-/// Manages the audio playback state, current position in the song,
-/// and the underlying CPAL audio stream. Provides simple play/pause controls.
+/// Manages the decoded audio and the current position in the song, which
+/// the audio output advances while the song plays.
 ///
 
 pub struct Song {
-    /// Current playback state (true if playing)
-    is_playing: bool,
-    /// Active audio stream (None when paused/stopped)
-    audio_stream: Option<cpal::Stream>,
     /// Shared audio sample data (32-bit float samples between -1.0 and 1.0)
-    audio_data: Arc<Mutex<Vec<f32>>>,
-    /// Current playback position in samples
-    current_frame: usize,
+    ///
+    /// The samples never change after loading, so the audio callback and the
+    /// analyzers can read them concurrently without locking.
+    audio_data: Arc<Vec<f32>>,
+    /// Current playback position, advanced by the audio callback
+    playhead: Arc<Playhead>,
+    /// Level the audio callback plays the song at
+    gain: Arc<TrackGain>,
+    /// Sample rate of the loaded file in Hz
+    sample_rate: u32,
+    /// Number of interleaved channels in the loaded file
+    channels: u16,
+    /// Beat grid from the offline analysis, once it has finished
+    beat_grid: Option<BeatGrid>,
+    /// Pending background beat analysis
+    beat_grid_job: Option<Receiver<BeatGrid>>,
+    /// Waveform overview, once loaded from cache or computed
+    peaks: Option<WaveformPeaks>,
+    /// Pending background waveform overview load
+    peaks_job: Option<Receiver<WaveformPeaks>>,
+    /// Album the song belongs to, if the file is tagged with one
+    album: Option<String>,
     pub title: String,
     pub filename: String,
 }
 
 impl Song {
-    /// Creates a new Song instance from file
-    pub fn from_file(song_file_name: &str) -> Self {
-        let song_path = format!("music_library/{}", song_file_name);
-        let audio_data = match Self::load_wav(&song_path) {
-            Ok(data) => Arc::new(Mutex::new(data)),
-            Err(e) => {
-                eprintln!("Failed to load audio file: {}", e);
-                Arc::new(Mutex::new(Vec::new()))
-            }
-        };
-
+    /// Creates a new Song instance from decoded audio
+    ///
+    /// # Arguments
+    /// * `song_file_name` - Filename inside the library directory
+    /// * `audio` - Samples and format decoded by `spawn_decode`
+    pub fn from_audio(song_file_name: &str, audio: DecodedAudio) -> Self {
         Song {
-            is_playing: false,
-            audio_stream: None,
-            audio_data,
-            current_frame: 0,
+            audio_data: Arc::new(audio.samples),
+            playhead: Arc::new(Playhead::new()),
+            gain: Arc::new(TrackGain::new()),
+            sample_rate: audio.spec.sample_rate,
+            channels: audio.spec.channels,
+            beat_grid: None,
+            beat_grid_job: None,
+            peaks: None,
+            peaks_job: None,
+            album: audio.album,
             title: Self::parse_title(song_file_name),
             filename: song_file_name.to_string(),
         }
     }
 
+    /// Decodes a song file on a background thread
+    ///
+    /// # Arguments
+    /// * `path` - Path of the song file
+    ///
+    /// # Returns
+    /// Receiver that yields the decoded audio, or the decoding error
+    pub fn spawn_decode(path: PathBuf) -> Receiver<Result<DecodedAudio, hound::Error>> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // Nobody may be waiting any more, which is fine
+            let _ = sender.send(Self::decode(&path));
+        });
+        receiver
+    }
+
+    /// Decodes a song file on the calling thread
+    ///
+    /// # Arguments
+    /// * `path` - Path of the song file
+    pub fn decode(path: &Path) -> Result<DecodedAudio, hound::Error> {
+        Self::load_wav(path)
+    }
+
     /// Creates an empty Song instance
     pub fn empty() -> Self {
         Song {
-            is_playing: false,
-            audio_stream: None,
-            audio_data: Arc::new(Mutex::new(Vec::new())),
-            current_frame: 0,
+            audio_data: Arc::new(Vec::new()),
+            playhead: Arc::new(Playhead::new()),
+            gain: Arc::new(TrackGain::new()),
+            sample_rate: 44_100,
+            channels: 2,
+            beat_grid: None,
+            beat_grid_job: None,
+            peaks: None,
+            peaks_job: None,
+            album: None,
             title: "".to_string(),
             filename: "".to_string(),
         }
     }
 
-    /// Updates playback state based on external command
+    /// Checks whether two songs are tagged with the same album
+    pub fn is_same_album(&self, other: &Song) -> bool {
+        self.album.is_some() && self.album == other.album
+    }
+
+    /// Returns the handle the audio output plays the song through
+    ///
+    /// The output converts it to the stream's sample rate and channels.
+    pub fn track(&self) -> Track {
+        Track::new(
+            self.audio_data.clone(),
+            self.playhead.clone(),
+            self.gain.clone(),
+            self.channels,
+            self.sample_rate,
+        )
+    }
+
+    /// Sets the level the song is played at, even while it plays
     ///
     /// # Arguments
-    /// * `should_play` - True if audio should be playing, false if paused
+    /// * `db` - Gain in dB; 0.0 plays the song as it is
+    pub fn set_gain_db(&self, db: f32) {
+        self.gain.set_db(db);
+    }
+
+    /// Returns whether the playhead has reached the end of the song
+    pub fn is_finished(&self) -> bool {
+        self.position() >= self.audio_data.len()
+    }
+
+    /// Returns the decoded audio samples
     ///
-    /// This will automatically start or stop playback as needed.
-    pub fn update(&mut self, should_play: bool) {
-        if should_play && !self.is_playing {
-            self.play();
-        } else if !should_play && self.is_playing {
-            self.pause();
-        }
-        self.is_playing = should_play;
+    /// # Returns
+    /// Interleaved f32 samples (-1.0 to 1.0) shared with the audio callback
+    pub fn samples(&self) -> &[f32] {
+        &self.audio_data
     }
 
-    /// Returns current playback state
+    /// Returns the current playback position
+    ///
+    /// A seek waiting for the fade-out already counts as the new position.
     ///
     /// # Returns
-    /// True if audio is currently playing, false if paused/stopped
-    pub fn is_playing(&self) -> bool {
-        self.is_playing
+    /// Index of the next interleaved sample the audio callback will output
+    pub fn position(&self) -> usize {
+        self.playhead.position()
     }
 
-    /// Starts audio playback
+    /// Returns the current playback position in seconds
+    pub fn position_seconds(&self) -> f64 {
+        let frame = self.position() / self.channels.max(1) as usize;
+        frame as f64 / self.sample_rate as f64
+    }
+
+    /// Returns the position being heard, which lags the playhead
     ///
-    /// Initializes audio stream if not already playing.
-    /// Uses the default audio output device.
+    /// # Arguments
+    /// * `latency` - Time from the audio callback until a sample is heard
     ///
-    /// # Panics
-    /// - If no audio output device is available
-    /// - If audio device configuration is unsupported
-    fn play(&mut self) {
-        if self.audio_stream.is_some() {
-            return;
-        }
+    /// # Returns
+    /// Index of the interleaved sample being heard, at a frame boundary
+    pub fn audible_position(&self, latency: Duration) -> usize {
+        let channels = self.channels.max(1) as usize;
+        let lag_frames = (latency.as_secs_f64() * self.sample_rate as f64) as usize;
+        (self.position() / channels).saturating_sub(lag_frames) * channels
+    }
 
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .expect("no output device available");
-        let config = device.default_output_config().unwrap();
+    /// Returns the position being heard in seconds
+    ///
+    /// # Arguments
+    /// * `latency` - Time from the audio callback until a sample is heard
+    pub fn audible_seconds(&self, latency: Duration) -> f64 {
+        let frame = self.audible_position(latency) / self.channels.max(1) as usize;
+        frame as f64 / self.sample_rate as f64
+    }
 
-        let audio_data = self.audio_data.clone();
-        let mut current_frame = self.current_frame;
+    /// Returns the song's beat grid
+    ///
+    /// The grid is computed on a background thread once the song is
+    /// selected, so this returns `None` until that analysis has finished and
+    /// `poll_analysis` has picked it up.
+    pub fn beat_grid(&self) -> Option<&BeatGrid> {
+        self.beat_grid.as_ref()
+    }
 
-        let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => device.build_output_stream(
-                &config.into(),
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    let audio_data = audio_data.lock().unwrap();
-                    for sample in data.iter_mut() {
-                        *sample = if current_frame < audio_data.len() {
-                            audio_data[current_frame]
-                        } else {
-                            0.0
-                        };
-                        current_frame += 1;
-                    }
-                },
-                move |err| eprintln!("an error occurred on stream: {}", err),
-                None,
-            ),
-            _ => panic!("unsupported sample format"),
-        };
+    /// Collects the results of finished background analysis
+    ///
+    /// Called once per frame; never blocks.
+    pub fn poll_analysis(&mut self) {
+        if let Some(job) = &self.beat_grid_job
+            && let Ok(grid) = job.try_recv()
+        {
+            self.beat_grid = Some(grid);
+            self.beat_grid_job = None;
+        }
+        if let Some(job) = &self.peaks_job
+            && let Ok(peaks) = job.try_recv()
+        {
+            self.peaks = Some(peaks);
+            self.peaks_job = None;
+        }
+    }
 
-        match stream {
-            Ok(stream) => {
-                stream.play().unwrap();
-                self.audio_stream = Some(stream);
-            }
-            Err(e) => eprintln!("Error creating audio stream: {}", e),
+    /// Returns the length of the song in seconds
+    pub fn duration_seconds(&self) -> f64 {
+        let frames = self.audio_data.len() / self.channels.max(1) as usize;
+        frames as f64 / self.sample_rate as f64
+    }
+
+    /// Moves the playhead
+    ///
+    /// The audio callback fades out before the jump and back in after it;
+    /// until then, `position` already reports the new position.
+    ///
+    /// # Arguments
+    /// * `seconds` - Target position, clamped to the length of the song
+    pub fn seek(&self, seconds: f64) {
+        let channels = self.channels.max(1) as usize;
+        let frames = self.audio_data.len() / channels;
+        let frame = (seconds.max(0.0) * self.sample_rate as f64) as usize;
+        self.playhead.seek(frame.min(frames) * channels);
+    }
+
+    /// Starts loading the waveform overview in the background
+    ///
+    /// Peaks are read from the index cache when it is up to date, otherwise
+    /// they are computed and written to the cache.
+    ///
+    /// # Arguments
+    /// * `source` - Path of the song file, used to validate the cache
+    /// * `cache` - Path of the cache file
+    pub fn load_peaks(&mut self, source: PathBuf, cache: PathBuf) {
+        if self.peaks.is_some() || self.peaks_job.is_some() || self.filename.is_empty() {
+            return;
         }
+        self.peaks_job = Some(WaveformPeaks::spawn_load(
+            source,
+            cache,
+            self.audio_data.clone(),
+            self.channels,
+        ));
+    }
+
+    /// Returns the waveform overview, or `None` while it is still loading
+    pub fn peaks(&self) -> Option<&WaveformPeaks> {
+        self.peaks.as_ref()
+    }
+
+    /// Returns the sample rate of the loaded file in Hz
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of interleaved channels in the loaded file
+    pub fn channels(&self) -> u16 {
+        self.channels
     }
 
-    /// Pauses audio playback
+    /// Starts analyzing the whole song for beats in the background
     ///
-    /// Stops the audio stream and maintains current playback position.
-    fn pause(&mut self) {
-        if let Some(stream) = self.audio_stream.take() {
-            drop(stream); // This will stop the stream
+    /// The grid is read from the index cache when it is up to date, otherwise
+    /// it is computed and written to the cache.
+    ///
+    /// # Arguments
+    /// * `source` - Path of the song file, used to validate the cache
+    /// * `cache` - Path of the cache file
+    pub fn analyze_beats(&mut self, source: PathBuf, cache: PathBuf) {
+        if self.beat_grid.is_some() || self.beat_grid_job.is_some() || self.audio_data.is_empty() {
+            return;
         }
+        self.beat_grid_job = Some(BeatGrid::spawn_load(
+            source,
+            cache,
+            self.audio_data.clone(),
+            self.channels,
+            self.sample_rate,
+        ));
     }
 
     pub fn parse_title(song_file_name: &str) -> String {
@@ -170,16 +364,331 @@ impl Song {
     /// * `path` - Path to WAV file (16-bit PCM format)
     ///
     /// # Returns
-    /// Result containing vector of normalized f32 samples (-1.0 to 1.0) or error
+    /// Result containing vector of normalized f32 samples (-1.0 to 1.0),
+    /// the file's format spec and its album and ReplayGain tags, or error
     ///
     /// # Errors
     /// Returns hound::Error if file cannot be read or is in invalid format
-    fn load_wav(path: &str) -> Result<Vec<f32>, hound::Error> {
-        let reader = hound::WavReader::open(Path::new(path))?;
+    fn load_wav(path: &Path) -> Result<DecodedAudio, hound::Error> {
+        let reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
         let samples: Vec<f32> = reader
             .into_samples::<i16>()
             .map(|s| s.unwrap_or(0) as f32 / i16::MAX as f32)
             .collect();
-        Ok(samples)
+        let tags = read_tags(path).unwrap_or_default();
+        Ok(DecodedAudio {
+            samples,
+            spec,
+            album: tags.album,
+            replay_gain: tags.replay_gain,
+        })
+    }
+}
+
+/// Reads the album and ReplayGain tags of a WAV file
+///
+/// hound skips chunks it does not need, so the RIFF chunks are walked here.
+/// The album comes from the `LIST`/`INFO` chunk; ReplayGain from an `id3 `
+/// chunk, where tagging tools put the same ID3 tag as in an MP3 file.
+///
+/// # Returns
+/// The tags found, or `None` if the file cannot be read
+fn read_tags(path: &Path) -> Option<WavTags> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut tags = WavTags::default();
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().ok()?) as usize;
+        // Chunks are padded to an even length
+        let padded_size = size + size % 2;
+        let id = &chunk_header[0..4];
+        if id != b"LIST" && id != b"id3 " && id != b"ID3 " {
+            file.seek_relative(padded_size as i64).ok()?;
+            continue;
+        }
+
+        let mut chunk = vec![0u8; padded_size];
+        if file.read_exact(&mut chunk).is_err() {
+            // A truncated last chunk still gives what was found before it
+            break;
+        }
+        if id != b"LIST" {
+            read_id3_replay_gain(&chunk[..size], &mut tags.replay_gain);
+        } else if let Some(info) = chunk[..size].strip_prefix(b"INFO")
+            && let Some(album) = find_info_tag(info, b"IPRD")
+        {
+            tags.album = Some(album);
+        }
+    }
+    Some(tags)
+}
+
+/// Finds a tag in the sub-chunks of an `INFO` list
+///
+/// # Arguments
+/// * `info` - Contents of the list after the `INFO` type
+/// * `id` - Four-character tag to look for
+fn find_info_tag(mut info: &[u8], id: &[u8; 4]) -> Option<String> {
+    while info.len() >= 8 {
+        let size = u32::from_le_bytes(info[4..8].try_into().ok()?) as usize;
+        let data = info.get(8..8 + size)?;
+        if &info[0..4] == id {
+            let text = String::from_utf8_lossy(data);
+            let text = text.trim_end_matches('\0').trim();
+            return (!text.is_empty()).then(|| text.to_string());
+        }
+        info = info.get(8 + size + size % 2..)?;
+    }
+    None
+}
+
+/// Reads the ReplayGain `TXXX` frames of an ID3v2.3 or ID3v2.4 tag
+///
+/// Unsynchronised tags are not supported; other frames are skipped.
+///
+/// # Arguments
+/// * `tag` - The whole tag, starting with its `ID3` header
+/// * `replay_gain` - Receives every ReplayGain value found
+fn read_id3_replay_gain(tag: &[u8], replay_gain: &mut ReplayGain) -> Option<()> {
+    let header = tag.get(0..10)?;
+    let version = header[3];
+    let flags = header[5];
+    if &header[0..3] != b"ID3" || !(3..=4).contains(&version) || flags & 0x80 != 0 {
+        return None;
+    }
+    let end = (10 + syncsafe(&header[6..10])).min(tag.len());
+    let mut frames = tag.get(10..end)?;
+
+    if flags & 0x40 != 0 {
+        // The extended header counts its own size in version 4 only
+        let size = frames.get(0..4)?;
+        let skip = if version == 4 {
+            syncsafe(size)
+        } else {
+            4 + u32::from_be_bytes(size.try_into().ok()?) as usize
+        };
+        frames = frames.get(skip..)?;
+    }
+
+    while frames.len() >= 10 && frames[0] != 0 {
+        let size = if version == 4 {
+            syncsafe(&frames[4..8])
+        } else {
+            u32::from_be_bytes(frames[4..8].try_into().ok()?) as usize
+        };
+        let body = frames.get(10..10 + size)?;
+        if &frames[0..4] == b"TXXX"
+            && let Some((&encoding, text)) = body.split_first()
+        {
+            let strings = decode_id3_text(encoding, text);
+            if let [description, value, ..] = strings.as_slice() {
+                replay_gain.set_tag(description, value);
+            }
+        }
+        frames = &frames[10 + size..];
+    }
+    Some(())
+}
+
+/// Decodes a 28-bit ID3 size stored in four 7-bit bytes
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |size, &byte| (size << 7) | (byte & 0x7f) as usize)
+}
+
+/// Decodes the null-separated strings of an ID3 text frame
+///
+/// # Arguments
+/// * `encoding` - 0 for Latin-1, 1 for UTF-16 with byte order mark, 2 for
+///   UTF-16BE, 3 for UTF-8
+/// * `text` - The frame after its encoding byte
+fn decode_id3_text(encoding: u8, text: &[u8]) -> Vec<String> {
+    match encoding {
+        0 => text
+            .split(|&byte| byte == 0)
+            .map(|part| part.iter().map(|&byte| byte as char).collect())
+            .collect(),
+        1 | 2 => {
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|pair| [pair[0], pair[1]])
+                .map(u16::from_be_bytes)
+                .collect();
+            units
+                .split(|&unit| unit == 0)
+                .map(|part| {
+                    // A byte order mark starts each string in encoding 1
+                    let (part, little_endian) = match part.first() {
+                        Some(0xfffe) => (&part[1..], true),
+                        Some(0xfeff) => (&part[1..], false),
+                        _ => (part, false),
+                    };
+                    let units = part.iter().map(|&unit| {
+                        if little_endian {
+                            unit.swap_bytes()
+                        } else {
+                            unit
+                        }
+                    });
+                    char::decode_utf16(units)
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect()
+                })
+                .collect()
+        }
+        _ => text
+            .split(|&byte| byte == 0)
+            .map(|part| String::from_utf8_lossy(part).into_owned())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Encodes a 28-bit size in four 7-bit bytes
+    fn syncsafe_bytes(size: usize) -> [u8; 4] {
+        [21, 14, 7, 0].map(|shift| ((size >> shift) & 0x7f) as u8)
+    }
+
+    /// Builds an ID3v2 tag of the given version from `(id, body)` frames
+    fn id3_tag(version: u8, frames: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (id, frame) in frames {
+            body.extend_from_slice(*id);
+            if version == 4 {
+                body.extend_from_slice(&syncsafe_bytes(frame.len()));
+            } else {
+                body.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            }
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(frame);
+        }
+        let mut tag = vec![b'I', b'D', b'3', version, 0, 0];
+        tag.extend_from_slice(&syncsafe_bytes(body.len()));
+        tag.extend(body);
+        tag
+    }
+
+    /// Builds the body of a UTF-8 `TXXX` frame
+    fn txxx(description: &str, value: &str) -> Vec<u8> {
+        [&[3], description.as_bytes(), &[0], value.as_bytes()].concat()
+    }
+
+    /// Builds a RIFF chunk, padded to an even length
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    #[test]
+    fn reads_replay_gain_from_id3v2_3_and_v2_4() {
+        // A frame longer than 127 bytes tells syncsafe and plain sizes apart
+        let frames = [
+            (b"COMM", vec![0; 200]),
+            (b"TXXX", txxx("REPLAYGAIN_TRACK_GAIN", "-6.50 dB")),
+            (b"TXXX", txxx("replaygain_track_peak", "0.988547")),
+        ];
+        for version in [3, 4] {
+            let mut replay_gain = ReplayGain::default();
+            assert!(read_id3_replay_gain(&id3_tag(version, &frames), &mut replay_gain).is_some());
+            assert_eq!(replay_gain.track_gain_db, Some(-6.5));
+            assert_eq!(replay_gain.track_peak, Some(0.988547));
+        }
+
+        // Other versions and unsynchronised tags are not read
+        let mut replay_gain = ReplayGain::default();
+        let mut tag = id3_tag(4, &frames);
+        tag[3] = 2;
+        assert!(read_id3_replay_gain(&tag, &mut replay_gain).is_none());
+        tag[3] = 4;
+        tag[5] = 0x80;
+        assert!(read_id3_replay_gain(&tag, &mut replay_gain).is_none());
+        assert_eq!(replay_gain, ReplayGain::default());
+    }
+
+    #[test]
+    fn decodes_id3_text_in_every_encoding() {
+        assert_eq!(decode_id3_text(0, b"caf\xe9\0x"), ["café", "x"]);
+        assert_eq!(decode_id3_text(3, "café\0x".as_bytes()), ["café", "x"]);
+
+        let utf16 = |text: &str, little_endian: bool| -> Vec<u8> {
+            text.encode_utf16()
+                .flat_map(|unit| {
+                    if little_endian {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect()
+        };
+        // Each string of encoding 1 starts with its own byte order mark
+        let text = [
+            &[0xff, 0xfe][..],
+            &utf16("Gain", true),
+            &[0, 0],
+            &[0xfe, 0xff],
+            &utf16("-3 dB", false),
+        ]
+        .concat();
+        assert_eq!(decode_id3_text(1, &text), ["Gain", "-3 dB"]);
+        let text = [utf16("Gain", false), vec![0, 0], utf16("é", false)].concat();
+        assert_eq!(decode_id3_text(2, &text), ["Gain", "é"]);
+    }
+
+    #[test]
+    fn reads_the_album_and_replay_gain_of_a_wav_file() {
+        let path = std::env::temp_dir().join(format!("song_tags_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // Append the tags after the audio, as tagging tools do
+        let info = [
+            &b"INFO"[..],
+            &chunk(b"INAM", b"Title\0"),
+            &chunk(b"IPRD", b"Album Name\0"),
+        ]
+        .concat();
+        let id3 = id3_tag(4, &[(b"TXXX", txxx("REPLAYGAIN_ALBUM_GAIN", "+1.25 dB"))]);
+        let mut file = fs::read(&path).unwrap();
+        file.extend(chunk(b"LIST", &info));
+        file.extend(chunk(b"id3 ", &id3));
+        let riff_size = (file.len() - 8) as u32;
+        file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        fs::write(&path, file).unwrap();
+
+        let audio = Song::decode(&path);
+        let _ = fs::remove_file(&path);
+        let audio = audio.unwrap();
+        assert_eq!(audio.samples().len(), 100);
+        assert_eq!(audio.album(), Some("Album Name"));
+        assert_eq!(audio.replay_gain().album_gain_db, Some(1.25));
+        assert_eq!(audio.replay_gain().track_gain_db, None);
     }
 }
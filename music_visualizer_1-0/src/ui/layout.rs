@@ -0,0 +1,158 @@
+//! Layout helper
+//!
+//! Cuts widget rectangles off an area one after another, either from the top
+//! down (vertical) or from the left across (horizontal).
+
+use nannou::prelude::*;
+
+/// Direction in which a layout places its widgets
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+/// Hands out consecutive rectangles from an area
+pub struct Layout {
+    /// Part of the area that has not been handed out yet
+    remaining: Rect,
+    /// Direction widgets are placed in
+    direction: Direction,
+    /// Gap left between consecutive widgets, in pixels
+    spacing: f32,
+}
+
+impl Layout {
+    /// Creates a layout that stacks widgets from the top of an area down
+    ///
+    /// # Arguments
+    /// * `area` - The rectangle to fill
+    pub fn vertical(area: Rect) -> Self {
+        Layout {
+            remaining: area,
+            direction: Direction::Vertical,
+            spacing: 0.0,
+        }
+    }
+
+    /// Creates a layout that places widgets from the left of an area across
+    ///
+    /// # Arguments
+    /// * `area` - The rectangle to fill
+    pub fn horizontal(area: Rect) -> Self {
+        Layout {
+            remaining: area,
+            direction: Direction::Horizontal,
+            spacing: 0.0,
+        }
+    }
+
+    /// Sets the gap left between consecutive widgets
+    pub fn spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Takes the next widget rectangle
+    ///
+    /// # Arguments
+    /// * `size` - Height (vertical) or width (horizontal) of the widget,
+    ///   clamped to the space that is left
+    ///
+    /// # Returns
+    /// The widget rectangle, spanning the full width (vertical) or height
+    /// (horizontal) of the area
+    pub fn next(&mut self, size: f32) -> Rect {
+        let area = self.remaining;
+        match self.direction {
+            Direction::Vertical => {
+                let size = size.min(area.h());
+                let used = (size + self.spacing).min(area.h());
+                self.remaining = area.pad_top(used);
+                Rect::from_x_y_w_h(area.x(), area.top() - size / 2.0, area.w(), size)
+            }
+            Direction::Horizontal => {
+                let size = size.min(area.w());
+                let used = (size + self.spacing).min(area.w());
+                self.remaining = area.pad_left(used);
+                Rect::from_x_y_w_h(area.left() + size / 2.0, area.y(), size, area.h())
+            }
+        }
+    }
+
+    /// Skips a gap without placing a widget
+    pub fn skip(&mut self, size: f32) {
+        self.next(size);
+    }
+
+    /// Splits the remaining space into equally sized widget rectangles
+    ///
+    /// # Arguments
+    /// * `count` - Number of rectangles
+    pub fn split(&mut self, count: usize) -> Vec<Rect> {
+        let total = match self.direction {
+            Direction::Vertical => self.remaining.h(),
+            Direction::Horizontal => self.remaining.w(),
+        };
+        let gaps = self.spacing * count.saturating_sub(1) as f32;
+        let size = ((total - gaps) / count.max(1) as f32).max(0.0);
+        (0..count).map(|_| self.next(size)).collect()
+    }
+
+    /// Returns the space that has not been handed out yet
+    pub fn rest(&self) -> Rect {
+        self.remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 × 200 area from (0, 0) to (100, 200)
+    fn area() -> Rect {
+        Rect::from_x_y_w_h(50.0, 100.0, 100.0, 200.0)
+    }
+
+    #[test]
+    fn vertical_layout_stacks_from_the_top_with_spacing() {
+        let mut layout = Layout::vertical(area()).spacing(10.0);
+        let first = layout.next(30.0);
+        let second = layout.next(20.0);
+
+        assert_eq!((first.top(), first.bottom()), (200.0, 170.0));
+        assert_eq!((first.left(), first.w()), (0.0, 100.0));
+        assert_eq!((second.top(), second.bottom()), (160.0, 140.0));
+        assert_eq!(layout.rest().top(), 130.0);
+        assert_eq!(layout.rest().bottom(), 0.0);
+    }
+
+    #[test]
+    fn widgets_are_clamped_to_the_space_left() {
+        let mut layout = Layout::vertical(area()).spacing(10.0);
+        layout.next(190.0);
+        let last = layout.next(50.0);
+        assert_eq!(last.h(), 0.0);
+        assert_eq!(layout.rest().h(), 0.0);
+    }
+
+    #[test]
+    fn horizontal_split_shares_the_width_between_gaps() {
+        let mut layout = Layout::horizontal(area()).spacing(5.0);
+        layout.skip(10.0);
+        let rects = layout.split(3);
+
+        assert_eq!(rects.len(), 3);
+        let widths: Vec<f32> = rects.iter().map(Rect::w).collect();
+        assert_eq!(widths, vec![25.0, 25.0, 25.0]);
+        assert_eq!(rects[0].left(), 15.0);
+        assert_eq!(rects[1].left(), 45.0);
+        assert_eq!(rects[2].right(), 100.0);
+        assert!(rects.iter().all(|rect| rect.h() == 200.0));
+    }
+
+    #[test]
+    fn splitting_into_nothing_gives_no_rects() {
+        assert!(Layout::vertical(area()).split(0).is_empty());
+    }
+}
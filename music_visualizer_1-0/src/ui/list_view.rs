@@ -0,0 +1,349 @@
+//! Scrollable list view
+//!
+//! Displays items as a column of fixed-height rows:
+//! - Rows highlight under the mouse and can be clicked
+//! - A selection cursor can be moved with the keyboard
+//! - The list scrolls with the mouse wheel and keyboard
+//!
+//! Only rows inside the visible area are laid out and drawn, so the cost per
+//! frame does not depend on the size of the library.
+
+use super::Input;
+use nannou::prelude::*;
+
+/// Height of a single row in pixels
+const ROW_HEIGHT: f32 = 36.0;
+/// Rows scrolled per mouse wheel notch
+const ROWS_PER_WHEEL_LINE: f32 = 3.0;
+/// Width of the scrollbar in pixels
+const SCROLLBAR_WIDTH: f32 = 6.0;
+/// Font size of row titles
+const FONT_SIZE: u32 = 16;
+
+/// Virtualized list with hover, cursor and scrolling
+///
+/// Unlike the other widgets the list keeps its scroll offset and cursor
+/// between frames, so it is owned by the screen that shows it. The items
+/// themselves are not stored; callers pass the row count and a label lookup.
+pub struct ListView {
+    /// Rectangle the rows are drawn in
+    list_rect: Rect,
+    /// Distance scrolled from the top of the list, in pixels
+    scroll_offset: f32,
+    /// Row highlighted by the keyboard selection cursor
+    cursor: usize,
+    /// Row currently under the mouse
+    hovered: Option<usize>,
+}
+
+impl ListView {
+    /// Creates a new ListView scrolled to the top
+    ///
+    /// # Arguments
+    /// * `list_rect` - The bounding rectangle for the rows
+    pub fn new(list_rect: Rect) -> Self {
+        ListView {
+            list_rect,
+            scroll_offset: 0.0,
+            cursor: 0,
+            hovered: None,
+        }
+    }
+
+    /// Moves the list to a new area, keeping the scroll offset in range
+    ///
+    /// # Arguments
+    /// * `list_rect` - The new bounding rectangle for the rows
+    /// * `count` - Number of items in the list
+    pub fn set_rect(&mut self, list_rect: Rect, count: usize) {
+        self.list_rect = list_rect;
+        self.scroll_to(self.scroll_offset, count);
+    }
+
+    /// Scrolls back to the top and moves the cursor to the first row
+    ///
+    /// Used when the items change completely, e.g. after filtering.
+    pub fn reset(&mut self) {
+        self.scroll_offset = 0.0;
+        self.cursor = 0;
+        self.hovered = None;
+    }
+
+    /// Tracks the mouse and handles clicks
+    ///
+    /// # Arguments
+    /// * `input` - Mouse state of the current frame
+    /// * `count` - Number of items in the list
+    ///
+    /// # Returns
+    /// Index of the row that was clicked, if any
+    pub fn update(&mut self, input: &Input, count: usize) -> Option<usize> {
+        self.hovered = self.row_at(input.mouse).filter(|&row| row < count);
+
+        let clicked_row = self.hovered.filter(|_| input.clicked)?;
+        self.cursor = clicked_row;
+        Some(clicked_row)
+    }
+
+    /// Scrolls the list with the mouse wheel if the mouse is over it
+    ///
+    /// # Arguments
+    /// * `mouse` - Current mouse position
+    /// * `delta` - Wheel movement reported by the window
+    /// * `count` - Number of items in the list
+    pub fn scroll_wheel(&mut self, mouse: Point2, delta: MouseScrollDelta, count: usize) {
+        if !self.list_rect.contains(mouse) {
+            return;
+        }
+        let pixels = match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines * ROWS_PER_WHEEL_LINE * ROW_HEIGHT,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32,
+        };
+        // Wheel up (positive) moves towards the top of the list
+        self.scroll_to(self.scroll_offset - pixels, count);
+    }
+
+    /// Returns whether a key is used by `key_pressed`
+    pub fn handles(key: Key) -> bool {
+        matches!(
+            key,
+            Key::Up | Key::Down | Key::PageUp | Key::PageDown | Key::Home | Key::End | Key::Return
+        )
+    }
+
+    /// Moves the cursor with the keyboard
+    ///
+    /// Handles up/down, page up/down, home/end and enter.
+    ///
+    /// # Arguments
+    /// * `key` - Key that was pressed
+    /// * `count` - Number of items in the list
+    ///
+    /// # Returns
+    /// Index of the row under the cursor when enter is pressed
+    pub fn key_pressed(&mut self, key: Key, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+        let page = self.visible_rows().max(1);
+        let last = count - 1;
+
+        self.cursor = match key {
+            Key::Up => self.cursor.saturating_sub(1),
+            Key::Down => (self.cursor + 1).min(last),
+            Key::PageUp => self.cursor.saturating_sub(page),
+            Key::PageDown => (self.cursor + page).min(last),
+            Key::Home => 0,
+            Key::End => last,
+            Key::Return => return Some(self.cursor.min(last)),
+            _ => return None,
+        };
+
+        self.reveal_cursor(count);
+        None
+    }
+
+    /// Scrolls just enough to bring the cursor row into view
+    fn reveal_cursor(&mut self, count: usize) {
+        let top = self.cursor as f32 * ROW_HEIGHT;
+        let bottom = top + ROW_HEIGHT;
+        if top < self.scroll_offset {
+            self.scroll_to(top, count);
+        } else if bottom > self.scroll_offset + self.list_rect.h() {
+            self.scroll_to(bottom - self.list_rect.h(), count);
+        }
+    }
+
+    /// Sets the scroll offset, clamped so the list never scrolls past its ends
+    fn scroll_to(&mut self, offset: f32, count: usize) {
+        let max_offset = (count as f32 * ROW_HEIGHT - self.list_rect.h()).max(0.0);
+        self.scroll_offset = offset.clamp(0.0, max_offset);
+    }
+
+    /// Returns the number of rows that fit in the list area
+    fn visible_rows(&self) -> usize {
+        (self.list_rect.h() / ROW_HEIGHT) as usize
+    }
+
+    /// Returns the row under a point, ignoring rows past the end of the list
+    fn row_at(&self, point: Point2) -> Option<usize> {
+        if !self.list_rect.contains(point) {
+            return None;
+        }
+        let distance_from_top = self.list_rect.top() - point.y + self.scroll_offset;
+        Some((distance_from_top / ROW_HEIGHT) as usize)
+    }
+
+    /// Returns the rectangle of a row at the current scroll offset
+    fn row_rect(&self, row: usize) -> Rect {
+        let top = self.list_rect.top() + self.scroll_offset - row as f32 * ROW_HEIGHT;
+        Rect::from_x_y_w_h(
+            self.list_rect.x() - SCROLLBAR_WIDTH / 2.0,
+            top - ROW_HEIGHT / 2.0,
+            self.list_rect.w() - SCROLLBAR_WIDTH,
+            ROW_HEIGHT,
+        )
+    }
+
+    /// Renders the visible rows and the scrollbar
+    ///
+    /// Draws:
+    /// - Row backgrounds (cursor and hover highlights)
+    /// - Row titles, shortened to fit
+    /// - Scrollbar thumb when the list is longer than the visible area
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `count` - Number of items in the list
+    /// * `label` - Returns the text of a row; only called for visible rows
+    pub fn draw<'a>(&self, draw: &Draw, count: usize, label: impl Fn(usize) -> &'a str) {
+        let first = (self.scroll_offset / ROW_HEIGHT) as usize;
+        // One extra row covers the partially visible row at the bottom
+        let last = (first + self.visible_rows() + 1).min(count);
+        let max_chars = (self.list_rect.w() / (FONT_SIZE as f32 * 0.55)) as usize;
+
+        for row in first..last {
+            // Clip rows that stick out above or below the list area
+            let Some(rect) = self.row_rect(row).overlap(self.list_rect) else {
+                continue;
+            };
+
+            let background = if self.hovered == Some(row) {
+                Some(rgb(0.3, 0.3, 0.35))
+            } else if self.cursor == row {
+                Some(rgb(0.2, 0.2, 0.3))
+            } else {
+                None
+            };
+            if let Some(color) = background {
+                draw.rect().xy(rect.xy()).wh(rect.wh()).color(color);
+            }
+
+            // Skip text for rows that are mostly clipped
+            if rect.h() >= ROW_HEIGHT / 2.0 {
+                draw.text(&shorten(label(row), max_chars))
+                    .xy(rect.xy())
+                    .wh(rect.pad_left(8.0).wh())
+                    .left_justify()
+                    .no_line_wrap()
+                    .color(WHITE)
+                    .font_size(FONT_SIZE);
+            }
+        }
+
+        // Draw scrollbar thumb sized to the visible fraction of the list
+        let content_height = count as f32 * ROW_HEIGHT;
+        if content_height > self.list_rect.h() {
+            let visible_fraction = self.list_rect.h() / content_height;
+            let thumb_height = (self.list_rect.h() * visible_fraction).max(20.0);
+            let travel = self.list_rect.h() - thumb_height;
+            let scrolled_fraction = self.scroll_offset / (content_height - self.list_rect.h());
+            draw.rect()
+                .x_y(
+                    self.list_rect.right() - SCROLLBAR_WIDTH / 2.0,
+                    self.list_rect.top() - thumb_height / 2.0 - travel * scrolled_fraction,
+                )
+                .w_h(SCROLLBAR_WIDTH, thumb_height)
+                .color(rgb(0.5, 0.5, 0.5));
+        }
+    }
+}
+
+/// Shortens a title to a number of characters, ending it with "..." if cut
+fn shorten(title: &str, max_chars: usize) -> String {
+    if title.chars().count() <= max_chars {
+        title.to_string()
+    } else {
+        let kept: String = title.chars().take(max_chars.saturating_sub(3)).collect();
+        format!("{}...", kept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// List five rows high, from y = 0 to y = 180
+    fn list() -> ListView {
+        ListView::new(Rect::from_x_y_w_h(100.0, 90.0, 200.0, 5.0 * ROW_HEIGHT))
+    }
+
+    #[test]
+    fn rows_are_counted_from_the_top_and_follow_the_scroll_offset() {
+        let mut list = list();
+        assert_eq!(list.row_at(pt2(100.0, 179.0)), Some(0));
+        assert_eq!(list.row_at(pt2(100.0, 180.0 - ROW_HEIGHT - 1.0)), Some(1));
+        assert_eq!(list.row_at(pt2(100.0, 1.0)), Some(4));
+        assert_eq!(list.row_at(pt2(300.0, 90.0)), None);
+
+        list.scroll_to(ROW_HEIGHT * 2.5, 20);
+        assert_eq!(list.row_at(pt2(100.0, 179.0)), Some(2));
+        assert_eq!(
+            list.row_at(pt2(100.0, 180.0 - ROW_HEIGHT / 2.0 - 1.0)),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn clicks_past_the_last_item_are_ignored() {
+        let mut list = list();
+        let click_row = |row: usize| {
+            let y = 180.0 - (row as f32 + 0.5) * ROW_HEIGHT;
+            Input::new(pt2(100.0, y), true, false)
+        };
+        assert_eq!(list.update(&click_row(1), 3), Some(1));
+        assert_eq!(list.cursor, 1);
+        assert_eq!(list.update(&click_row(3), 3), None);
+    }
+
+    #[test]
+    fn scrolling_stops_at_both_ends() {
+        let mut list = list();
+        list.scroll_to(-50.0, 20);
+        assert_eq!(list.scroll_offset, 0.0);
+        list.scroll_to(10_000.0, 20);
+        assert_eq!(list.scroll_offset, 15.0 * ROW_HEIGHT);
+        // A list shorter than its area never scrolls
+        list.scroll_to(100.0, 3);
+        assert_eq!(list.scroll_offset, 0.0);
+    }
+
+    #[test]
+    fn the_wheel_scrolls_only_over_the_list() {
+        let mut list = list();
+        let down = MouseScrollDelta::LineDelta(0.0, -1.0);
+        list.scroll_wheel(pt2(100.0, 90.0), down, 20);
+        assert_eq!(list.scroll_offset, ROWS_PER_WHEEL_LINE * ROW_HEIGHT);
+        list.scroll_wheel(pt2(500.0, 90.0), down, 20);
+        assert_eq!(list.scroll_offset, ROWS_PER_WHEEL_LINE * ROW_HEIGHT);
+    }
+
+    #[test]
+    fn keys_move_the_cursor_and_keep_it_in_view() {
+        let mut list = list();
+        assert_eq!(list.key_pressed(Key::Up, 20), None);
+        assert_eq!(list.cursor, 0);
+
+        list.key_pressed(Key::PageDown, 20);
+        assert_eq!(list.cursor, 5);
+        // Row 5 is just below the visible rows, so the list scrolls one row
+        assert_eq!(list.scroll_offset, ROW_HEIGHT);
+
+        list.key_pressed(Key::End, 20);
+        assert_eq!(list.cursor, 19);
+        assert_eq!(list.scroll_offset, 15.0 * ROW_HEIGHT);
+
+        list.key_pressed(Key::Home, 20);
+        assert_eq!((list.cursor, list.scroll_offset), (0, 0.0));
+
+        list.key_pressed(Key::Down, 20);
+        assert_eq!(list.key_pressed(Key::Return, 20), Some(1));
+        assert_eq!(list.key_pressed(Key::Return, 0), None);
+    }
+
+    #[test]
+    fn long_titles_are_shortened_with_an_ellipsis() {
+        assert_eq!(shorten("Short", 10), "Short");
+        assert_eq!(shorten("A rather long title", 10), "A rathe...");
+    }
+}
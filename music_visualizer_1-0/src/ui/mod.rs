@@ -0,0 +1,161 @@
+//! Immediate-mode widget toolkit for the menu
+//!
+//! Widgets are cheap values rebuilt every frame from the current state:
+//! - Update code asks a widget whether it was clicked or dragged
+//! - View code draws the same widget with its hover/pressed/disabled look
+//! - `Layout` hands out the widget rectangles from the menu area
+//!
+//! Both sides read the mouse from an `Input` snapshot, and hit-testing only
+//! uses plain `Rect` geometry, so widget behavior does not depend on a window.
+//! `ListView` and `TextInput` keep a little state between frames (scroll
+//! offset, typed text) and are owned by the screen that shows them.
+
+mod layout;
+mod list_view;
+mod text_input;
+mod widgets;
+
+pub use layout::Layout;
+pub use list_view::ListView;
+pub use text_input::TextInput;
+pub use widgets::{Button, Graph, Label, Meter, Slider, Toggle};
+
+use nannou::prelude::*;
+
+/// Mouse state of a single frame
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Input {
+    /// Mouse position in window coordinates
+    pub mouse: Point2,
+    /// True while a mouse button is held
+    pub is_down: bool,
+    /// True only on the frame a mouse button was first pressed
+    pub clicked: bool,
+}
+
+impl Input {
+    /// Creates an input snapshot
+    ///
+    /// # Arguments
+    /// * `mouse` - Mouse position in window coordinates
+    /// * `is_down` - Whether a mouse button is held this frame
+    /// * `was_down` - Whether a mouse button was held the previous frame
+    pub fn new(mouse: Point2, is_down: bool, was_down: bool) -> Self {
+        Input {
+            mouse,
+            is_down,
+            clicked: is_down && !was_down,
+        }
+    }
+
+    /// Returns whether the mouse is over a rectangle
+    pub fn hovers(&self, rect: Rect) -> bool {
+        rect.contains(self.mouse)
+    }
+
+    /// Returns whether a rectangle was clicked this frame
+    pub fn clicked_in(&self, rect: Rect) -> bool {
+        self.clicked && self.hovers(rect)
+    }
+}
+
+/// Interaction state of a widget, deciding how it is drawn
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WidgetState {
+    Normal,
+    Hovered,
+    Pressed,
+    Disabled,
+}
+
+impl WidgetState {
+    /// Works out the state of a widget from the mouse
+    ///
+    /// # Arguments
+    /// * `rect` - Bounds of the widget
+    /// * `input` - Mouse state of the current frame
+    /// * `enabled` - False if the widget cannot be used right now
+    pub fn of(rect: Rect, input: &Input, enabled: bool) -> Self {
+        if !enabled {
+            WidgetState::Disabled
+        } else if input.hovers(rect) && input.is_down {
+            WidgetState::Pressed
+        } else if input.hovers(rect) {
+            WidgetState::Hovered
+        } else {
+            WidgetState::Normal
+        }
+    }
+
+    /// Returns the fill color for a widget with the given base color
+    pub fn fill(self, base: Rgb) -> Rgb {
+        let scale = |amount: f32| {
+            rgb(
+                (base.red * amount).min(1.0),
+                (base.green * amount).min(1.0),
+                (base.blue * amount).min(1.0),
+            )
+        };
+        match self {
+            WidgetState::Normal => base,
+            WidgetState::Hovered => scale(1.3),
+            WidgetState::Pressed => scale(0.7),
+            WidgetState::Disabled => rgb(0.18, 0.18, 0.18),
+        }
+    }
+
+    /// Returns the text color for a widget in this state
+    pub fn text_color(self) -> Rgb {
+        match self {
+            WidgetState::Disabled => rgb(0.45, 0.45, 0.45),
+            _ => rgb(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_only_on_the_frame_the_button_goes_down() {
+        let mouse = pt2(0.0, 0.0);
+        assert!(Input::new(mouse, true, false).clicked);
+        assert!(!Input::new(mouse, true, true).clicked);
+        assert!(!Input::new(mouse, false, true).clicked);
+    }
+
+    #[test]
+    fn hit_testing_includes_the_edges() {
+        let rect = Rect::from_x_y_w_h(10.0, 20.0, 40.0, 20.0);
+        let input = |x, y| Input::new(pt2(x, y), true, false);
+        assert!(input(10.0, 20.0).hovers(rect));
+        assert!(input(-10.0, 10.0).hovers(rect));
+        assert!(input(30.0, 30.0).hovers(rect));
+        assert!(!input(30.1, 20.0).hovers(rect));
+        assert!(!input(10.0, 9.9).hovers(rect));
+    }
+
+    #[test]
+    fn clicked_in_needs_a_click_over_the_rect() {
+        let rect = Rect::from_x_y_w_h(0.0, 0.0, 10.0, 10.0);
+        assert!(Input::new(pt2(1.0, 1.0), true, false).clicked_in(rect));
+        assert!(!Input::new(pt2(1.0, 1.0), true, true).clicked_in(rect));
+        assert!(!Input::new(pt2(20.0, 1.0), true, false).clicked_in(rect));
+    }
+
+    #[test]
+    fn widget_state_follows_the_mouse() {
+        let rect = Rect::from_x_y_w_h(0.0, 0.0, 10.0, 10.0);
+        let over = Input::new(pt2(0.0, 0.0), false, false);
+        let pressed = Input::new(pt2(0.0, 0.0), true, false);
+        let away = Input::new(pt2(50.0, 0.0), true, false);
+        assert_eq!(WidgetState::of(rect, &over, true), WidgetState::Hovered);
+        assert_eq!(WidgetState::of(rect, &pressed, true), WidgetState::Pressed);
+        assert_eq!(WidgetState::of(rect, &away, true), WidgetState::Normal);
+        assert_eq!(
+            WidgetState::of(rect, &pressed, false),
+            WidgetState::Disabled
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Single-line text input
+//!
+//! Clicking the field focuses it; clicking anywhere else blurs it. While
+//! focused it consumes typed characters and every key except those used to
+//! move through a list of results (arrows, page up/down, enter) and Tab.
+
+use super::{Input, WidgetState};
+use nannou::prelude::*;
+
+/// Editable line of text that keeps its contents between frames
+pub struct TextInput {
+    /// Current contents
+    text: String,
+    /// Text shown while the field is empty
+    placeholder: &'static str,
+    /// True while typed characters go into the field
+    focused: bool,
+    /// Set when focused by a shortcut key, whose character must not be typed
+    skip_next_character: bool,
+}
+
+impl TextInput {
+    /// Creates an empty, unfocused text input
+    ///
+    /// # Arguments
+    /// * `placeholder` - Text shown while the field is empty
+    pub fn new(placeholder: &'static str) -> Self {
+        TextInput {
+            text: String::new(),
+            placeholder,
+            focused: false,
+            skip_next_character: false,
+        }
+    }
+
+    /// Returns the current contents
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Focuses the field from a keyboard shortcut
+    ///
+    /// The character of the shortcut key arrives after the key press, so it
+    /// is swallowed instead of typed.
+    pub fn focus(&mut self) {
+        self.focused = true;
+        self.skip_next_character = true;
+    }
+
+    /// Focuses the field when it is clicked and blurs it on clicks elsewhere
+    ///
+    /// # Arguments
+    /// * `rect` - Bounds of the field
+    /// * `input` - Mouse state of the current frame
+    pub fn update(&mut self, rect: Rect, input: &Input) {
+        if input.clicked {
+            self.focused = input.hovers(rect);
+        }
+    }
+
+    /// Edits the text from keyboard events while focused
+    ///
+    /// # Arguments
+    /// * `event` - The window event to handle
+    ///
+    /// # Returns
+    /// Whether the event was consumed by the field; `true` also means the
+    /// text may have changed
+    pub fn event(&mut self, event: &WindowEvent) -> bool {
+        if !self.focused {
+            return false;
+        }
+        match event {
+            ReceivedCharacter(_) if self.skip_next_character => self.skip_next_character = false,
+            ReceivedCharacter(c) if !c.is_control() => self.text.push(*c),
+            KeyPressed(Key::Back) => {
+                self.text.pop();
+            }
+            KeyPressed(Key::Escape) => self.focused = false,
+            // Keys for moving through results still reach the rest of the menu
+            KeyPressed(
+                Key::Up | Key::Down | Key::PageUp | Key::PageDown | Key::Return | Key::Tab,
+            ) => return false,
+            // Everything else is typing, not a shortcut
+            KeyPressed(_) | KeyReleased(_) => {}
+            // Control characters arrive alongside the matching key press
+            ReceivedCharacter(_) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Renders the field with its text, or the placeholder while empty
+    pub fn draw(&self, draw: &Draw, rect: Rect, input: &Input) {
+        let state = WidgetState::of(rect, input, true);
+        let border = if self.focused {
+            rgb(0.4, 0.6, 1.0)
+        } else {
+            rgb(0.3, 0.3, 0.3)
+        };
+
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(state.fill(rgb(0.15, 0.15, 0.15)))
+            .stroke(border)
+            .stroke_weight(1.0);
+
+        let (text, color) = if self.text.is_empty() && !self.focused {
+            (self.placeholder.to_string(), rgb(0.5, 0.5, 0.5))
+        } else if self.focused {
+            (format!("{}|", self.text), rgb(1.0, 1.0, 1.0))
+        } else {
+            (self.text.clone(), rgb(1.0, 1.0, 1.0))
+        };
+        draw.text(&text)
+            .xy(rect.xy())
+            .wh(rect.pad_left(6.0).wh())
+            .left_justify()
+            .no_line_wrap()
+            .color(color)
+            .font_size(14);
+    }
+}
@@ -0,0 +1,460 @@
+//! Stateless widgets
+//!
+//! Each widget is built with its rectangle and current value, asked about
+//! interaction during update, and rebuilt the same way to be drawn.
+
+use super::{Input, WidgetState};
+use nannou::prelude::*;
+
+/// Static text
+pub struct Label {
+    /// Bounds the text is centered in
+    rect: Rect,
+    /// Text to show
+    text: String,
+    /// Font size in points
+    font_size: u32,
+    /// Text color
+    color: Rgb,
+}
+
+impl Label {
+    /// Creates a white label with a 16pt font
+    ///
+    /// # Arguments
+    /// * `text` - Text to show
+    /// * `rect` - Bounds the text is centered in
+    pub fn new(text: impl Into<String>, rect: Rect) -> Self {
+        Label {
+            rect,
+            text: text.into(),
+            font_size: 16,
+            color: rgb(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Sets the font size
+    pub fn font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Sets the text color
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Renders the text
+    pub fn draw(&self, draw: &Draw) {
+        draw.text(&self.text)
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(self.color)
+            .font_size(self.font_size);
+    }
+}
+
+/// Push button that reports clicks
+pub struct Button {
+    /// Bounds of the button
+    pub rect: Rect,
+    /// Text shown on the button
+    label: String,
+    /// Identifies the button's action
+    pub tag: &'static str,
+    /// False if the button cannot be clicked right now
+    enabled: bool,
+    /// Fill color in the normal state
+    color: Rgb,
+    /// Font size of the label in points
+    font_size: u32,
+}
+
+impl Button {
+    /// Creates an enabled gray button
+    ///
+    /// # Arguments
+    /// * `label` - Text shown on the button
+    /// * `tag` - Identifies the button's action
+    /// * `rect` - Bounds of the button
+    pub fn new(label: impl Into<String>, tag: &'static str, rect: Rect) -> Self {
+        Button {
+            rect,
+            label: label.into(),
+            tag,
+            enabled: true,
+            color: rgb(0.3, 0.3, 0.3),
+            font_size: 16,
+        }
+    }
+
+    /// Enables or disables the button
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Sets the fill color in the normal state
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the font size of the label
+    pub fn font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Returns whether the button was clicked this frame
+    pub fn clicked(&self, input: &Input) -> bool {
+        self.enabled && input.clicked_in(self.rect)
+    }
+
+    /// Renders the button in its current state
+    pub fn draw(&self, draw: &Draw, input: &Input) {
+        let state = WidgetState::of(self.rect, input, self.enabled);
+        draw.rect()
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(state.fill(self.color));
+        draw.text(&self.label)
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(state.text_color())
+            .font_size(self.font_size);
+    }
+}
+
+/// Two-state button with a label and color per state
+pub struct Toggle {
+    /// Bounds of the toggle
+    rect: Rect,
+    /// Current state
+    on: bool,
+    /// Text shown while on
+    label_on: &'static str,
+    /// Text shown while off
+    label_off: &'static str,
+    /// False if the toggle cannot be switched right now
+    enabled: bool,
+}
+
+impl Toggle {
+    /// Creates an enabled toggle
+    ///
+    /// # Arguments
+    /// * `rect` - Bounds of the toggle
+    /// * `on` - Current state
+    /// * `label_on` - Text shown while on
+    /// * `label_off` - Text shown while off
+    pub fn new(rect: Rect, on: bool, label_on: &'static str, label_off: &'static str) -> Self {
+        Toggle {
+            rect,
+            on,
+            label_on,
+            label_off,
+            enabled: true,
+        }
+    }
+
+    /// Enables or disables the toggle
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Returns whether the toggle was switched this frame
+    pub fn toggled(&self, input: &Input) -> bool {
+        self.enabled && input.clicked_in(self.rect)
+    }
+
+    /// Renders the toggle, green while on and red while off
+    pub fn draw(&self, draw: &Draw, input: &Input) {
+        let state = WidgetState::of(self.rect, input, self.enabled);
+        let (base, label) = if self.on {
+            (rgb(0.0, 0.5, 0.0), self.label_on)
+        } else {
+            (rgb(1.0, 0.0, 0.0), self.label_off)
+        };
+        let text_color = if state == WidgetState::Disabled {
+            state.text_color()
+        } else {
+            rgb(0.0, 0.0, 0.0)
+        };
+
+        draw.rect()
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(state.fill(base));
+        draw.text(label)
+            .xy(self.rect.xy())
+            .color(text_color)
+            .font_size(24);
+    }
+}
+
+/// Horizontal slider over a range of values
+pub struct Slider {
+    /// Bounds of the slider
+    pub rect: Rect,
+    /// Text shown on top of the slider
+    label: String,
+    /// Current value
+    value: f32,
+    /// Lowest value
+    min: f32,
+    /// Highest value
+    max: f32,
+    /// Values snap to multiples of this above `min` (0.0 for no snapping)
+    step: f32,
+}
+
+impl Slider {
+    /// Creates a slider
+    ///
+    /// # Arguments
+    /// * `label` - Text shown on top of the slider
+    /// * `rect` - Bounds of the slider
+    /// * `value` - Current value
+    /// * `min` - Lowest value
+    /// * `max` - Highest value
+    pub fn new(label: impl Into<String>, rect: Rect, value: f32, min: f32, max: f32) -> Self {
+        Slider {
+            rect,
+            label: label.into(),
+            value,
+            min,
+            max,
+            step: 0.0,
+        }
+    }
+
+    /// Makes values snap to multiples of `step` above the minimum
+    pub fn step(mut self, step: f32) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Returns the value under the mouse while the slider is held
+    ///
+    /// # Returns
+    /// The new value if the mouse is pressed on the slider and the value
+    /// differs from the current one
+    pub fn dragged(&self, input: &Input) -> Option<f32> {
+        if !input.is_down || !input.hovers(self.rect) || self.max <= self.min {
+            return None;
+        }
+        let fraction = ((input.mouse.x - self.rect.left()) / self.rect.w()).clamp(0.0, 1.0);
+        let mut value = self.min + fraction * (self.max - self.min);
+        if self.step > 0.0 {
+            value = self.min + ((value - self.min) / self.step).round() * self.step;
+        }
+        let value = value.clamp(self.min, self.max);
+        (value != self.value).then_some(value)
+    }
+
+    /// Renders the track, the filled portion up to the value, and the label
+    pub fn draw(&self, draw: &Draw, input: &Input) {
+        let state = WidgetState::of(self.rect, input, true);
+        let fraction = if self.max > self.min {
+            ((self.value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let filled_width = self.rect.w() * fraction;
+
+        draw.rect()
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(state.fill(rgb(0.2, 0.2, 0.2)));
+        draw.rect()
+            .x_y(self.rect.left() + filled_width / 2.0, self.rect.y())
+            .w_h(filled_width, self.rect.h())
+            .color(state.fill(rgb(0.2, 0.35, 0.6)));
+        draw.text(&self.label)
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(state.text_color())
+            .font_size(14);
+    }
+}
+
+/// Line graph of values spread evenly from left to right
+pub struct Graph {
+    /// Bounds of the graph
+    rect: Rect,
+    /// Values from left to right
+    values: Vec<f32>,
+    /// Value at the bottom edge
+    min: f32,
+    /// Value at the top edge
+    max: f32,
+}
+
+impl Graph {
+    /// Creates a graph; values outside `min..=max` are drawn at the edges
+    ///
+    /// # Arguments
+    /// * `rect` - Bounds of the graph
+    /// * `values` - Values from left to right
+    /// * `min` - Value at the bottom edge
+    /// * `max` - Value at the top edge
+    pub fn new(rect: Rect, values: Vec<f32>, min: f32, max: f32) -> Self {
+        Graph {
+            rect,
+            values,
+            min,
+            max,
+        }
+    }
+
+    /// Renders the background, a line at zero if it is in range, and the values
+    pub fn draw(&self, draw: &Draw) {
+        draw.rect()
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(rgb(0.15, 0.15, 0.15));
+        if self.max <= self.min || self.values.is_empty() {
+            return;
+        }
+        let y_of = |value: f32| {
+            let fraction = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+            self.rect.bottom() + fraction * self.rect.h()
+        };
+        if (self.min..=self.max).contains(&0.0) {
+            draw.line()
+                .start(pt2(self.rect.left(), y_of(0.0)))
+                .end(pt2(self.rect.right(), y_of(0.0)))
+                .color(rgb(0.35, 0.35, 0.35))
+                .weight(1.0);
+        }
+        let step = self.rect.w() / (self.values.len().max(2) - 1) as f32;
+        let points = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| pt2(self.rect.left() + index as f32 * step, y_of(value)));
+        draw.polyline()
+            .weight(2.0)
+            .points(points)
+            .color(rgb(0.4, 0.6, 1.0));
+    }
+}
+
+/// Horizontal bar showing a level, with a label on top
+pub struct Meter {
+    /// Bounds of the bar
+    rect: Rect,
+    /// Text shown over the bar
+    label: String,
+    /// Filled part of the bar, from 0.0 (empty) to 1.0 (full)
+    fraction: f32,
+    /// Color of the filled part
+    color: Rgb,
+}
+
+impl Meter {
+    /// Creates a meter filled in green
+    ///
+    /// # Arguments
+    /// * `label` - Text shown over the bar
+    /// * `rect` - Bounds of the bar
+    /// * `value` - Level to show; values outside `0.0..=max` are drawn at the ends
+    /// * `max` - Level that fills the whole bar
+    pub fn new(label: impl Into<String>, rect: Rect, value: f32, max: f32) -> Self {
+        Meter {
+            rect,
+            label: label.into(),
+            fraction: if max > 0.0 {
+                (value / max).clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+            color: rgb(0.0, 0.5, 0.0),
+        }
+    }
+
+    /// Sets the color of the filled part
+    pub fn color(mut self, color: Rgb) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Renders the background, the filled part from the left and the label
+    pub fn draw(&self, draw: &Draw) {
+        draw.rect()
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(rgb(0.15, 0.15, 0.15));
+        if self.fraction > 0.0 {
+            let width = self.rect.w() * self.fraction;
+            draw.rect()
+                .x_y(self.rect.left() + width / 2.0, self.rect.y())
+                .w_h(width, self.rect.h())
+                .color(self.color);
+        }
+        draw.text(&self.label)
+            .xy(self.rect.xy())
+            .wh(self.rect.wh())
+            .color(rgb(1.0, 1.0, 1.0))
+            .font_size(12);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Slider from x = 0 to x = 100 around y = 0
+    fn slider(value: f32) -> Slider {
+        Slider::new(
+            "",
+            Rect::from_x_y_w_h(50.0, 0.0, 100.0, 20.0),
+            value,
+            0.0,
+            10.0,
+        )
+    }
+
+    fn pressed_at(x: f32, y: f32) -> Input {
+        Input::new(pt2(x, y), true, true)
+    }
+
+    #[test]
+    fn slider_maps_the_mouse_across_its_range() {
+        assert_eq!(slider(5.0).dragged(&pressed_at(0.0, 0.0)), Some(0.0));
+        assert_eq!(slider(5.0).dragged(&pressed_at(25.0, 5.0)), Some(2.5));
+        assert_eq!(slider(5.0).dragged(&pressed_at(100.0, -5.0)), Some(10.0));
+    }
+
+    #[test]
+    fn slider_snaps_to_its_step() {
+        let slider = slider(0.0).step(2.0);
+        assert_eq!(slider.dragged(&pressed_at(33.0, 0.0)), Some(4.0));
+        assert_eq!(slider.dragged(&pressed_at(95.0, 0.0)), Some(10.0));
+    }
+
+    #[test]
+    fn slider_ignores_released_outside_and_unchanged() {
+        let released = Input::new(pt2(50.0, 0.0), false, true);
+        assert_eq!(slider(2.0).dragged(&released), None);
+        assert_eq!(slider(2.0).dragged(&pressed_at(50.0, 11.0)), None);
+        assert_eq!(slider(5.0).dragged(&pressed_at(50.0, 0.0)), None);
+    }
+
+    #[test]
+    fn disabled_buttons_and_toggles_ignore_clicks() {
+        let rect = Rect::from_x_y_w_h(0.0, 0.0, 20.0, 20.0);
+        let click = Input::new(pt2(0.0, 0.0), true, false);
+        assert!(Button::new("", "tag", rect).clicked(&click));
+        assert!(!Button::new("", "tag", rect).enabled(false).clicked(&click));
+        assert!(Toggle::new(rect, false, "ON", "OFF").toggled(&click));
+        assert!(
+            !Toggle::new(rect, false, "ON", "OFF")
+                .enabled(false)
+                .toggled(&click)
+        );
+    }
+}
@@ -1,23 +1,41 @@
 //! Visualization module
 //!
-//! Handles the main display area that shows playback status through:
-//! - Color changes (green for playing, red for paused)
-//! - Text status indicators
-//! - Responsive layout based on assigned rectangle
+//! Handles the main display area, which shows one visualizer at a time:
+//! - Status (green for playing, red for paused)
+//! - Spectrogram (scrolling time/frequency heatmap)
+//! - Game of Life (cellular automaton seeded by the music)
+//! - Particles (audio-reactive particle system)
+//!
+//! Layout is responsive to the assigned rectangle. The view subscribes to
+//! the player's events and shows a notice over the visualizer while a song
+//! is loading or after it failed to load.
 
+use crate::player::{PlayerEvent, PlayerState};
+use crate::visualizer::{
+    AudioFrame, GameOfLife, Particles, Spectrogram, Status, Visualizer, VisualizerParam,
+};
 use nannou::prelude::*;
+use std::sync::mpsc::Receiver;
 
 /// Represents the main visualization view
 ///
 /// Manages:
-/// - Playback state visualization
+/// - The available visualizers and which one is active
 /// - Display area dimensions
-/// - Visual feedback rendering
+/// - Forwarding analysis data and parameter changes to the active visualizer
 pub struct View {
     /// Rectangle defining the view's bounds and position
     view_rect: Rect,
-    /// Current playback state (true when audio is playing)
-    is_playing: bool,
+    /// All visual modes, in the order they are cycled through
+    visualizers: Vec<Box<dyn Visualizer>>,
+    /// Index of the visualizer currently on screen
+    active: usize,
+    /// Events from the player
+    player_events: Receiver<PlayerEvent>,
+    /// Player state as of the last event
+    player_state: PlayerState,
+    /// Message of the last load failure
+    error: Option<String>,
 }
 
 impl View {
@@ -25,57 +43,102 @@ impl View {
     ///
     /// # Arguments
     /// * `view_rect` - The bounding rectangle for the view area
+    /// * `player_events` - Subscription to the player's events
     ///
-    /// Initializes with paused (red) state by default
-    pub fn new(view_rect: Rect) -> Self {
+    /// Starts on the status visualizer
+    pub fn new(view_rect: Rect, player_events: Receiver<PlayerEvent>) -> Self {
         View {
             view_rect,
-            is_playing: false,
+            visualizers: vec![
+                Box::new(Status::default()),
+                Box::new(Spectrogram::new()),
+                Box::new(GameOfLife::new()),
+                Box::new(Particles::new()),
+            ],
+            active: 0,
+            player_events,
+            player_state: PlayerState::Stopped,
+            error: None,
         }
     }
 
-    /// Updates the playback state
+    /// Moves the view to a new area, e.g. after the window was resized
     ///
     /// # Arguments
-    /// * `is_playing` - New playback state (true for playing, false for paused)
+    /// * `view_rect` - The new bounding rectangle for the view area
+    pub fn set_rect(&mut self, view_rect: Rect) {
+        self.view_rect = view_rect;
+    }
+
+    /// Applies the player's events and updates the active visualizer
     ///
-    /// This affects both the background color and status text
-    pub fn update(&mut self, is_playing: bool) {
-        self.is_playing = is_playing;
+    /// # Arguments
+    /// * `frame` - Analysis data for the current frame
+    pub fn update(&mut self, frame: &AudioFrame) {
+        for event in self.player_events.try_iter() {
+            match event {
+                PlayerEvent::StateChanged(state) => self.player_state = state,
+                PlayerEvent::Error(message) => self.error = Some(message),
+                PlayerEvent::TrackChanged(_) => self.error = None,
+                PlayerEvent::Seeked(_) => {}
+            }
+        }
+        self.visualizers[self.active].update(frame);
     }
 
-    /// Renders the visualization
+    /// Switches to the next visualizer, wrapping around at the end
+    pub fn next_visualizer(&mut self) {
+        self.active = (self.active + 1) % self.visualizers.len();
+    }
+
+    /// Returns the name of the active visualizer
+    pub fn visualizer_name(&self) -> &'static str {
+        self.visualizers[self.active].name()
+    }
+
+    /// Returns the tweakable parameters of the active visualizer
+    pub fn visualizer_params(&self) -> Vec<VisualizerParam> {
+        self.visualizers[self.active].params()
+    }
+
+    /// Changes a parameter of the active visualizer
     ///
-    /// Draws:
-    /// - Background rectangle with state-appropriate color
-    ///   - Green with 80% opacity when playing
-    ///   - Red with 80% opacity when paused
-    /// - Centered status text
-    ///   - "PLAYING" when active
-    ///   - "PAUSED" when inactive
+    /// # Arguments
+    /// * `name` - Parameter name as reported by `visualizer_params`
+    /// * `value` - New parameter value
+    pub fn set_visualizer_param(&mut self, name: &str, value: f32) {
+        self.visualizers[self.active].set_param(name, value);
+    }
+
+    /// Renders the active visualizer, with a notice while loading or after
+    /// a load failed
     ///
     /// # Arguments
     /// * `draw` - Nannou Draw context for rendering
     pub fn draw(&self, draw: &Draw) {
-        // Set color based on playback state
-        let bg_color = if self.is_playing {
-            rgba(0.0, 0.5, 0.0, 0.8) // Green when playing
-        } else {
-            rgba(0.5, 0.0, 0.0, 0.8) // Red when paused
-        };
+        self.visualizers[self.active].draw(draw, self.view_rect);
 
-        // Draw background
-        draw.rect()
-            .xy(self.view_rect.xy())
-            .wh(self.view_rect.wh())
-            .color(bg_color);
-
-        // Add status text overlay
-        let status_text = if self.is_playing { "PLAYING" } else { "PAUSED" };
-
-        draw.text(status_text)
-            .xy(self.view_rect.xy())
-            .color(WHITE)
-            .font_size(48);
+        let notice = match (self.player_state, &self.error) {
+            (PlayerState::Loading, _) => Some(("Loading...", rgb(1.0, 1.0, 1.0))),
+            (PlayerState::Error, Some(message)) => Some((message.as_str(), rgb(1.0, 0.4, 0.4))),
+            _ => None,
+        };
+        if let Some((text, color)) = notice {
+            let rect = Rect::from_x_y_w_h(
+                self.view_rect.x(),
+                self.view_rect.top() - 30.0,
+                self.view_rect.w(),
+                40.0,
+            );
+            draw.rect()
+                .xy(rect.xy())
+                .wh(rect.wh())
+                .color(rgba(0.0, 0.0, 0.0, 0.6));
+            draw.text(text)
+                .xy(rect.xy())
+                .wh(rect.wh())
+                .color(color)
+                .font_size(18);
+        }
     }
 }
@@ -0,0 +1,305 @@
+//! Game of Life visualizer
+//!
+//! Runs a cellular automaton on a wrapping grid that is driven by the music:
+//! - Onsets drop gliders at random positions
+//! - Each spectral band feeds a vertical strip of the grid, sprinkling live
+//!   cells in proportion to its energy
+//! - Generations advance in step with the tempo
+//!
+//! The rule (Conway, HighLife, Day & Night) and the color palette are
+//! selectable through the visualizer parameters.
+
+use super::{AudioFrame, Visualizer, VisualizerParam};
+use nannou::prelude::*;
+
+/// Number of cells across the grid
+const GRID_WIDTH: usize = 96;
+/// Number of cells down the grid
+const GRID_HEIGHT: usize = 64;
+/// Number of spectral bands, each mapped to a vertical strip of the grid
+const BANDS: usize = 8;
+/// Lowest frequency fed into the band mapping, in Hz
+const LOWEST_BAND_FREQUENCY: f32 = 40.0;
+/// Most cells a band at full energy can bring to life per frame
+const MAX_SEEDS_PER_BAND: f32 = 6.0;
+/// Tempo assumed until the beat tracker has an estimate
+const DEFAULT_BPM: f32 = 120.0;
+/// Age at which a cell reaches the end of the palette
+const MAX_AGE: u8 = 32;
+/// Relative (x, y) offsets of a glider's live cells, heading down-right
+const GLIDER: [(i32, i32); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+/// Birth/survival rules, stored as bit masks over the live neighbour count
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LifeRule {
+    /// B3/S23
+    Conway,
+    /// B36/S23, known for its self-replicators
+    HighLife,
+    /// B3678/S34678, symmetric between live and dead cells
+    DayAndNight,
+}
+
+impl LifeRule {
+    /// All rules in menu order
+    const ALL: [LifeRule; 3] = [LifeRule::Conway, LifeRule::HighLife, LifeRule::DayAndNight];
+    /// Menu labels matching `ALL`
+    const LABELS: &'static [&'static str] = &["Conway", "HighLife", "Day & Night"];
+
+    /// Returns the `(birth, survival)` masks, where bit `n` means `n` neighbours
+    fn masks(self) -> (u16, u16) {
+        let mask = |counts: &[u32]| counts.iter().fold(0u16, |mask, &n| mask | 1 << n);
+        match self {
+            LifeRule::Conway => (mask(&[3]), mask(&[2, 3])),
+            LifeRule::HighLife => (mask(&[3, 6]), mask(&[2, 3])),
+            LifeRule::DayAndNight => (mask(&[3, 6, 7, 8]), mask(&[3, 4, 6, 7, 8])),
+        }
+    }
+}
+
+/// Color palettes, running from newborn to long-lived cells
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LifePalette {
+    Neon,
+    Fire,
+    Ocean,
+    Mono,
+}
+
+impl LifePalette {
+    /// All palettes in menu order
+    const ALL: [LifePalette; 4] = [
+        LifePalette::Neon,
+        LifePalette::Fire,
+        LifePalette::Ocean,
+        LifePalette::Mono,
+    ];
+    /// Menu labels matching `ALL`
+    const LABELS: &'static [&'static str] = &["Neon", "Fire", "Ocean", "Mono"];
+
+    /// Returns the color of a cell
+    ///
+    /// # Arguments
+    /// * `age` - Generations the cell has been alive (1 = newborn)
+    fn color(self, age: u8) -> Rgba {
+        let t = age.min(MAX_AGE) as f32 / MAX_AGE as f32;
+        let (young, old) = match self {
+            LifePalette::Neon => ([0.2, 1.0, 0.9], [0.9, 0.1, 0.9]),
+            LifePalette::Fire => ([1.0, 1.0, 0.6], [0.6, 0.05, 0.0]),
+            LifePalette::Ocean => ([0.7, 1.0, 1.0], [0.0, 0.15, 0.5]),
+            LifePalette::Mono => ([1.0, 1.0, 1.0], [0.3, 0.3, 0.3]),
+        };
+        let mix = |c: usize| young[c] + (old[c] - young[c]) * t;
+        rgba(mix(0), mix(1), mix(2), 1.0)
+    }
+}
+
+/// Music-driven cellular automaton
+pub struct GameOfLife {
+    /// Age of each cell in row-major order (0 = dead)
+    cells: Vec<u8>,
+    /// Scratch buffer for the next generation
+    next_cells: Vec<u8>,
+    /// Active birth/survival rule
+    rule: LifeRule,
+    /// Active color palette
+    palette: LifePalette,
+    /// Generations per beat
+    ticks_per_beat: f32,
+    /// Multiplier on how strongly spectral energy seeds the grid
+    sensitivity: f32,
+    /// Fraction of a generation accumulated since the last tick
+    tick_progress: f32,
+    /// Playback time of the previous update, in seconds
+    last_time: Option<f64>,
+}
+
+impl GameOfLife {
+    /// Creates an empty grid using Conway's rules
+    pub fn new() -> Self {
+        GameOfLife {
+            cells: vec![0; GRID_WIDTH * GRID_HEIGHT],
+            next_cells: vec![0; GRID_WIDTH * GRID_HEIGHT],
+            rule: LifeRule::Conway,
+            palette: LifePalette::Neon,
+            ticks_per_beat: 2.0,
+            sensitivity: 1.0,
+            tick_progress: 0.0,
+            last_time: None,
+        }
+    }
+
+    /// Returns the index of a cell, wrapping coordinates around the edges
+    fn index(x: i32, y: i32) -> usize {
+        let x = x.rem_euclid(GRID_WIDTH as i32) as usize;
+        let y = y.rem_euclid(GRID_HEIGHT as i32) as usize;
+        y * GRID_WIDTH + x
+    }
+
+    /// Brings a cell to life unless it already is
+    fn spawn(&mut self, x: i32, y: i32) {
+        let cell = &mut self.cells[Self::index(x, y)];
+        if *cell == 0 {
+            *cell = 1;
+        }
+    }
+
+    /// Drops a glider with a random position and heading
+    fn inject_glider(&mut self) {
+        let x = random_range(0, GRID_WIDTH as i32);
+        let y = random_range(0, GRID_HEIGHT as i32);
+        let flip_x = if random::<bool>() { -1 } else { 1 };
+        let flip_y = if random::<bool>() { -1 } else { 1 };
+        for (dx, dy) in GLIDER {
+            self.spawn(x + dx * flip_x, y + dy * flip_y);
+        }
+    }
+
+    /// Sprinkles live cells into each band's strip according to its energy
+    fn seed_from_spectrum(&mut self, frame: &AudioFrame) {
+        let strip_width = GRID_WIDTH / BANDS;
+        let ratio = frame.nyquist() / LOWEST_BAND_FREQUENCY;
+
+        for band in 0..BANDS {
+            // Log-spaced bands, lowest on the left
+            let low = LOWEST_BAND_FREQUENCY * ratio.powf(band as f32 / BANDS as f32);
+            let high = LOWEST_BAND_FREQUENCY * ratio.powf((band + 1) as f32 / BANDS as f32);
+            let energy = frame.band_energy(low, high);
+            let seeds = (energy * energy * MAX_SEEDS_PER_BAND * self.sensitivity).round() as usize;
+
+            for _ in 0..seeds {
+                let x = band * strip_width + random_range(0, strip_width);
+                let y = random_range(0, GRID_HEIGHT);
+                self.spawn(x as i32, y as i32);
+            }
+        }
+    }
+
+    /// Advances the grid by one generation
+    fn step(&mut self) {
+        let (birth, survival) = self.rule.masks();
+
+        for y in 0..GRID_HEIGHT as i32 {
+            for x in 0..GRID_WIDTH as i32 {
+                let mut neighbours = 0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if (dx, dy) != (0, 0) && self.cells[Self::index(x + dx, y + dy)] > 0 {
+                            neighbours += 1;
+                        }
+                    }
+                }
+
+                let index = Self::index(x, y);
+                let age = self.cells[index];
+                self.next_cells[index] = if age > 0 && survival & (1 << neighbours) != 0 {
+                    age.saturating_add(1)
+                } else if age == 0 && birth & (1 << neighbours) != 0 {
+                    1
+                } else {
+                    0
+                };
+            }
+        }
+
+        std::mem::swap(&mut self.cells, &mut self.next_cells);
+    }
+}
+
+impl Visualizer for GameOfLife {
+    fn name(&self) -> &'static str {
+        "Game of Life"
+    }
+
+    /// Seeds the grid from the audio and runs as many generations as the
+    /// tempo calls for since the previous frame
+    fn update(&mut self, frame: &AudioFrame) {
+        let elapsed = self
+            .last_time
+            .map_or(0.0, |last| (frame.time - last).clamp(0.0, 0.25) as f32);
+        self.last_time = Some(frame.time);
+
+        if !frame.is_playing {
+            return;
+        }
+
+        if frame.onset.is_some() {
+            self.inject_glider();
+        }
+        self.seed_from_spectrum(frame);
+
+        let beats_per_second = frame.bpm.unwrap_or(DEFAULT_BPM) / 60.0;
+        self.tick_progress += elapsed * beats_per_second * self.ticks_per_beat;
+        while self.tick_progress >= 1.0 {
+            self.step();
+            self.tick_progress -= 1.0;
+        }
+    }
+
+    /// Draws every live cell as a colored square in a single mesh
+    fn draw(&self, draw: &Draw, rect: Rect) {
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgb(0.02, 0.02, 0.02));
+
+        let cell_width = rect.w() / GRID_WIDTH as f32;
+        let cell_height = rect.h() / GRID_HEIGHT as f32;
+        let mut triangles = Vec::new();
+
+        for (index, &age) in self.cells.iter().enumerate() {
+            if age == 0 {
+                continue;
+            }
+            let left = rect.left() + (index % GRID_WIDTH) as f32 * cell_width;
+            let bottom = rect.bottom() + (index / GRID_WIDTH) as f32 * cell_height;
+            let (right, top) = (left + cell_width, bottom + cell_height);
+            let color = self.palette.color(age);
+
+            triangles.extend(
+                [
+                    pt3(left, bottom, 0.0),
+                    pt3(right, bottom, 0.0),
+                    pt3(left, top, 0.0),
+                    pt3(left, top, 0.0),
+                    pt3(right, bottom, 0.0),
+                    pt3(right, top, 0.0),
+                ]
+                .into_iter()
+                .map(|corner| (corner, color)),
+            );
+        }
+
+        draw.mesh().tris_colored(triangles);
+    }
+
+    fn params(&self) -> Vec<VisualizerParam> {
+        let rule = LifeRule::ALL.iter().position(|&r| r == self.rule);
+        let palette = LifePalette::ALL.iter().position(|&p| p == self.palette);
+
+        vec![
+            VisualizerParam::choice("Rule", rule.unwrap_or(0), LifeRule::LABELS),
+            VisualizerParam::choice("Palette", palette.unwrap_or(0), LifePalette::LABELS),
+            VisualizerParam::numeric("Ticks/beat", self.ticks_per_beat, 1.0, 8.0, 1.0),
+            VisualizerParam::numeric("Sensitivity", self.sensitivity, 0.0, 3.0, 0.25),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        match name {
+            "Rule" => {
+                if let Some(&rule) = LifeRule::ALL.get(value as usize) {
+                    self.rule = rule;
+                }
+            }
+            "Palette" => {
+                if let Some(&palette) = LifePalette::ALL.get(value as usize) {
+                    self.palette = palette;
+                }
+            }
+            "Ticks/beat" => self.ticks_per_beat = value,
+            "Sensitivity" => self.sensitivity = value,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Visualizer module
+//!
+//! Defines the interface shared by every visual mode the `View` can display:
+//! - `Visualizer` trait for updating and drawing a mode
+//! - `AudioFrame` with the analysis data handed to a mode each frame
+//! - `VisualizerParam` for live-tweakable settings shown in the menu
+
+mod life;
+mod particles;
+mod spectrogram;
+mod status;
+
+pub use life::GameOfLife;
+pub use particles::Particles;
+pub use spectrogram::Spectrogram;
+pub use status::Status;
+
+use nannou::prelude::*;
+
+/// Level treated as silence when normalizing band energy, in dBFS
+const ENERGY_FLOOR_DB: f32 = -80.0;
+
+/// Analysis data for a single video frame
+pub struct AudioFrame<'a> {
+    /// Whether audio is currently playing
+    pub is_playing: bool,
+    /// Playback position in seconds
+    pub time: f64,
+    /// Magnitude spectrum from 0 Hz to Nyquist in dBFS
+    pub spectrum: &'a [f32],
+    /// Sample rate of the analyzed audio in Hz
+    pub sample_rate: u32,
+    /// Strength of an onset detected this frame (1.0 = just above threshold)
+    pub onset: Option<f32>,
+    /// Whether a beat fell in this frame
+    pub beat: bool,
+    /// Current tempo estimate in beats per minute
+    pub bpm: Option<f32>,
+}
+
+impl AudioFrame<'_> {
+    /// Returns the highest representable frequency in Hz
+    pub fn nyquist(&self) -> f32 {
+        self.sample_rate as f32 / 2.0
+    }
+
+    /// Returns the average level of a frequency band
+    ///
+    /// # Arguments
+    /// * `low` - Bottom of the band in Hz
+    /// * `high` - Top of the band in Hz
+    ///
+    /// # Returns
+    /// Level from 0.0 (at or below `ENERGY_FLOOR_DB`) to 1.0 (full scale)
+    pub fn band_energy(&self, low: f32, high: f32) -> f32 {
+        if self.spectrum.is_empty() {
+            return 0.0;
+        }
+
+        let bins_per_hz = self.spectrum.len() as f32 / self.nyquist();
+        let last_bin = self.spectrum.len() - 1;
+        let first = ((low * bins_per_hz) as usize).min(last_bin);
+        let last = ((high * bins_per_hz) as usize).clamp(first, last_bin);
+        let band = &self.spectrum[first..=last];
+
+        let average_db = band.iter().sum::<f32>() / band.len() as f32;
+        (1.0 - average_db / ENERGY_FLOOR_DB).clamp(0.0, 1.0)
+    }
+}
+
+/// A live-tweakable visualizer setting
+///
+/// Enumerated settings (like a color map) use whole-number values that index
+/// into `labels`.
+#[derive(Clone, Debug)]
+pub struct VisualizerParam {
+    /// Display name, also used to identify the parameter in `set_param`
+    pub name: &'static str,
+    /// Current value
+    pub value: f32,
+    /// Smallest allowed value
+    pub min: f32,
+    /// Largest allowed value
+    pub max: f32,
+    /// Interval menu sliders snap to, and the change per mouse wheel notch
+    pub step: f32,
+    /// Names for enumerated values (empty for numeric parameters)
+    pub labels: &'static [&'static str],
+}
+
+impl VisualizerParam {
+    /// Creates a numeric parameter
+    pub fn numeric(name: &'static str, value: f32, min: f32, max: f32, step: f32) -> Self {
+        VisualizerParam {
+            name,
+            value,
+            min,
+            max,
+            step,
+            labels: &[],
+        }
+    }
+
+    /// Creates an enumerated parameter whose value indexes into `labels`
+    pub fn choice(name: &'static str, index: usize, labels: &'static [&'static str]) -> Self {
+        VisualizerParam {
+            name,
+            value: index as f32,
+            min: 0.0,
+            max: labels.len().saturating_sub(1) as f32,
+            step: 1.0,
+            labels,
+        }
+    }
+
+    /// Returns the value after moving `steps` steps, clamped to the valid range
+    ///
+    /// Enumerated parameters wrap around instead of clamping.
+    pub fn stepped(&self, steps: i32) -> f32 {
+        if self.labels.is_empty() {
+            (self.value + self.step * steps as f32).clamp(self.min, self.max)
+        } else {
+            let count = self.labels.len() as i32;
+            (self.value as i32 + steps).rem_euclid(count) as f32
+        }
+    }
+
+    /// Formats the current value for display
+    pub fn display_value(&self) -> String {
+        if let Some(label) = self.labels.get(self.value as usize) {
+            label.to_string()
+        } else if self.step >= 1.0 {
+            format!("{:.0}", self.value)
+        } else {
+            format!("{:.2}", self.value)
+        }
+    }
+}
+
+/// A visual mode that reacts to the music
+pub trait Visualizer {
+    /// Returns the name shown in the menu
+    fn name(&self) -> &'static str;
+
+    /// Advances the visualizer by one frame
+    ///
+    /// # Arguments
+    /// * `frame` - Analysis data for the current frame
+    fn update(&mut self, frame: &AudioFrame);
+
+    /// Renders the visualizer
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `rect` - Area the visualizer should fill
+    fn draw(&self, draw: &Draw, rect: Rect);
+
+    /// Returns the visualizer's tweakable parameters
+    fn params(&self) -> Vec<VisualizerParam> {
+        Vec::new()
+    }
+
+    /// Changes a parameter by name (unknown names are ignored)
+    ///
+    /// # Arguments
+    /// * `name` - Parameter name as reported by `params`
+    /// * `value` - New value, already clamped to the parameter's range
+    fn set_param(&mut self, _name: &str, _value: f32) {}
+}
@@ -0,0 +1,301 @@
+//! Audio-reactive particle system visualizer
+//!
+//! Emits particles from the center of the view and lets them drift around a
+//! set of orbiting attractors:
+//! - Bass energy drives the emission rate and particle size
+//! - Mid energy drives the launch speed
+//! - Treble energy shifts the color
+//! - Beats fire an extra burst of particles
+//!
+//! A hard particle budget keeps the frame rate stable, and everything
+//! (including trail length and attractor pull) is a live visualizer parameter.
+
+use super::{AudioFrame, Visualizer, VisualizerParam};
+use nannou::prelude::*;
+
+/// Longest trail a particle can leave, in recorded positions
+const MAX_TRAIL_LENGTH: usize = 16;
+/// Longest time step simulated in one update, in seconds
+const MAX_TIME_STEP: f32 = 0.05;
+/// Distance over which attractor pull is softened to avoid singularities
+const ATTRACTOR_SOFTENING: f32 = 0.05;
+/// Orbit radius of the attractors in normalized units
+const ATTRACTOR_ORBIT: f32 = 0.5;
+/// Fraction of velocity kept per second (simple drag)
+const DRAG_PER_SECOND: f32 = 0.6;
+/// Upper edge of the bass band in Hz
+const BASS_CUTOFF: f32 = 250.0;
+/// Upper edge of the mid band in Hz
+const MID_CUTOFF: f32 = 2000.0;
+
+/// A single particle, in normalized coordinates where the view spans -1.0 to 1.0
+struct Particle {
+    /// Current position
+    position: Vec2,
+    /// Velocity in units per second
+    velocity: Vec2,
+    /// Seconds since emission
+    age: f32,
+    /// Seconds until the particle disappears
+    lifetime: f32,
+    /// Hue from 0.0 to 1.0
+    hue: f32,
+    /// Radius in pixels
+    size: f32,
+    /// Previous positions, most recent first
+    trail: [Vec2; MAX_TRAIL_LENGTH],
+    /// Number of valid entries in `trail`
+    trail_len: usize,
+}
+
+/// Particle system driven by band energies and beats
+pub struct Particles {
+    /// Live particles, never more than `budget`
+    particles: Vec<Particle>,
+    /// Fractional particles carried over between frames
+    emission_carry: f32,
+    /// Phase of the attractor orbit in radians
+    attractor_phase: f32,
+    /// Playback time of the previous update, in seconds
+    last_time: Option<f64>,
+    /// Most recent bass energy, kept for drawing
+    bass: f32,
+    /// Maximum number of particles alive at once
+    budget: f32,
+    /// Particles per second at full bass energy
+    emission: f32,
+    /// Launch speed multiplier
+    speed: f32,
+    /// Particle size multiplier
+    size: f32,
+    /// Number of recorded trail positions
+    trail_length: f32,
+    /// Number of attractors
+    attractors: f32,
+    /// Strength of attractor pull
+    gravity: f32,
+    /// Particles emitted on every beat
+    beat_burst: f32,
+}
+
+impl Particles {
+    /// Creates an empty particle system with default settings
+    pub fn new() -> Self {
+        Particles {
+            particles: Vec::new(),
+            emission_carry: 0.0,
+            attractor_phase: 0.0,
+            last_time: None,
+            bass: 0.0,
+            budget: 1500.0,
+            emission: 400.0,
+            speed: 1.0,
+            size: 1.0,
+            trail_length: 8.0,
+            attractors: 2.0,
+            gravity: 0.5,
+            beat_burst: 60.0,
+        }
+    }
+
+    /// Returns the current attractor positions
+    fn attractor_positions(&self) -> Vec<Vec2> {
+        let count = self.attractors as usize;
+        (0..count)
+            .map(|i| {
+                let angle = self.attractor_phase + TAU * i as f32 / count as f32;
+                vec2(angle.cos(), angle.sin()) * ATTRACTOR_ORBIT
+            })
+            .collect()
+    }
+
+    /// Emits particles from the center, stopping at the budget
+    ///
+    /// # Arguments
+    /// * `count` - Number of particles to emit
+    /// * `mid` - Mid band energy, scaling launch speed
+    /// * `treble` - Treble band energy, shifting the hue
+    fn emit(&mut self, count: usize, mid: f32, treble: f32) {
+        let room = (self.budget as usize).saturating_sub(self.particles.len());
+        for _ in 0..count.min(room) {
+            let angle = random_range(0.0, TAU);
+            let speed = self.speed * (0.2 + 0.8 * mid) * random_range(0.5, 1.0);
+            self.particles.push(Particle {
+                position: Vec2::ZERO,
+                velocity: vec2(angle.cos(), angle.sin()) * speed,
+                age: 0.0,
+                lifetime: random_range(1.5, 3.5),
+                hue: (0.6 - 0.5 * treble + random_range(-0.05, 0.05)).rem_euclid(1.0),
+                size: self.size * (2.0 + 6.0 * self.bass) * random_range(0.7, 1.3),
+                trail: [Vec2::ZERO; MAX_TRAIL_LENGTH],
+                trail_len: 0,
+            });
+        }
+    }
+
+    /// Moves every particle and removes the ones that expired or left the view
+    fn simulate(&mut self, dt: f32) {
+        let attractors = self.attractor_positions();
+        let trail_length = self.trail_length as usize;
+        let drag = DRAG_PER_SECOND.powf(dt);
+
+        for particle in &mut self.particles {
+            for attractor in &attractors {
+                let offset = *attractor - particle.position;
+                let distance_squared = offset.length_squared() + ATTRACTOR_SOFTENING;
+                particle.velocity +=
+                    offset / distance_squared.sqrt() * self.gravity * dt / distance_squared;
+            }
+            particle.velocity *= drag;
+
+            // Record the old position at the front of the trail
+            particle.trail.copy_within(0..MAX_TRAIL_LENGTH - 1, 1);
+            particle.trail[0] = particle.position;
+            particle.trail_len = (particle.trail_len + 1).min(trail_length);
+
+            particle.position += particle.velocity * dt;
+            particle.age += dt;
+        }
+
+        self.particles.retain(|particle| {
+            particle.age < particle.lifetime
+                && particle.position.x.abs() <= 1.2
+                && particle.position.y.abs() <= 1.2
+        });
+    }
+}
+
+impl Visualizer for Particles {
+    fn name(&self) -> &'static str {
+        "Particles"
+    }
+
+    fn update(&mut self, frame: &AudioFrame) {
+        let dt = self
+            .last_time
+            .map_or(0.0, |last| (frame.time - last) as f32)
+            .clamp(0.0, MAX_TIME_STEP);
+        self.last_time = Some(frame.time);
+
+        if !frame.is_playing {
+            return;
+        }
+
+        self.bass = frame.band_energy(20.0, BASS_CUTOFF);
+        let mid = frame.band_energy(BASS_CUTOFF, MID_CUTOFF);
+        let treble = frame.band_energy(MID_CUTOFF, frame.nyquist());
+
+        self.emission_carry += self.emission * self.bass * self.bass * dt;
+        let mut count = self.emission_carry as usize;
+        self.emission_carry -= count as f32;
+        if frame.beat {
+            count += self.beat_burst as usize;
+        }
+
+        self.attractor_phase = (self.attractor_phase + dt * (0.3 + mid)).rem_euclid(TAU);
+        self.emit(count, mid, treble);
+        self.simulate(dt);
+    }
+
+    /// Draws all particles and their trails as one mesh
+    ///
+    /// Trails are tapered quads that fade out towards their tail; particle
+    /// heads are small squares. Attractors are drawn as faint rings.
+    fn draw(&self, draw: &Draw, rect: Rect) {
+        draw.rect()
+            .xy(rect.xy())
+            .wh(rect.wh())
+            .color(rgb(0.0, 0.0, 0.02));
+
+        let half = rect.wh() / 2.0;
+        let to_screen = |p: Vec2| rect.xy() + p * half;
+        let mut triangles = Vec::new();
+
+        for particle in &self.particles {
+            let fade = 1.0 - particle.age / particle.lifetime;
+            let color = hsla(particle.hue, 0.9, 0.6, fade);
+            let head = to_screen(particle.position);
+
+            // Trail segments from the head backwards, narrowing as they age
+            let mut previous = head;
+            for (i, &point) in particle.trail[..particle.trail_len].iter().enumerate() {
+                let point = to_screen(point);
+                let taper = 1.0 - (i + 1) as f32 / (particle.trail_len + 1) as f32;
+                let normal =
+                    (point - previous).perp().normalize_or_zero() * particle.size * 0.5 * taper;
+                let tail_color = hsla(particle.hue, 0.9, 0.6, fade * taper);
+                triangles.extend([
+                    (previous + normal, color),
+                    (previous - normal, color),
+                    (point + normal, tail_color),
+                    (point + normal, tail_color),
+                    (previous - normal, color),
+                    (point - normal, tail_color),
+                ]);
+                previous = point;
+            }
+
+            let r = particle.size / 2.0;
+            triangles.extend([
+                (head + vec2(-r, -r), color),
+                (head + vec2(r, -r), color),
+                (head + vec2(-r, r), color),
+                (head + vec2(-r, r), color),
+                (head + vec2(r, -r), color),
+                (head + vec2(r, r), color),
+            ]);
+        }
+
+        draw.mesh().tris_colored(
+            triangles
+                .into_iter()
+                .map(|(point, color)| (point.extend(0.0), color)),
+        );
+
+        for attractor in self.attractor_positions() {
+            draw.ellipse()
+                .xy(to_screen(attractor))
+                .radius(6.0 + 10.0 * self.bass)
+                .no_fill()
+                .stroke(rgba(1.0, 1.0, 1.0, 0.3))
+                .stroke_weight(1.5);
+        }
+    }
+
+    fn params(&self) -> Vec<VisualizerParam> {
+        vec![
+            VisualizerParam::numeric("Budget", self.budget, 100.0, 5000.0, 100.0),
+            VisualizerParam::numeric("Emission", self.emission, 0.0, 2000.0, 50.0),
+            VisualizerParam::numeric("Speed", self.speed, 0.1, 3.0, 0.1),
+            VisualizerParam::numeric("Size", self.size, 0.25, 4.0, 0.25),
+            VisualizerParam::numeric(
+                "Trail",
+                self.trail_length,
+                0.0,
+                MAX_TRAIL_LENGTH as f32,
+                1.0,
+            ),
+            VisualizerParam::numeric("Attractors", self.attractors, 0.0, 6.0, 1.0),
+            VisualizerParam::numeric("Gravity", self.gravity, 0.0, 3.0, 0.1),
+            VisualizerParam::numeric("Beat burst", self.beat_burst, 0.0, 300.0, 10.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        match name {
+            "Budget" => {
+                self.budget = value;
+                // Enforce a lowered budget right away
+                self.particles.truncate(value as usize);
+            }
+            "Emission" => self.emission = value,
+            "Speed" => self.speed = value,
+            "Size" => self.size = value,
+            "Trail" => self.trail_length = value,
+            "Attractors" => self.attractors = value,
+            "Gravity" => self.gravity = value,
+            "Beat burst" => self.beat_burst = value,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Scrolling spectrogram (waterfall) visualizer
+//!
+//! Draws a time/frequency heatmap that scrolls from right to left:
+//! - Time runs along the x axis, newest column on the right
+//! - Frequency runs along the y axis on a linear, log or mel scale
+//! - Loudness maps to color through a selectable color map
+//!
+//! History lives in a fixed-size ring buffer, so memory use does not depend on
+//! the length of the track.
+
+use super::{AudioFrame, Visualizer, VisualizerParam};
+use crate::analysis::SILENCE_DB;
+use nannou::prelude::*;
+
+/// Number of time columns kept in the history ring buffer
+const HISTORY_COLUMNS: usize = 256;
+/// Number of frequency rows per column
+const FREQUENCY_ROWS: usize = 128;
+/// Lowest frequency shown on the log scale, in Hz
+const MIN_LOG_FREQUENCY: f32 = 20.0;
+/// Smallest allowed gap between the dB floor and ceiling
+const MIN_DB_SPAN: f32 = 10.0;
+
+/// Color maps that translate a normalized level into a color
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorMap {
+    Magma,
+    Viridis,
+    Grayscale,
+}
+
+impl ColorMap {
+    /// All color maps in menu order
+    const ALL: [ColorMap; 3] = [ColorMap::Magma, ColorMap::Viridis, ColorMap::Grayscale];
+    /// Menu labels matching `ALL`
+    const LABELS: &'static [&'static str] = &["Magma", "Viridis", "Grayscale"];
+
+    /// Evenly spaced control points of the magma palette
+    const MAGMA: [[u8; 3]; 9] = [
+        [0x00, 0x00, 0x04],
+        [0x1c, 0x10, 0x44],
+        [0x4f, 0x12, 0x7b],
+        [0x81, 0x25, 0x81],
+        [0xb5, 0x36, 0x7a],
+        [0xe5, 0x50, 0x64],
+        [0xfb, 0x87, 0x61],
+        [0xfe, 0xc2, 0x87],
+        [0xfc, 0xfd, 0xbf],
+    ];
+    /// Evenly spaced control points of the viridis palette
+    const VIRIDIS: [[u8; 3]; 9] = [
+        [0x44, 0x01, 0x54],
+        [0x47, 0x2d, 0x7b],
+        [0x3b, 0x52, 0x8b],
+        [0x2c, 0x72, 0x8e],
+        [0x21, 0x91, 0x8c],
+        [0x28, 0xae, 0x80],
+        [0x5e, 0xc9, 0x62],
+        [0xad, 0xdc, 0x30],
+        [0xfd, 0xe7, 0x25],
+    ];
+
+    /// Returns the color for a level
+    ///
+    /// # Arguments
+    /// * `level` - Normalized level from 0.0 (quiet) to 1.0 (loud)
+    fn color(self, level: f32) -> Rgba {
+        let level = level.clamp(0.0, 1.0);
+        let stops = match self {
+            ColorMap::Magma => &Self::MAGMA,
+            ColorMap::Viridis => &Self::VIRIDIS,
+            ColorMap::Grayscale => return rgba(level, level, level, 1.0),
+        };
+
+        // Linearly interpolate between the two surrounding control points
+        let position = level * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let t = position - index as f32;
+        let channel = |c: usize| {
+            let a = stops[index][c] as f32 / 255.0;
+            let b = stops[index + 1][c] as f32 / 255.0;
+            a + (b - a) * t
+        };
+
+        rgba(channel(0), channel(1), channel(2), 1.0)
+    }
+}
+
+/// Mappings from vertical position to frequency
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrequencyScale {
+    Linear,
+    Log,
+    Mel,
+}
+
+impl FrequencyScale {
+    /// All scales in menu order
+    const ALL: [FrequencyScale; 3] = [
+        FrequencyScale::Linear,
+        FrequencyScale::Log,
+        FrequencyScale::Mel,
+    ];
+    /// Menu labels matching `ALL`
+    const LABELS: &'static [&'static str] = &["Linear", "Log", "Mel"];
+
+    /// Returns the frequency at a vertical position
+    ///
+    /// # Arguments
+    /// * `t` - Normalized position from 0.0 (bottom) to 1.0 (top)
+    /// * `nyquist` - Highest representable frequency in Hz
+    fn frequency(self, t: f32, nyquist: f32) -> f32 {
+        match self {
+            FrequencyScale::Linear => t * nyquist,
+            FrequencyScale::Log => MIN_LOG_FREQUENCY * (nyquist / MIN_LOG_FREQUENCY).powf(t),
+            FrequencyScale::Mel => mel_to_hz(t * hz_to_mel(nyquist)),
+        }
+    }
+}
+
+/// Converts a frequency in Hz to the mel scale
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+/// Converts a mel value back to Hz
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Scrolling time/frequency heatmap
+pub struct Spectrogram {
+    /// Ring buffer of columns, each `FREQUENCY_ROWS` levels in dBFS (bottom row first)
+    history: Vec<f32>,
+    /// Column that the next update will overwrite (also the oldest column)
+    next_column: usize,
+    /// Palette used to color levels
+    color_map: ColorMap,
+    /// Mapping from row to frequency
+    scale: FrequencyScale,
+    /// Level drawn with the coldest color, in dBFS
+    min_db: f32,
+    /// Level drawn with the hottest color, in dBFS
+    max_db: f32,
+    /// Nyquist frequency of the last analyzed frame, used for axis labels
+    nyquist: f32,
+}
+
+impl Spectrogram {
+    /// Creates a spectrogram with an empty (silent) history
+    pub fn new() -> Self {
+        Spectrogram {
+            history: vec![SILENCE_DB; HISTORY_COLUMNS * FREQUENCY_ROWS],
+            next_column: 0,
+            color_map: ColorMap::Magma,
+            scale: FrequencyScale::Log,
+            min_db: -90.0,
+            max_db: -10.0,
+            nyquist: 22_050.0,
+        }
+    }
+
+    /// Returns the level of a frequency, interpolating between bins
+    ///
+    /// # Arguments
+    /// * `frame` - Analysis data holding the spectrum
+    /// * `low` - Bottom of the row's frequency band in Hz
+    /// * `high` - Top of the row's frequency band in Hz
+    ///
+    /// # Returns
+    /// The loudest bin inside the band, or an interpolated value when the band
+    /// is narrower than a single bin
+    fn band_level(frame: &AudioFrame, low: f32, high: f32) -> f32 {
+        let spectrum = frame.spectrum;
+        let bins_per_hz = spectrum.len() as f32 / frame.nyquist();
+        let last_bin = spectrum.len() - 1;
+        let first = (low * bins_per_hz).max(0.0);
+        let end = (high * bins_per_hz).min(last_bin as f32);
+
+        if end - first >= 1.0 {
+            spectrum[first.ceil() as usize..=end.floor() as usize]
+                .iter()
+                .copied()
+                .fold(SILENCE_DB, f32::max)
+        } else {
+            let center = ((first + end) / 2.0).min(last_bin as f32);
+            let index = (center as usize).min(last_bin.saturating_sub(1));
+            let t = center - index as f32;
+            let next = spectrum[(index + 1).min(last_bin)];
+            spectrum[index] + (next - spectrum[index]) * t
+        }
+    }
+
+    /// Clears the history, used when the frequency axis changes
+    fn clear(&mut self) {
+        self.history.fill(SILENCE_DB);
+    }
+
+    /// Returns the normalized level of a history cell
+    fn level(&self, column: usize, row: usize) -> f32 {
+        let db = self.history[column * FREQUENCY_ROWS + row];
+        (db - self.min_db) / (self.max_db - self.min_db)
+    }
+}
+
+impl Visualizer for Spectrogram {
+    fn name(&self) -> &'static str {
+        "Spectrogram"
+    }
+
+    /// Appends one column to the history while audio is playing
+    fn update(&mut self, frame: &AudioFrame) {
+        if !frame.is_playing || frame.spectrum.is_empty() {
+            return;
+        }
+
+        self.nyquist = frame.nyquist();
+
+        let start = self.next_column * FREQUENCY_ROWS;
+        for row in 0..FREQUENCY_ROWS {
+            let low = self
+                .scale
+                .frequency(row as f32 / FREQUENCY_ROWS as f32, self.nyquist);
+            let high = self
+                .scale
+                .frequency((row + 1) as f32 / FREQUENCY_ROWS as f32, self.nyquist);
+            self.history[start + row] = Self::band_level(frame, low, high);
+        }
+
+        self.next_column = (self.next_column + 1) % HISTORY_COLUMNS;
+    }
+
+    /// Draws the history as a single colored mesh, oldest column on the left,
+    /// followed by frequency labels along the left edge
+    fn draw(&self, draw: &Draw, rect: Rect) {
+        let column_step = rect.w() / (HISTORY_COLUMNS - 1) as f32;
+        let row_step = rect.h() / (FREQUENCY_ROWS - 1) as f32;
+
+        let mut vertices = Vec::with_capacity(HISTORY_COLUMNS * FREQUENCY_ROWS);
+        for x in 0..HISTORY_COLUMNS {
+            let column = (self.next_column + x) % HISTORY_COLUMNS;
+            for row in 0..FREQUENCY_ROWS {
+                let point = pt3(
+                    rect.left() + x as f32 * column_step,
+                    rect.bottom() + row as f32 * row_step,
+                    0.0,
+                );
+                vertices.push((point, self.color_map.color(self.level(column, row))));
+            }
+        }
+
+        // Two triangles per grid cell
+        let mut indices = Vec::with_capacity((HISTORY_COLUMNS - 1) * (FREQUENCY_ROWS - 1) * 6);
+        for x in 0..HISTORY_COLUMNS - 1 {
+            for row in 0..FREQUENCY_ROWS - 1 {
+                let bottom_left = x * FREQUENCY_ROWS + row;
+                let bottom_right = bottom_left + FREQUENCY_ROWS;
+                indices.extend_from_slice(&[
+                    bottom_left,
+                    bottom_right,
+                    bottom_left + 1,
+                    bottom_left + 1,
+                    bottom_right,
+                    bottom_right + 1,
+                ]);
+            }
+        }
+
+        draw.mesh().indexed_colored(vertices, indices);
+
+        // Label a few frequencies so the axis scale is readable
+        for t in [0.25, 0.5, 0.75] {
+            let hz = self.scale.frequency(t, self.nyquist);
+            let label = if hz >= 1000.0 {
+                format!("{:.1} kHz", hz / 1000.0)
+            } else {
+                format!("{:.0} Hz", hz)
+            };
+            draw.text(&label)
+                .xy(pt2(rect.left() + 40.0, rect.bottom() + t * rect.h()))
+                .color(WHITE)
+                .font_size(12);
+        }
+    }
+
+    fn params(&self) -> Vec<VisualizerParam> {
+        let color_map = ColorMap::ALL.iter().position(|&c| c == self.color_map);
+        let scale = FrequencyScale::ALL.iter().position(|&s| s == self.scale);
+
+        vec![
+            VisualizerParam::choice("Colors", color_map.unwrap_or(0), ColorMap::LABELS),
+            VisualizerParam::choice("Scale", scale.unwrap_or(0), FrequencyScale::LABELS),
+            VisualizerParam::numeric("Floor dB", self.min_db, SILENCE_DB, -20.0, 5.0),
+            VisualizerParam::numeric("Ceiling dB", self.max_db, -60.0, 0.0, 5.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) {
+        match name {
+            "Colors" => {
+                if let Some(&color_map) = ColorMap::ALL.get(value as usize) {
+                    self.color_map = color_map;
+                }
+            }
+            "Scale" => {
+                if let Some(&scale) = FrequencyScale::ALL.get(value as usize)
+                    && scale != self.scale
+                {
+                    self.scale = scale;
+                    self.clear();
+                }
+            }
+            // Keep the floor below the ceiling so levels stay normalizable
+            "Floor dB" => self.min_db = value.min(self.max_db - MIN_DB_SPAN),
+            "Ceiling dB" => self.max_db = value.max(self.min_db + MIN_DB_SPAN),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Playback status visualizer
+//!
+//! The original display: a green background while playing and a red one while
+//! paused, with a centered status label. The background pulses on beats (and
+//! more gently on onsets) and the current tempo is shown below the label.
+
+use super::{AudioFrame, Visualizer};
+use nannou::prelude::*;
+
+/// Fraction of the beat pulse that remains after each frame
+const PULSE_DECAY: f32 = 0.85;
+
+/// Shows whether audio is playing through color and text
+#[derive(Default)]
+pub struct Status {
+    /// Current playback state (true when audio is playing)
+    is_playing: bool,
+    /// Brightness boost from the last beat, decaying towards 0.0
+    pulse: f32,
+    /// Current tempo estimate
+    bpm: Option<f32>,
+}
+
+impl Visualizer for Status {
+    fn name(&self) -> &'static str {
+        "Status"
+    }
+
+    fn update(&mut self, frame: &AudioFrame) {
+        self.is_playing = frame.is_playing;
+        self.bpm = frame.bpm;
+        self.pulse = if frame.beat {
+            1.0
+        } else if let Some(strength) = frame.onset {
+            // Off-beat onsets give a smaller kick
+            self.pulse.max(0.25 * strength.min(2.0))
+        } else {
+            self.pulse * PULSE_DECAY
+        };
+    }
+
+    /// Draws:
+    /// - Background rectangle with state-appropriate color
+    ///   - Green with 80% opacity when playing, brightening on beats
+    ///   - Red with 80% opacity when paused
+    /// - Centered status text
+    ///   - "PLAYING" when active
+    ///   - "PAUSED" when inactive
+    /// - Tempo below the status text once it is known
+    fn draw(&self, draw: &Draw, rect: Rect) {
+        // Set color based on playback state
+        let bg_color = if self.is_playing {
+            rgba(0.0, 0.5 + 0.4 * self.pulse, 0.0, 0.8) // Green when playing
+        } else {
+            rgba(0.5, 0.0, 0.0, 0.8) // Red when paused
+        };
+
+        // Draw background
+        draw.rect().xy(rect.xy()).wh(rect.wh()).color(bg_color);
+
+        // Add status text overlay
+        let status_text = if self.is_playing { "PLAYING" } else { "PAUSED" };
+
+        draw.text(status_text)
+            .xy(rect.xy())
+            .color(WHITE)
+            .font_size(48);
+
+        if let Some(bpm) = self.bpm {
+            draw.text(&format!("{:.0} BPM", bpm))
+                .xy(pt2(rect.x(), rect.y() - 50.0))
+                .color(WHITE)
+                .font_size(24);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Master volume, mute and balance
+//!
+//! The settings live in atomics so the menu can change them while the audio
+//! callback reads them, without locking. The callback turns them into a gain
+//! per output channel and ramps towards new gains over one buffer, so changes
+//! never click.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Quietest volume in dB; anything at or below it is silence
+pub const MIN_VOLUME_DB: f32 = -60.0;
+/// Loudest volume in dB
+pub const MAX_VOLUME_DB: f32 = 0.0;
+
+/// Output volume settings shared between the UI and the audio callback
+pub struct VolumeControl {
+    /// Master volume in dB, stored as `f32` bits
+    volume_db: AtomicU32,
+    /// True while the output is muted
+    muted: AtomicBool,
+    /// Left/right balance from -1.0 (left only) to 1.0 (right only), as `f32` bits
+    balance: AtomicU32,
+}
+
+impl VolumeControl {
+    /// Creates volume settings
+    ///
+    /// # Arguments
+    /// * `volume_db` - Master volume in dB
+    /// * `muted` - Whether the output starts muted
+    /// * `balance` - Left/right balance from -1.0 to 1.0
+    pub fn new(volume_db: f32, muted: bool, balance: f32) -> Self {
+        let control = VolumeControl {
+            volume_db: AtomicU32::new(0),
+            muted: AtomicBool::new(muted),
+            balance: AtomicU32::new(0),
+        };
+        control.set_volume_db(volume_db);
+        control.set_balance(balance);
+        control
+    }
+
+    /// Returns the master volume in dB
+    pub fn volume_db(&self) -> f32 {
+        f32::from_bits(self.volume_db.load(Ordering::Relaxed))
+    }
+
+    /// Sets the master volume, clamped to `MIN_VOLUME_DB..=MAX_VOLUME_DB`
+    pub fn set_volume_db(&self, volume_db: f32) {
+        let volume_db = volume_db.clamp(MIN_VOLUME_DB, MAX_VOLUME_DB);
+        self.volume_db.store(volume_db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns whether the output is muted
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    /// Mutes or unmutes the output
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Returns the left/right balance from -1.0 (left only) to 1.0 (right only)
+    pub fn balance(&self) -> f32 {
+        f32::from_bits(self.balance.load(Ordering::Relaxed))
+    }
+
+    /// Sets the left/right balance, clamped to -1.0..=1.0
+    pub fn set_balance(&self, balance: f32) {
+        let balance = balance.clamp(-1.0, 1.0);
+        self.balance.store(balance.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Computes the linear gains the current settings call for
+    ///
+    /// The balance only ever turns one side down, so the centered position
+    /// plays both sides at full volume.
+    ///
+    /// # Returns
+    /// `[left, right, other]` gains, where `other` is used for mono output
+    /// and for channels beyond the first two
+    pub fn channel_gains(&self) -> [f32; 3] {
+        let volume_db = self.volume_db();
+        let master = if self.is_muted() || volume_db <= MIN_VOLUME_DB {
+            0.0
+        } else {
+            10f32.powf(volume_db / 20.0)
+        };
+        let balance = self.balance();
+        [
+            master * (1.0 - balance).min(1.0),
+            master * (1.0 + balance).min(1.0),
+            master,
+        ]
+    }
+}
+
+/// Applies channel gains to an interleaved output buffer
+///
+/// Gains ramp linearly from `current` to `target` across the buffer, and
+/// `current` is updated to `target` afterwards.
+///
+/// # Arguments
+/// * `data` - Interleaved output samples
+/// * `channels` - Number of interleaved output channels
+/// * `current` - Gains applied at the end of the previous buffer
+/// * `target` - Gains to reach by the end of this buffer
+pub fn apply_gains(data: &mut [f32], channels: usize, current: &mut [f32; 3], target: [f32; 3]) {
+    let channels = channels.max(1);
+    let frames = (data.len() / channels).max(1) as f32;
+
+    for (frame_index, frame) in data.chunks_mut(channels).enumerate() {
+        let progress = (frame_index + 1) as f32 / frames;
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            let slot = match (channels, channel) {
+                (1, _) => 2,
+                (_, 0) => 0,
+                (_, 1) => 1,
+                _ => 2,
+            };
+            let gain = current[slot] + (target[slot] - current[slot]) * progress;
+            *sample *= gain;
+        }
+    }
+
+    *current = target;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_gains(actual: [f32; 3], expected: [f32; 3]) {
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn balance_turns_one_side_down() {
+        let control = VolumeControl::new(-6.0, false, 0.0);
+        let master = 10f32.powf(-6.0 / 20.0);
+        assert_gains(control.channel_gains(), [master, master, master]);
+
+        control.set_balance(-0.25);
+        assert_gains(control.channel_gains(), [master, 0.75 * master, master]);
+        control.set_balance(1.0);
+        assert_gains(control.channel_gains(), [0.0, master, master]);
+        // Out-of-range settings are clamped
+        control.set_balance(3.0);
+        assert_eq!(control.balance(), 1.0);
+        control.set_volume_db(12.0);
+        assert_gains(control.channel_gains(), [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn mute_and_the_lowest_volume_are_silent() {
+        let control = VolumeControl::new(0.0, true, 0.5);
+        assert_gains(control.channel_gains(), [0.0; 3]);
+        control.set_muted(false);
+        control.set_volume_db(MIN_VOLUME_DB);
+        assert_gains(control.channel_gains(), [0.0; 3]);
+
+        let mut data = vec![0.5; 64];
+        let mut current = [0.0; 3];
+        apply_gains(&mut data, 2, &mut current, control.channel_gains());
+        assert!(data.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn gains_ramp_to_their_target_without_a_step() {
+        let mut data = vec![1.0; 2 * 100];
+        let mut current = [0.0, 1.0, 1.0];
+        apply_gains(&mut data, 2, &mut current, [1.0, 0.5, 1.0]);
+        assert_eq!(current, [1.0, 0.5, 1.0]);
+
+        let left: Vec<f32> = data.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = data.iter().skip(1).step_by(2).copied().collect();
+        // Each frame moves an equal step; the last one reaches the target
+        for pair in left.windows(2) {
+            assert!((pair[1] - pair[0] - 0.01).abs() < 1e-5);
+        }
+        for pair in right.windows(2) {
+            assert!((pair[0] - pair[1] - 0.005).abs() < 1e-5);
+        }
+        assert_eq!((left[99], right[99]), (1.0, 0.5));
+
+        // The next buffer carries on where this one ended
+        let mut data = vec![1.0; 2 * 100];
+        apply_gains(&mut data, 2, &mut current, [1.0, 0.5, 1.0]);
+        assert!(data.chunks(2).all(|frame| frame == [1.0, 0.5]));
+    }
+
+    #[test]
+    fn mono_and_extra_channels_use_the_master_gain() {
+        let target = [0.25, 0.5, 0.75];
+        let mut current = target;
+        let mut mono = vec![1.0; 4];
+        apply_gains(&mut mono, 1, &mut current, target);
+        assert!(mono.iter().all(|&sample| sample == 0.75));
+
+        let mut surround = vec![1.0; 4 * 4];
+        apply_gains(&mut surround, 4, &mut current, target);
+        assert!(
+            surround
+                .chunks(4)
+                .all(|frame| frame == [0.25, 0.5, 0.75, 0.75])
+        );
+    }
+}